@@ -0,0 +1,248 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::bail;
+use sqlx::query;
+use tokio::{
+    fs::{metadata, remove_file, rename, try_exists},
+    task::spawn_blocking,
+};
+use tracing::{debug, trace, warn};
+use walkdir::WalkDir;
+
+use crate::{
+    Artifact,
+    artifact::ArtifactManager,
+    util::{mv, set_readonly},
+};
+
+/// Compression level for stored artifacts, favouring speed since most of them are already compressed jars.
+const LEVEL: i32 = 3;
+
+impl ArtifactManager {
+    /// Path of the stored file when compressed with zstd.
+    pub fn compressed_path(&self, blake3: &str) -> PathBuf {
+        self.storage_path(blake3).with_added_extension("zst")
+    }
+}
+
+fn encode(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut writer = BufWriter::new(File::create_new(dst)?);
+    zstd::stream::copy_encode(&mut reader, &mut writer, LEVEL)?;
+    writer.flush()
+}
+
+fn decode(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut writer = BufWriter::new(File::create(dst)?);
+    zstd::stream::copy_decode(&mut reader, &mut writer)?;
+    writer.flush()
+}
+
+/// Summary of [`ArtifactManager::compress`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompressStat {
+    /// Number of artifacts compressed.
+    pub count: usize,
+    /// Total size of the compressed artifacts before compression.
+    pub before: u64,
+    /// Total size of the compressed artifacts after compression.
+    pub after: u64,
+}
+
+impl ArtifactManager {
+    /// Compress stored artifacts that have not been accessed for `unused_for` with zstd.
+    ///
+    /// Compressed artifacts are decompressed again when retrieved.
+    /// Artifacts compressing poorly, e.g. jars, are left as they are,
+    /// and so are those deployed anywhere, which may link to the stored file.
+    pub async fn compress(&self, unused_for: Duration) -> anyhow::Result<CompressStat> {
+        let store = self.dir.join("artifact");
+
+        let mut stat = CompressStat::default();
+
+        if !try_exists(&store).await? {
+            return Ok(stat);
+        }
+
+        let threshold = SystemTime::now() - unused_for;
+
+        let deployed = self.deployed_roots(&[]).await?;
+
+        for entry in WalkDir::new(&store) {
+            let entry = entry?;
+            let path = entry.path();
+
+            if !entry.file_type().is_file() || path.extension().is_some() {
+                continue;
+            }
+
+            let Some(blake3) = path.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            if deployed.contains(blake3) {
+                trace!("not compressing deployed {blake3}");
+                continue;
+            }
+
+            let meta = metadata(path).await?;
+            // access time is not updated on some mounts, in which case fall back to modification time
+            let last_used = meta.accessed()?.max(meta.modified()?);
+            if last_used > threshold {
+                continue;
+            }
+
+            let Some(art) = self.get(blake3).await? else {
+                warn!("skipping unindexed file {}", path.display());
+                continue;
+            };
+
+            let part = path.with_added_extension("zst.part");
+            let (src, dst) = (path.to_owned(), part.clone());
+            spawn_blocking(move || encode(&src, &dst)).await??;
+
+            let len = metadata(&part).await?.len();
+
+            if len >= art.len * 9 / 10 {
+                trace!("not compressing {art}, which only shrinks to {len} bytes");
+                remove_file(&part).await?;
+                continue;
+            }
+
+            set_readonly(&part).await?;
+            self.apply_store_mode(&part).await?;
+            rename(&part, self.compressed_path(blake3)).await?;
+            self.set_stored_len(blake3, Some(len)).await?;
+            remove_file(path).await?;
+
+            debug!("compressed {art} from {} to {len} bytes", art.len);
+
+            stat.count += 1;
+            stat.before += art.len;
+            stat.after += len;
+        }
+
+        Ok(stat)
+    }
+
+    /// Restore a compressed artifact to its storage path, returning whether it was compressed.
+    pub(super) async fn decompress(&self, art: &Artifact) -> anyhow::Result<bool> {
+        let compressed = self.compressed_path(&art.blake3);
+
+        if !try_exists(&compressed).await? {
+            return Ok(false);
+        }
+
+        debug!("decompressing {art}");
+
+        let part = self.storage_path(&art.blake3).with_added_extension("part");
+        let (src, dst) = (compressed.clone(), part.clone());
+        spawn_blocking(move || decode(&src, &dst)).await??;
+
+        if !art.verify(&part).await? {
+            remove_file(&part).await?;
+            bail!("compressed artifact {art} is corrupted");
+        }
+
+        set_readonly(&part).await?;
+        self.apply_store_mode(&part).await?;
+        mv(&part, self.storage_path(&art.blake3)).await?;
+        self.set_stored_len(&art.blake3, None).await?;
+        remove_file(&compressed).await?;
+
+        Ok(true)
+    }
+
+    /// Record the size of an artifact on disk, `None` meaning it is stored uncompressed.
+    async fn set_stored_len(&self, blake3: &str, len: Option<u64>) -> anyhow::Result<()> {
+        self.write(|| {
+            query("UPDATE artifact SET stored_len = ? WHERE blake3 = ?")
+                .bind(len.map(|x| x as i64))
+                .bind(blake3)
+                .execute(&self.index)
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs::{create_dir_all, read, symlink_metadata, write};
+
+    use super::*;
+    use crate::checksum::blake3;
+
+    /// Store a highly compressible artifact of `content` repeated.
+    async fn stored(manager: &ArtifactManager, content: &str) -> Artifact {
+        let tmp = manager.storage_dir().join("tmp");
+        write(&tmp, content.repeat(4096)).await.unwrap();
+
+        let b3 = blake3(&tmp).await.unwrap();
+        let art = Artifact::new(
+            b3.clone(),
+            "log.txt".into(),
+            None,
+            metadata(&tmp).await.unwrap().len(),
+        );
+
+        let path = manager.storage_path(&b3);
+        create_dir_all(path.parent().unwrap()).await.unwrap();
+        rename(&tmp, &path).await.unwrap();
+        manager.insert(&art).await.unwrap();
+
+        art
+    }
+
+    #[tokio::test]
+    async fn compress_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ArtifactManager::in_dir(dir.path()).await;
+
+        let art = stored(&manager, "creeper ").await;
+        let content = read(manager.storage_path(&art.blake3)).await.unwrap();
+
+        let stat = manager.compress(Duration::ZERO).await.unwrap();
+        assert_eq!(stat.count, 1);
+        assert!(stat.after < stat.before);
+        assert!(!manager.storage_path(&art.blake3).exists());
+        assert!(manager.compressed_path(&art.blake3).exists());
+
+        let path = manager.retrieve(&art).await.unwrap();
+        assert_eq!(read(&path).await.unwrap(), content);
+        assert!(!manager.compressed_path(&art.blake3).exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn compress_skip_deployed() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ArtifactManager::in_dir(&dir.path().join("storage")).await;
+
+        let art = stored(&manager, "creeper ").await;
+
+        let link = dir.path().join("game").join("log.txt");
+        create_dir_all(link.parent().unwrap()).await.unwrap();
+        tokio::fs::symlink(manager.storage_path(&art.blake3), &link)
+            .await
+            .unwrap();
+        manager
+            .record_deployed(&link.display().to_string(), &art.blake3)
+            .await
+            .unwrap();
+
+        let stat = manager.compress(Duration::ZERO).await.unwrap();
+        assert_eq!(stat.count, 0);
+
+        // the deployed link still resolves
+        assert!(link.exists());
+        assert!(symlink_metadata(&link).await.unwrap().is_symlink());
+    }
+}
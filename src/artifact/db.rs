@@ -1,10 +1,54 @@
+use std::time::Duration;
+
 use anyhow::bail;
-use sqlx::{AssertSqlSafe, query, query_as};
-use tracing::warn;
+use chrono::Utc;
+use sqlx::{AssertSqlSafe, query, query_as, sqlite::SqliteQueryResult};
+use tokio::{fs::metadata, time::sleep};
+use tracing::{debug, info, warn};
 
 use crate::{Artifact, artifact::ArtifactManager, checksum::HashFunc};
 
+/// Number of attempts of a write to the index before giving up on a busy database.
+const WRITE_ATTEMPT: u32 = 5;
+
+/// Seconds within which repeated accesses of an artifact are not recorded again.
+const ACCESS_GRANULARITY: i64 = 3600;
+
+/// Whether an error is transient contention, i.e. `SQLITE_BUSY` or `SQLITE_LOCKED` including their extended codes.
+fn is_busy(e: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(e) = e else {
+        return false;
+    };
+
+    e.code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
 impl ArtifactManager {
+    /// Run a write on the index, retrying with exponential backoff while the database is busy,
+    /// e.g. when another creeper process holds the write lock longer than the busy timeout.
+    pub(super) async fn write<F, Fut>(&self, mut run: F) -> anyhow::Result<SqliteQueryResult>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<SqliteQueryResult, sqlx::Error>>,
+    {
+        let mut delay = Duration::from_millis(50);
+
+        for attempt in 1.. {
+            match run().await {
+                Err(e) if is_busy(&e) && attempt < WRITE_ATTEMPT => {
+                    debug!("artifact index busy, retrying in {delay:?}: {e}");
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+                res => return Ok(res?),
+            }
+        }
+
+        unreachable!()
+    }
+
     pub(super) async fn select(
         &self,
         hash: HashFunc,
@@ -23,32 +67,37 @@ impl ArtifactManager {
         Ok(found)
     }
 
-    pub(super) async fn insert(&self, artifact: &Artifact) -> anyhow::Result<()> {
+    pub(crate) async fn insert(&self, artifact: &Artifact) -> anyhow::Result<()> {
         if self.get(&artifact.blake3).await?.is_some() {
             warn!("duplicate add of artifact, this is likely due to an inefficient design");
             return Ok(());
         }
 
-        query("INSERT INTO artifact (blake3, name, src, len, sha1, sha256, md5) VALUES (?, ?, ?, ?, ?, ?, ?)")
-        .bind(&artifact.blake3)
-        .bind(&artifact.name)
-        .bind(&artifact.src)
-        .bind(artifact.len as i64)
-        .bind(&artifact.sha1)
-        .bind(&artifact.sha256)
-        .bind(&artifact.md5)
-        .execute(&self.index)
+        self.write(|| {
+            query("INSERT INTO artifact (blake3, name, src, len, sha1, sha256, md5) VALUES (?, ?, ?, ?, ?, ?, ?)")
+                .bind(&artifact.blake3)
+                .bind(&artifact.name)
+                .bind(&artifact.src)
+                .bind(artifact.len as i64)
+                .bind(&artifact.sha1)
+                .bind(&artifact.sha256)
+                .bind(&artifact.md5)
+                .execute(&self.index)
+        })
         .await?;
         Ok(())
     }
 
     pub(super) async fn update(&self, art: &Artifact) -> anyhow::Result<()> {
-        let r = query("UPDATE artifact SET sha1 = ?, sha256 = ?, md5 = ? WHERE blake3 = ?")
-            .bind(&art.sha1)
-            .bind(&art.sha256)
-            .bind(&art.md5)
-            .bind(&art.blake3)
-            .execute(&self.index)
+        let r = self
+            .write(|| {
+                query("UPDATE artifact SET sha1 = ?, sha256 = ?, md5 = ? WHERE blake3 = ?")
+                    .bind(&art.sha1)
+                    .bind(&art.sha256)
+                    .bind(&art.md5)
+                    .bind(&art.blake3)
+                    .execute(&self.index)
+            })
             .await?;
 
         match r.rows_affected() {
@@ -57,4 +106,121 @@ impl ArtifactManager {
             _ => panic!("duplicate blake3 (primary key)"),
         }
     }
+
+    /// Record that an artifact is accessed now, see [`super::stat::StoredArtifact::last_accessed`].
+    ///
+    /// To keep repeated retrievals cheap, the timestamp only advances at [`ACCESS_GRANULARITY`],
+    /// so that most calls update no row.
+    pub(super) async fn touch(&self, blake3: &str) -> anyhow::Result<()> {
+        let now = Utc::now().timestamp();
+
+        self.write(|| {
+            query(
+                "UPDATE artifact SET last_accessed = ? WHERE blake3 = ? AND (last_accessed IS NULL OR last_accessed <= ?)",
+            )
+            .bind(now)
+            .bind(blake3)
+            .bind(now - ACCESS_GRANULARITY)
+            .execute(&self.index)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record that artifact `blake3` has been deployed to the absolute `path`, see [`Self::deployed`].
+    pub(super) async fn record_deployed(&self, path: &str, blake3: &str) -> anyhow::Result<()> {
+        self.write(|| {
+            query("INSERT OR REPLACE INTO deployed (path, blake3) VALUES (?, ?)")
+                .bind(path)
+                .bind(blake3)
+                .execute(&self.index)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether the file at the absolute `path` has been deployed by creeper,
+    /// rather than placed there by the user.
+    pub(super) async fn deployed(&self, path: &str) -> anyhow::Result<bool> {
+        let (deployed,): (bool,) = query_as("SELECT COUNT(*) > 0 FROM deployed WHERE path = ?")
+            .bind(path)
+            .fetch_one(&self.index)
+            .await?;
+
+        Ok(deployed)
+    }
+
+    /// Run `VACUUM` on the index database, returning its file size before and after.
+    pub async fn vacuum(&self) -> anyhow::Result<(u64, u64)> {
+        let path = self.index_path();
+
+        let before = metadata(&path).await?.len();
+
+        info!("vacuuming artifact index");
+
+        // sqlite refuses to vacuum while another connection holds a transaction
+        if let Err(e) = query("VACUUM").execute(&self.index).await {
+            bail!("cannot vacuum artifact index, is another creeper process running? {e}");
+        }
+
+        let after = metadata(&path).await?.len();
+
+        Ok((before, after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::try_join_all;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // one manager each, like separate creeper processes sharing the storage
+        let mut manager = vec![];
+        for _ in 0..8 {
+            manager.push(ArtifactManager::in_dir(dir.path()).await);
+        }
+
+        let write = manager.iter().enumerate().map(|(i, m)| async move {
+            for j in 0..32 {
+                let blake3 = format!("{:064x}", i * 32 + j);
+                let artifact = Artifact::new(blake3.clone(), format!("{i}-{j}"), None, 0);
+                m.insert(&artifact).await?;
+                m.touch(&blake3).await?;
+            }
+            anyhow::Ok(())
+        });
+        try_join_all(write).await.unwrap();
+
+        for i in 0..8 * 32 {
+            let blake3 = format!("{i:064x}");
+            assert!(manager[0].get(&blake3).await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn closed_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let blake3 = "0".repeat(64);
+
+        let manager = ArtifactManager::in_dir(dir.path()).await;
+        manager
+            .insert(&Artifact::new(blake3.clone(), "file".into(), None, 0))
+            .await
+            .unwrap();
+
+        manager.close().await;
+        assert!(manager.index.is_closed());
+        assert!(manager.touch(&blake3).await.is_err());
+
+        // everything written before is persisted
+        let manager = ArtifactManager::in_dir(dir.path()).await;
+        assert!(manager.get(&blake3).await.unwrap().is_some());
+    }
 }
@@ -1,17 +1,28 @@
-use anyhow::{bail, ensure};
+use std::{
+    iter::once,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, ensure};
+use reqwest::{
+    Response, StatusCode,
+    header::{CONTENT_RANGE, HeaderValue, RANGE},
+};
 use tokio::{
-    fs::{File, create_dir_all, metadata, remove_file, try_exists},
+    fs::{File, OpenOptions, create_dir_all, metadata, remove_file, rename, try_exists},
     io::{AsyncWriteExt, BufWriter},
+    time::sleep,
 };
-use tracing::{Span, debug, info, instrument, trace};
+use tracing::{Span, debug, info, instrument, trace, warn};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 
 use crate::{
     Artifact, Checksum,
     artifact::ArtifactManager,
-    checksum::{HashFunc, blake3},
+    checksum::{HashFunc, calc_multi},
+    http::{INITIAL_BACKOFF, is_transient, retry_after},
     mv,
-    path::creeper_cache_dir,
+    path::creeper_download_cache_dir,
     pbar::PROGRESS_STYLE_DOWNLOAD,
     util::{set_readonly, summarize},
 };
@@ -33,14 +44,22 @@ impl ArtifactManager {
         for checksum in &checksums {
             if let Some(mut art) = self.get_checksum(checksum).await? {
                 debug!("fingerprint found in local storage");
+                self.explain(&name, format!("indexed by {}", checksum.function));
 
                 let path = self.retrieve(&art).await?;
 
                 let func = checksum.function;
 
+                // because the `retrieve` method already checks blake3,
+                // no need to calculate again
+                let funcs = checksums
+                    .iter()
+                    .map(|c| c.function)
+                    .filter(|f| *f != HashFunc::Blake3)
+                    .collect::<Vec<_>>();
+                let sums = calc_multi(&path, &funcs).await?;
+
                 for checksum in checksums {
-                    // because the `retrieve` method already checks blake3,
-                    // no need to calculate again
                     if checksum.function == HashFunc::Blake3 {
                         ensure!(
                             checksum.hex_hash == art.blake3,
@@ -49,7 +68,7 @@ impl ArtifactManager {
                         continue;
                     }
 
-                    if !checksum.check(&path).await? {
+                    if sums[&checksum.function] != checksum.hex_hash {
                         bail!("incorrect checksum for {path:?}, expected {checksum}");
                     }
 
@@ -66,48 +85,24 @@ impl ArtifactManager {
             bail!("offline mode enabled, cannot download {src}");
         }
 
-        let cache = creeper_cache_dir()?.join("download").join(summarize(&src));
+        self.explain(&name, format!("not indexed, downloading from {src}"));
+
+        let cache = download_cache_path(&src, &checksums)?;
 
         trace!("download caching to {cache:?}");
         create_dir_all(cache.parent().unwrap()).await?;
 
-        if try_exists(&cache).await? {
-            // TODO: continue download if the file is incomplete
-            remove_file(&cache).await?;
-        }
-
-        let semaphore = self.semaphore.acquire().await?;
+        // an incomplete download can only be resumed if it is verified with checksums afterwards
+        self.fetch(&name, &src, &cache, len, !checksums.is_empty())
+            .await?;
 
-        let mut writer = BufWriter::new(File::create(&cache).await?);
+        let funcs = once(HashFunc::Blake3)
+            .chain(checksums.iter().map(|c| c.function))
+            .collect::<Vec<_>>();
+        let mut sums = calc_multi(&cache, &funcs).await?;
 
-        let span = Span::current();
-        let trunc: String = name.chars().take(8).collect();
-        span.pb_set_message(&trunc);
-        span.pb_set_style(&PROGRESS_STYLE_DOWNLOAD);
-        span.pb_set_length(len.unwrap_or(0));
-
-        let req = self.http.get(&src).build()?;
-        let mut res = self.http.execute(req).await?;
-
-        if len.is_none() {
-            span.pb_set_length(res.content_length().unwrap_or(0));
-        }
-
-        while let Some(chunk) = res.chunk().await? {
-            writer.write_all(&chunk).await?;
-            span.pb_inc(chunk.len() as u64);
-        }
-
-        writer.shutdown().await?;
-
-        drop(semaphore);
-
-        info!("download finished");
-
-        set_readonly(&cache).await?;
-
-        let b3 = blake3(&cache).await?;
-        let path = Artifact::storage_path(&b3)?;
+        let b3 = sums.remove(&HashFunc::Blake3).unwrap();
+        let path = self.storage_path(&b3);
 
         let download_len = metadata(&cache).await?.len();
 
@@ -131,7 +126,7 @@ impl ArtifactManager {
                 continue;
             }
 
-            if !checksum.check(&cache).await? {
+            if sums[&checksum.function] != checksum.hex_hash {
                 bail!("broken download {}, expected {checksum}", cache.display());
             }
 
@@ -142,8 +137,591 @@ impl ArtifactManager {
 
         if !self.has_storage(&art.blake3).await? {
             mv(&cache, &path).await?;
+            self.apply_store_mode(&path).await?;
+        } else {
+            remove_file(&cache).await?;
         }
 
         Ok(art)
     }
+
+    /// Download `src` into the readonly file `cache`, reporting progress on the current span.
+    ///
+    /// Data is written to `<cache>.part` and only renamed to `cache` once complete,
+    /// so that `cache` never holds a truncated download.
+    /// If `resumable`, an incomplete download left in `<cache>.part` by an interrupted run is resumed with a range request,
+    /// so the caller must verify the result with checksums.
+    /// Transient failures are retried like [`crate::http::fetch_json_raw`], resuming the attempt if `resumable`.
+    /// `len` is the expected total length, if known.
+    /// `src` is rewritten according to [`crate::Config::mirror`].
+    pub(super) async fn fetch(
+        &self,
+        name: &str,
+        src: &str,
+        cache: &Path,
+        len: Option<u64>,
+        resumable: bool,
+    ) -> anyhow::Result<()> {
+        let part = cache.with_added_extension("part");
+
+        // a complete download still here failed verification, or was interrupted before it
+        if try_exists(cache).await? {
+            remove_file(cache).await?;
+        }
+
+        if try_exists(&part).await? {
+            let meta = metadata(&part).await?;
+            if !resumable || len.is_some_and(|len| meta.len() >= len) {
+                remove_file(&part).await?;
+            }
+        }
+
+        let semaphore = self.semaphore.acquire().await?;
+
+        let span = Span::current();
+        let trunc: String = name.chars().take(8).collect();
+        span.pb_set_message(&trunc);
+        span.pb_set_style(&PROGRESS_STYLE_DOWNLOAD);
+        span.pb_set_length(len.unwrap_or(0));
+
+        let max = self.fetch.attempt;
+        let mut delay = INITIAL_BACKOFF;
+
+        for attempt in 1..=max {
+            // an attempt failed midway is resumed like a download left by an interrupted run
+            let mut start = if resumable && try_exists(&part).await? {
+                metadata(&part).await?.len()
+            } else {
+                0
+            };
+
+            let mut res = match self.request(src, start).await {
+                Ok(res) => res,
+                Err(e) if is_transient_error(&e) && attempt < max => {
+                    warn!("failed to download {src}, retrying in {delay:?}: {e}");
+                    sleep(delay).await;
+                    delay *= 2;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if start > 0 {
+                let resume = match res.status() {
+                    StatusCode::PARTIAL_CONTENT => {
+                        match check_content_range(res.headers().get(CONTENT_RANGE), start, len) {
+                            Ok(()) => true,
+                            Err(e) => {
+                                warn!("cannot resume download {}: {e}", cache.display());
+                                false
+                            }
+                        }
+                    }
+                    // the partial download is longer than the file, e.g. it has changed upstream
+                    StatusCode::RANGE_NOT_SATISFIABLE => {
+                        debug!("server rejects resuming from byte {start}");
+                        false
+                    }
+                    // a full response, or an error reported below
+                    _ => {
+                        start = 0;
+                        true
+                    }
+                };
+
+                if !resume {
+                    debug!("restarting download from byte 0");
+                    remove_file(&part).await?;
+                    start = 0;
+                    res = self.request(src, 0).await?;
+                }
+            }
+
+            let wait = retry_after(&res);
+
+            let mut res = match res.error_for_status() {
+                Ok(res) => res,
+                Err(e) if is_transient(&e) && attempt < max => {
+                    let wait = wait.unwrap_or(delay);
+                    warn!("failed to download {src}, retrying in {wait:?}: {e}");
+                    sleep(wait).await;
+                    delay *= 2;
+                    continue;
+                }
+                Err(e) => Err(e)?,
+            };
+
+            let file = if start > 0 {
+                debug!("resuming download from byte {start}");
+                OpenOptions::new().append(true).open(&part).await?
+            } else {
+                File::create(&part).await?
+            };
+
+            if len.is_none() {
+                span.pb_set_length(res.content_length().unwrap_or(0) + start);
+            }
+            span.pb_set_position(start);
+
+            match write_body(&mut res, file, &span).await {
+                Ok(()) => {}
+                Err(e) if is_transient_error(&e) && attempt < max => {
+                    warn!("failed to download {src}, retrying in {delay:?}: {e}");
+                    sleep(delay).await;
+                    delay *= 2;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+
+            drop(semaphore);
+
+            info!("download finished");
+
+            set_readonly(&part).await?;
+            rename(&part, cache).await?;
+
+            return Ok(());
+        }
+
+        unreachable!()
+    }
+
+    /// Send a request for `src` from byte `start` on.
+    async fn request(&self, src: &str, start: u64) -> anyhow::Result<Response> {
+        let mut req = self.http.get(&*self.fetch.mirror(src));
+        if start > 0 {
+            req = req.header(RANGE, format!("bytes={start}-"));
+        }
+        let res = self.http.execute(req.build()?).await?;
+        Ok(res)
+    }
+}
+
+/// Stream the body of `res` into `file`, reporting progress on `span`.
+async fn write_body(res: &mut Response, file: File, span: &Span) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(file);
+
+    loop {
+        let chunk = match res.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                // keep what has been received, for the next attempt to resume from
+                writer.flush().await?;
+                Err(e)?
+            }
+        };
+
+        writer.write_all(&chunk).await?;
+        span.pb_inc(chunk.len() as u64);
+    }
+
+    writer.flush().await?;
+    writer.get_ref().sync_all().await?;
+    writer.shutdown().await?;
+
+    Ok(())
+}
+
+/// Whether `e` is a transient HTTP failure, see [`is_transient`].
+///
+/// A body cut short is reported as a decoding error, which a raw download can only get from reading the body.
+fn is_transient_error(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| is_transient(e) || e.is_decode())
+}
+
+/// Check that the `Content-Range` of a partial response continues a download at byte `start`,
+/// and agrees with the expected total length `len` if known.
+fn check_content_range(
+    header: Option<&HeaderValue>,
+    start: u64,
+    len: Option<u64>,
+) -> anyhow::Result<()> {
+    let header = header.ok_or(anyhow!("missing Content-Range"))?.to_str()?;
+
+    let invalid = || anyhow!("invalid Content-Range {header}");
+
+    let (range, total) = header
+        .strip_prefix("bytes ")
+        .and_then(|s| s.split_once('/'))
+        .ok_or_else(invalid)?;
+    let (first, last) = range.split_once('-').ok_or_else(invalid)?;
+    let first = first.parse::<u64>().map_err(|_| invalid())?;
+    let last = last.parse::<u64>().map_err(|_| invalid())?;
+
+    ensure!(
+        first == start,
+        "Content-Range {header} does not start at byte {start}"
+    );
+
+    if total != "*" {
+        let total = total.parse::<u64>().map_err(|_| invalid())?;
+        ensure!(
+            last + 1 == total,
+            "Content-Range {header} is not to the end"
+        );
+        if let Some(len) = len {
+            ensure!(
+                total == len,
+                "Content-Range {header} does not match length {len}"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Path to cache an in-progress download.
+///
+/// The file is keyed by the strongest expected checksum if any,
+/// so that the same content fetched from different sources shares the cache,
+/// otherwise by the source URL.
+pub(super) fn download_cache_path(src: &str, checksum: &[Checksum]) -> anyhow::Result<PathBuf> {
+    let key = [
+        HashFunc::Blake3,
+        HashFunc::Sha256,
+        HashFunc::Sha1,
+        HashFunc::Md5,
+    ]
+    .into_iter()
+    .find_map(|func| checksum.iter().find(|c| c.function == func))
+    .map(|c| format!("{}-{}", c.function, c.hex_hash))
+    .unwrap_or_else(|| summarize(src));
+
+    let path = creeper_download_cache_dir()?.join(key);
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+    use tokio::{
+        fs::{read, write},
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc::{UnboundedReceiver, unbounded_channel},
+    };
+
+    use super::*;
+
+    const CONTENT: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+    /// Serve [`CONTENT`] over HTTP, honoring `Range` only if `ranges`, otherwise rejecting it with 416.
+    ///
+    /// Returns the URL and the `Range` header of each request.
+    async fn serve(ranges: bool) -> (String, UnboundedReceiver<Option<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let (tx, rx) = unbounded_channel();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut req = vec![];
+                let mut buf = [0; 1024];
+                while !req.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                }
+
+                let range = String::from_utf8(req)
+                    .unwrap()
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: ").map(str::to_owned));
+                tx.send(range.clone()).unwrap();
+
+                let start = range
+                    .as_deref()
+                    .and_then(|r| r.strip_prefix("bytes="))
+                    .and_then(|r| r.strip_suffix('-'))
+                    .map(|r| r.parse::<usize>().unwrap());
+
+                let res = match start {
+                    Some(_) if !ranges => {
+                        b"HTTP/1.1 416 Range Not Satisfiable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                            .to_vec()
+                    }
+                    Some(start) => {
+                        let mut res = format!(
+                            "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes {start}-{}/{}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                            CONTENT.len() - 1,
+                            CONTENT.len(),
+                            CONTENT.len() - start
+                        )
+                        .into_bytes();
+                        res.extend_from_slice(&CONTENT[start..]);
+                        res
+                    }
+                    None => {
+                        let mut res = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                            CONTENT.len()
+                        )
+                        .into_bytes();
+                        res.extend_from_slice(CONTENT);
+                        res
+                    }
+                };
+
+                stream.write_all(&res).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn resume_partial_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("file");
+        write(cache.with_added_extension("part"), &CONTENT[..10])
+            .await
+            .unwrap();
+
+        let (url, mut range) = serve(true).await;
+        let manager = ArtifactManager::in_memory().await;
+
+        manager
+            .fetch("file", &url, &cache, Some(CONTENT.len() as u64), true)
+            .await
+            .unwrap();
+
+        assert_eq!(range.recv().await.unwrap().as_deref(), Some("bytes=10-"));
+        assert_eq!(read(&cache).await.unwrap(), CONTENT);
+        assert!(
+            !try_exists(cache.with_added_extension("part"))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_download() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let (tx, mut range) = unbounded_channel();
+
+        let len = CONTENT.len();
+        let mut truncated =
+            format!("HTTP/1.1 200 OK\r\ncontent-length: {len}\r\nconnection: close\r\n\r\n")
+                .into_bytes();
+        truncated.extend_from_slice(&CONTENT[..10]);
+        let mut rest = format!(
+            "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes 10-{}/{len}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            len - 1,
+            len - 10
+        )
+        .into_bytes();
+        rest.extend_from_slice(&CONTENT[10..]);
+
+        let res = [
+            b"HTTP/1.1 503 Service Unavailable\r\nretry-after: 0\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_vec(),
+            truncated,
+            rest,
+        ];
+
+        tokio::spawn(async move {
+            for res in res {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut req = vec![];
+                let mut buf = [0; 1024];
+                while !req.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                }
+
+                let range = String::from_utf8(req)
+                    .unwrap()
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: ").map(str::to_owned));
+                tx.send(range).unwrap();
+
+                stream.write_all(&res).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("file");
+        let manager = ArtifactManager::in_memory().await;
+
+        manager
+            .fetch("file", &url, &cache, Some(len as u64), true)
+            .await
+            .unwrap();
+
+        // the server error is retried, and the truncated body resumed
+        assert_eq!(range.recv().await.unwrap(), None);
+        assert_eq!(range.recv().await.unwrap(), None);
+        assert_eq!(range.recv().await.unwrap().as_deref(), Some("bytes=10-"));
+        assert_eq!(read(&cache).await.unwrap(), CONTENT);
+    }
+
+    #[tokio::test]
+    async fn restart_unsatisfiable_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("file");
+        write(cache.with_added_extension("part"), b"stale")
+            .await
+            .unwrap();
+
+        let (url, mut range) = serve(false).await;
+        let manager = ArtifactManager::in_memory().await;
+
+        manager
+            .fetch("file", &url, &cache, None, true)
+            .await
+            .unwrap();
+
+        assert_eq!(range.recv().await.unwrap().as_deref(), Some("bytes=5-"));
+        assert_eq!(range.recv().await.unwrap(), None);
+        assert_eq!(read(&cache).await.unwrap(), CONTENT);
+    }
+
+    #[tokio::test]
+    async fn jobs_cap_downloads() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        };
+
+        use clap::Parser;
+        use futures::future::try_join_all;
+        use tokio::sync::Semaphore;
+
+        use crate::{Args, Config};
+
+        let args = Args::parse_from(["creeper", "-j", "2"]);
+
+        // knobs set individually are not overridden
+        let mut config = Config {
+            parallel_extract: Some(3),
+            ..Config::default()
+        };
+        config.apply_args(&args);
+        assert_eq!(config.parallel_extract(), 3);
+        assert_eq!(config.parallel_download(), 2);
+
+        // count requests in flight, each held long enough to overlap with the others
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let active = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+
+        let (a, m) = (active.clone(), max.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let (active, max) = (a.clone(), m.clone());
+
+                tokio::spawn(async move {
+                    let mut req = vec![];
+                    let mut buf = [0; 1024];
+                    while !req.ends_with(b"\r\n\r\n") {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        req.extend_from_slice(&buf[..n]);
+                    }
+
+                    let n = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(n, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+
+                    let mut res = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                        CONTENT.len()
+                    )
+                    .into_bytes();
+                    res.extend_from_slice(CONTENT);
+                    stream.write_all(&res).await.unwrap();
+                    stream.shutdown().await.unwrap();
+                });
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ArtifactManager {
+            semaphore: Semaphore::new(config.parallel_download()),
+            ..ArtifactManager::in_memory().await
+        };
+
+        try_join_all((0..6).map(|i| {
+            let cache = dir.path().join(i.to_string());
+            let (manager, url) = (&manager, &url);
+            async move { manager.fetch("file", url, &cache, None, false).await }
+        }))
+        .await
+        .unwrap();
+
+        assert_eq!(max.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn content_range() {
+        let header = |s: &'static str| HeaderValue::from_static(s);
+
+        check_content_range(Some(&header("bytes 10-42/43")), 10, Some(43)).unwrap();
+        check_content_range(Some(&header("bytes 10-42/*")), 10, None).unwrap();
+
+        assert!(check_content_range(None, 10, None).is_err());
+        assert!(check_content_range(Some(&header("bytes 0-42/43")), 10, None).is_err());
+        assert!(check_content_range(Some(&header("bytes 10-20/43")), 10, None).is_err());
+        assert!(check_content_range(Some(&header("bytes 10-42/43")), 10, Some(50)).is_err());
+        assert!(check_content_range(Some(&header("items 10-42/43")), 10, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn explain_hit_and_miss() {
+        use std::sync::{Arc, Mutex};
+
+        /// Log output collected in memory.
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        // the same explanations are printed with `--explain` instead
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::TRACE)
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .finish(),
+        );
+        let output = || String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ArtifactManager::in_dir(dir.path()).await;
+
+        let (url, _range) = serve(true).await;
+        let art = Artifact::new(
+            blake3::hash(CONTENT).to_hex().to_string(),
+            "file".into(),
+            Some(url.clone()),
+            CONTENT.len() as u64,
+        );
+
+        manager.retrieve(&art).await.unwrap();
+        assert!(output().contains(&format!("file: not in storage, downloading from {url}")));
+
+        manager.retrieve(&art).await.unwrap();
+        assert!(output().contains("file: found in storage"));
+    }
 }
@@ -0,0 +1,135 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use sqlx::{query, query_as};
+use tokio::fs::{metadata, symlink_metadata, try_exists};
+use tracing::debug;
+use walkdir::WalkDir;
+
+use crate::{Artifact, artifact::ArtifactManager};
+
+/// Result of [`ArtifactManager::find_garbage`].
+#[derive(Clone, Debug, Default)]
+pub struct Garbage {
+    /// Indexed artifacts not to be kept, with their size on disk.
+    pub artifact: Vec<(Artifact, u64)>,
+    /// Files in storage that are not indexed at all, with their size.
+    pub orphan: Vec<(PathBuf, u64)>,
+}
+
+impl Garbage {
+    /// Total size of the garbage on disk.
+    pub fn size(&self) -> u64 {
+        self.artifact
+            .iter()
+            .map(|(_, len)| len)
+            .chain(self.orphan.iter().map(|(_, len)| len))
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.artifact.is_empty() && self.orphan.is_empty()
+    }
+}
+
+impl ArtifactManager {
+    /// Artifacts still present where they have been deployed to, wherever the instance is,
+    /// except for those deployed under the directories in `except`.
+    ///
+    /// Records of paths no longer existing are dropped.
+    pub async fn deployed_roots(&self, except: &[PathBuf]) -> anyhow::Result<HashSet<String>> {
+        let deployed = query_as::<_, (String, String)>("SELECT path, blake3 FROM deployed")
+            .fetch_all(&self.index)
+            .await?;
+
+        let mut root = HashSet::new();
+
+        for (path, b3) in deployed {
+            if except.iter().any(|dir| Path::new(&path).starts_with(dir)) {
+                continue;
+            }
+
+            // a dangling link still refers to the artifact
+            if symlink_metadata(&path).await.is_ok() {
+                root.insert(b3);
+                continue;
+            }
+
+            debug!("dropping deployment record of removed {path}");
+            self.write(|| {
+                query("DELETE FROM deployed WHERE path = ?")
+                    .bind(&path)
+                    .execute(&self.index)
+            })
+            .await?;
+        }
+
+        Ok(root)
+    }
+
+    /// Find stored artifacts whose blake3 is not in `keep`, and files in storage missing from the index.
+    ///
+    /// Nothing is removed, use [`Self::remove`] on the result.
+    pub async fn find_garbage(&self, keep: &HashSet<String>) -> anyhow::Result<Garbage> {
+        let all = query_as::<_, Artifact>("SELECT * FROM artifact")
+            .fetch_all(&self.index)
+            .await?
+            .into_iter()
+            .map(|art| (art.blake3.clone(), art))
+            .collect::<HashMap<_, _>>();
+
+        let mut garbage = Garbage::default();
+
+        for (b3, art) in &all {
+            if keep.contains(b3) {
+                continue;
+            }
+
+            let mut len = 0;
+            for path in [self.storage_path(&art.blake3), self.compressed_path(b3)] {
+                if try_exists(&path).await? {
+                    len += metadata(&path).await?.len();
+                }
+            }
+
+            garbage.artifact.push((art.clone(), len));
+        }
+
+        let store = self.dir.join("artifact");
+
+        if !try_exists(&store).await? {
+            return Ok(garbage);
+        }
+
+        for entry in WalkDir::new(&store) {
+            let entry = entry?;
+            let path = entry.path();
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            // other extensions are in-progress writes, possibly of another process
+            let b3 = match path.extension() {
+                None => path.file_name(),
+                Some(ext) if ext == "zst" => path.file_stem(),
+                Some(_) => continue,
+            };
+
+            if b3
+                .and_then(|s| s.to_str())
+                .is_some_and(|b3| all.contains_key(b3))
+            {
+                continue;
+            }
+
+            garbage
+                .orphan
+                .push((path.to_owned(), entry.metadata()?.len()));
+        }
+
+        Ok(garbage)
+    }
+}
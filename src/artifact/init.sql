@@ -6,7 +6,9 @@ CREATE TABLE IF NOT EXISTS
         len INTEGER NOT NULL,
         sha1 TEXT,
         sha256 TEXT,
-        md5 TEXT
+        md5 TEXT,
+        stored_len INTEGER,
+        last_accessed INTEGER
     );
 
 CREATE INDEX IF NOT EXISTS idx_artifact_name ON artifact (name);
@@ -16,3 +18,9 @@ CREATE UNIQUE INDEX IF NOT EXISTS idx_artifact_sha1 ON artifact (sha1);
 CREATE UNIQUE INDEX IF NOT EXISTS idx_artifact_sha256 ON artifact (sha256);
 
 CREATE UNIQUE INDEX IF NOT EXISTS idx_artifact_md5 ON artifact (md5);
+
+CREATE TABLE IF NOT EXISTS
+    deployed (
+        path TEXT PRIMARY KEY,
+        blake3 TEXT NOT NULL
+    );
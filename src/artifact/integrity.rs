@@ -0,0 +1,96 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use sqlx::{query, query_as};
+use tokio::{
+    fs::{remove_file, try_exists},
+    task::spawn_blocking,
+};
+use tracing::{debug, info};
+
+use crate::{Artifact, artifact::ArtifactManager, checksum::blake3};
+
+/// Result of [`ArtifactManager::check_integrity`].
+#[derive(Clone, Debug, Default)]
+pub struct IntegrityReport {
+    /// Number of indexed artifacts checked.
+    pub count: usize,
+    /// Indexed artifacts whose file is absent from storage.
+    pub missing: Vec<Artifact>,
+    /// Indexed artifacts whose stored file does not match their blake3.
+    pub corrupted: Vec<Artifact>,
+}
+
+/// Hash the content of a zstd compressed file without decompressing it to disk.
+async fn compressed_blake3(path: &Path) -> anyhow::Result<String> {
+    fn calc(path: &Path) -> anyhow::Result<String> {
+        let decoder = zstd::stream::read::Decoder::with_buffer(BufReader::new(File::open(path)?))?;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(decoder)?;
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+    let path = path.to_owned();
+    spawn_blocking(move || calc(&path)).await?
+}
+
+impl ArtifactManager {
+    /// Re-hash every artifact in the index, reporting those missing or corrupted in storage.
+    ///
+    /// Compressed artifacts are hashed on the fly, without being decompressed to disk.
+    pub async fn check_integrity(&self) -> anyhow::Result<IntegrityReport> {
+        let all = query_as::<_, Artifact>("SELECT * FROM artifact")
+            .fetch_all(&self.index)
+            .await?;
+
+        info!("checking integrity of {} artifacts", all.len());
+
+        let mut report = IntegrityReport {
+            count: all.len(),
+            ..Default::default()
+        };
+
+        for art in all {
+            let path = self.storage_path(&art.blake3);
+            let compressed = self.compressed_path(&art.blake3);
+
+            let b3 = if try_exists(&path).await? {
+                blake3(&path).await?
+            } else if try_exists(&compressed).await? {
+                compressed_blake3(&compressed).await?
+            } else {
+                debug!("missing stored file of {art}");
+                report.missing.push(art);
+                continue;
+            };
+
+            if b3 != art.blake3 {
+                debug!("stored file of {art} has blake3 {b3}");
+                report.corrupted.push(art);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Delete an artifact from storage and the index, so that it is downloaded again when next used.
+    pub async fn remove(&self, art: &Artifact) -> anyhow::Result<()> {
+        for path in [
+            self.storage_path(&art.blake3),
+            self.compressed_path(&art.blake3),
+        ] {
+            if try_exists(&path).await? {
+                remove_file(&path).await?;
+            }
+        }
+
+        self.write(|| {
+            query("DELETE FROM artifact WHERE blake3 = ?")
+                .bind(&art.blake3)
+                .execute(&self.index)
+        })
+        .await?;
+
+        debug!("removed {art}");
+
+        Ok(())
+    }
+}
@@ -1,29 +1,42 @@
+mod compress;
 mod db;
 mod download;
+mod gc;
+mod integrity;
 mod parallel;
+mod stat;
+
+pub use compress::CompressStat;
+use download::download_cache_path;
+pub use integrity::IntegrityReport;
+pub use parallel::StoreStat;
+pub use stat::StorageStat;
 
 use std::fmt::Display;
 use std::iter::once;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, bail};
+use colored::Colorize;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use sqlx::{Executor, SqlitePool, prelude::FromRow, sqlite::SqliteConnectOptions};
-use tokio::fs::{File, copy, create_dir_all, metadata, try_exists};
-use tokio::io::{AsyncWriteExt, BufWriter};
+use sqlx::{
+    AssertSqlSafe, Connection, Executor, SqliteConnection, SqlitePool, prelude::FromRow, query_as,
+    sqlite::SqliteConnectOptions,
+};
+use tokio::fs::{copy, create_dir_all, metadata, remove_file, try_exists};
 use tokio::sync::Semaphore;
-use tracing::{Span, debug, info, instrument, trace};
-use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing::{debug, instrument, trace, warn};
 
-use crate::path::{creeper_cache_dir, creeper_data_dir};
-use crate::pbar::PROGRESS_STYLE_DOWNLOAD;
-use crate::util::{mv, set_readonly, summarize};
+use crate::checksum;
+use crate::deploy::{DeployMode, deploy};
+use crate::http::FetchOption;
+use crate::path::creeper_data_dir;
+use crate::util::{ensure_dir, mv, parse_mode, set_mode, set_readonly};
 use crate::{
-    Checksum, Creeper,
+    Args, Checksum, Config, Creeper,
     checksum::{HashFunc, blake3},
 };
-use crate::{checksum, symlink_auto};
 
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize, FromRow)]
 #[serde(deny_unknown_fields)]
@@ -44,6 +57,13 @@ pub struct Artifact {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub md5: Option<String>,
+
+    /// Whether the file is deployed executable, e.g. a helper tool extracted from a zip entry marked so.
+    ///
+    /// This is a property of the deployed file rather than of the content, so the stored file is left as is.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[sqlx(default)]
+    pub executable: bool,
 }
 
 impl Display for Artifact {
@@ -62,6 +82,7 @@ impl Artifact {
             sha1: None,
             sha256: None,
             md5: None,
+            executable: false,
         }
     }
 
@@ -75,6 +96,7 @@ impl Artifact {
                 sha1,
                 sha256,
                 md5,
+                executable,
             } = art;
             if self.blake3 != blake3
                 || self.len != len
@@ -87,6 +109,7 @@ impl Artifact {
             self.sha1 = sha1;
             self.sha256 = sha256;
             self.md5 = md5;
+            self.executable |= executable;
         }
         Ok(())
     }
@@ -96,10 +119,7 @@ impl Artifact {
             .into_iter()
             .chain(self.sha1.map(Checksum::sha1))
             .chain(self.sha256.map(Checksum::sha256))
-    }
-
-    pub fn path(&self) -> anyhow::Result<PathBuf> {
-        Self::storage_path(&self.blake3)
+            .chain(self.md5.map(Checksum::md5))
     }
 
     pub fn has_checksum(&self, checksum: HashFunc) -> bool {
@@ -107,6 +127,7 @@ impl Artifact {
             HashFunc::Blake3 => true,
             HashFunc::Sha1 => self.sha1.is_some(),
             HashFunc::Sha256 => self.sha256.is_some(),
+            HashFunc::Md5 => self.md5.is_some(),
         }
     }
 
@@ -117,17 +138,10 @@ impl Artifact {
             }
             crate::checksum::HashFunc::Sha1 => self.sha1 = Some(checksum.hex_hash),
             crate::checksum::HashFunc::Sha256 => self.sha256 = Some(checksum.hex_hash),
+            crate::checksum::HashFunc::Md5 => self.md5 = Some(checksum.hex_hash),
         }
     }
 
-    pub fn storage_path(blake3: &str) -> anyhow::Result<PathBuf> {
-        let path = creeper_data_dir()?
-            .join("artifact")
-            .join(&blake3[..2])
-            .join(blake3);
-        Ok(path)
-    }
-
     pub async fn verify(&self, file: impl AsRef<Path>) -> anyhow::Result<bool> {
         let b3 = blake3(file).await?;
         Ok(b3 == self.blake3)
@@ -139,37 +153,131 @@ const DB_INIT_QUERY: &str = include_str!("init.sql");
 pub struct ArtifactManager {
     pub offline: bool,
 
+    /// Whether to re-hash stored files before using them, see [`Config::verify_storage`](crate::Config::verify_storage).
+    verify: bool,
+
     http: Client,
 
+    /// URL rewriting applied to downloads.
+    fetch: FetchOption,
+
+    /// Root directory of the storage and its index, see [`Config::storage`](crate::Config::storage).
+    dir: PathBuf,
+
     index: SqlitePool,
 
     semaphore: Semaphore,
+
+    /// Whether to report decisions on reusing or downloading artifacts, see [`Args::explain`](crate::Args::explain).
+    explain: bool,
+
+    /// Permissions of stored files, see [`Config::store_mode`](crate::Config::store_mode).
+    store_mode: Option<u32>,
 }
 
 impl ArtifactManager {
     pub async fn new(
         http: Client,
-        offline: bool,
-        parallel_download: usize,
+        fetch: FetchOption,
+        config: &Config,
+        args: &Args,
     ) -> anyhow::Result<Self> {
-        let path = creeper_data_dir()?.join("artifact.db");
+        let dir = config.storage.clone().map_or_else(creeper_data_dir, Ok)?;
+        let store_mode = config.store_mode.as_deref().map(parse_mode).transpose()?;
+        let path = dir.join("artifact.db");
         let opt = SqliteConnectOptions::default()
-            .filename(path)
+            .filename(&path)
             .create_if_missing(true);
+
+        // set up on a connection of its own, since pooled connections may hold a stale schema
+        let mut conn = SqliteConnection::connect_with(&opt).await?;
+        conn.execute(DB_INIT_QUERY).await?;
+        Self::migrate(&mut conn).await?;
+        conn.close().await?;
+
         let index = SqlitePool::connect_with(opt).await?;
-        index.execute(DB_INIT_QUERY).await?;
 
-        let semaphore = Semaphore::new(parallel_download);
+        // the index must stay writable by its owner
+        if let Some(mode) = store_mode {
+            set_mode(&path, mode | 0o600).await?;
+        }
+
+        let semaphore = Semaphore::new(config.parallel_download());
 
         let val = Self {
             index,
             http,
-            offline,
+            fetch,
+            dir,
+            offline: args.offline,
+            verify: config.verify_storage && !args.no_verify,
             semaphore,
+            explain: args.explain,
+            store_mode,
         };
         Ok(val)
     }
 
+    /// Report why an artifact is reused or downloaded if explaining, otherwise only log it.
+    fn explain(&self, name: &str, reason: impl Display) {
+        if self.explain {
+            eprintln!("{} {name}: {reason}", "Explain".bold().cyan());
+        } else {
+            trace!("{name}: {reason}");
+        }
+    }
+
+    /// Apply [`Config::store_mode`](crate::Config::store_mode) to a file placed into storage, if configured.
+    async fn apply_store_mode(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        if let Some(mode) = self.store_mode {
+            // stored files are never writable, whatever the configuration
+            set_mode(path, mode & !0o222).await?;
+        }
+        Ok(())
+    }
+
+    /// Bring an index database created by an older version up to date.
+    async fn migrate(conn: &mut SqliteConnection) -> anyhow::Result<()> {
+        for column in ["stored_len", "last_accessed"] {
+            let (has_column,): (bool,) =
+                query_as("SELECT COUNT(*) > 0 FROM pragma_table_info('artifact') WHERE name = ?")
+                    .bind(column)
+                    .fetch_one(&mut *conn)
+                    .await?;
+
+            if !has_column {
+                debug!("adding column {column} to artifact index");
+                // `.bind()` can not bind column names, which are constants here
+                conn.execute(AssertSqlSafe(format!(
+                    "ALTER TABLE artifact ADD COLUMN {column} INTEGER"
+                )))
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close the index database, after which the artifact storage cannot be used.
+    pub async fn close(&self) {
+        self.index.close().await;
+    }
+
+    /// Root directory of the artifact storage and its index.
+    pub fn storage_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path to the sqlite database indexing the artifact storage.
+    pub fn index_path(&self) -> PathBuf {
+        self.dir.join("artifact.db")
+    }
+
+    /// Path of the stored file of the artifact with hash `blake3`.
+    pub fn storage_path(&self, blake3: &str) -> PathBuf {
+        self.dir.join("artifact").join(&blake3[..2]).join(blake3)
+    }
+
     async fn get(&self, blake3: &str) -> anyhow::Result<Option<Artifact>> {
         self.select(HashFunc::Blake3, blake3).await
     }
@@ -179,7 +287,17 @@ impl ArtifactManager {
     }
 
     async fn has_storage(&self, blake3: &str) -> anyhow::Result<bool> {
-        let path = Artifact::storage_path(blake3)?;
+        if !self.verify {
+            let path = self.storage_path(blake3);
+            return Ok(try_exists(&path).await? && self.get(blake3).await?.is_some());
+        }
+
+        self.check_storage(blake3).await
+    }
+
+    /// Like [`Self::has_storage`], but always re-hash the stored file.
+    async fn check_storage(&self, blake3: &str) -> anyhow::Result<bool> {
+        let path = self.storage_path(blake3);
         if try_exists(&path).await? {
             if checksum::blake3(&path).await? == blake3 {
                 return Ok(true);
@@ -207,10 +325,19 @@ impl ArtifactManager {
     /// See [`Creeper::retrieve_artifact`].
     #[instrument(skip(self, art), fields(artifact = &art.name))]
     async fn retrieve(&self, art: &Artifact) -> anyhow::Result<PathBuf> {
-        let path = art.path()?;
+        let path = self.storage_path(&art.blake3);
 
         if self.has_storage(&art.blake3).await? {
+            self.explain(&art.name, "found in storage");
+            self.add_or_update(art.clone()).await?;
+            self.touch(&art.blake3).await?;
+            return Ok(path);
+        }
+
+        if self.decompress(art).await? {
+            self.explain(&art.name, "found compressed in storage, decompressed");
             self.add_or_update(art.clone()).await?;
+            self.touch(&art.blake3).await?;
             return Ok(path);
         }
 
@@ -223,47 +350,86 @@ impl ArtifactManager {
             None => bail!("missing download source"),
         };
 
+        if try_exists(&path).await? {
+            self.explain(
+                &art.name,
+                format!("blake3 mismatch in storage, re-downloading from {src}"),
+            );
+        } else {
+            self.explain(&art.name, format!("not in storage, downloading from {src}"));
+        }
+
         debug!("downloading from {}", src);
 
-        let cache = creeper_cache_dir()?.join(summarize(src));
+        let cache = download_cache_path(src, &[Checksum::blake3(art.blake3.clone())])?;
         trace!("download caching to {cache:?}");
         create_dir_all(cache.parent().unwrap()).await?;
 
-        let semaphore = self.semaphore.acquire().await?;
+        // resumable since the result is verified with blake3
+        self.fetch(&art.name, src, &cache, Some(art.len), true)
+            .await?;
 
-        let mut writer = BufWriter::new(File::create(&cache).await?);
+        if !art.verify(&cache).await? {
+            bail!("invalid download");
+        }
 
-        let span = Span::current();
-        let trunc: String = art.name.chars().take(8).collect();
-        span.pb_set_message(&trunc);
-        span.pb_set_style(&PROGRESS_STYLE_DOWNLOAD);
-        span.pb_set_length(art.len);
+        self.add_or_update(art.clone()).await?;
 
-        let req = self.http.get(src).build()?;
-        let mut res = self.http.execute(req).await?;
+        mv(&cache, &path).await?;
+        self.apply_store_mode(&path).await?;
+        self.touch(&art.blake3).await?;
 
-        while let Some(chunk) = res.chunk().await? {
-            writer.write_all(&chunk).await?;
-            span.pb_inc(chunk.len() as u64);
-        }
+        Ok(path)
+    }
 
-        writer.shutdown().await?;
+    /// Like [`Creeper::store_artifact`], but with the BLAKE3 hash of `file` already computed.
+    pub(crate) async fn store_hashed(
+        &self,
+        file: impl AsRef<Path>,
+        b3: String,
+    ) -> anyhow::Result<Artifact> {
+        let file = file.as_ref();
+
+        if let Some(mut art) = self.get(&b3).await? {
+            if self.affix_sidecar(file, &mut art).await? {
+                self.add_or_update(art.clone()).await?;
+            }
+            return Ok(art);
+        }
 
-        drop(semaphore);
+        let name = file
+            .file_name()
+            .ok_or(anyhow!("missing filename"))?
+            .to_str()
+            .ok_or(anyhow!("invalid filename"))?;
 
-        info!("download finished");
+        let metadata = metadata(file).await?;
+        let len = metadata.len();
 
-        set_readonly(&cache).await?;
+        let mut art = Artifact::new(b3, name.into(), None, len);
+        self.affix_sidecar(file, &mut art).await?;
 
-        if !art.verify(&cache).await? {
-            bail!("invalid download");
+        if !self.has_storage(&art.blake3).await? {
+            let storage = self.storage_path(&art.blake3);
+            ensure_dir(storage.parent().unwrap()).await?;
+            copy(file, &storage).await?;
+            set_readonly(&storage).await?;
+            self.apply_store_mode(&storage).await?;
         }
 
-        self.add_or_update(art.clone()).await?;
+        self.insert(&art).await?;
 
-        mv(&cache, &path).await?;
+        Ok(art)
+    }
 
-        Ok(path)
+    /// Affix to `art` the checksums from sidecar files of `file` it does not have yet,
+    /// returning whether any was affixed.
+    ///
+    /// Sidecars not matching the content of `file` are ignored with a warning.
+    async fn affix_sidecar(&self, file: &Path, art: &mut Artifact) -> anyhow::Result<bool> {
+        let claimed = checksum::read_sidecar(file).await?;
+
+        affix_verified(file, art, claimed).await
     }
 }
 
@@ -285,14 +451,17 @@ impl Creeper {
         self.artifact.retrieve(art).await
     }
 
-    /// Retrieve an artifact and create a soft link to it at the specified path.
+    /// Retrieve an artifact and create a soft link to it at the specified path,
+    /// or place it otherwise as configured in [`crate::Config::deploy`].
     /// Creating parent directories if necessary.
     ///
     /// If `path` exists and is a soft link matching the specified artifact, this function does only update the artifact database.
     /// Repeated calls to this function is guaranteed idempotent.
     ///
-    /// If `path` exists and is a soft link that does not match the specified artifact,
-    /// **or** if `path` exists and is not a soft link, the function fails.
+    /// If `path` exists and is a soft link that does not match the specified artifact, it is replaced.
+    /// If `path` exists and is a regular file that does not match, it is replaced if deployed by creeper earlier,
+    /// e.g. an outdated library copied with [`DeployMode::Copy`], otherwise the function fails.
+    /// Regular files are accepted in place of soft links unless deploying with soft links.
     ///
     /// See [`Self::retrieve_artifact`] for details and caveats.
     pub async fn retrieve_artifact_to(
@@ -307,38 +476,58 @@ impl Creeper {
             dst.display()
         );
 
+        let mode = self.config.deploy;
+
+        let record = std::path::absolute(dst)?.display().to_string();
+
         if dst.exists() {
-            if !dst.is_symlink() {
+            if mode == DeployMode::Symlink && !dst.is_symlink() {
                 bail!(
                     "can not retrieve artifact to {} because it already exists and is not a soft link",
                     dst.display()
                 );
             }
 
-            if !art.verify(dst).await? {
+            if art.verify(dst).await? {
+                trace!(
+                    "found valid artifact at {}, skipping retrieval",
+                    dst.display()
+                );
+
+                self.artifact.add_or_update(art.clone()).await?;
+                self.artifact.record_deployed(&record, &art.blake3).await?;
+
+                return Ok(());
+            }
+
+            // soft links are created by creeper, so one to another artifact is outdated rather than modified by the user
+            if dst.is_symlink() {
+                debug!("replacing outdated link at {}", dst.display());
+            } else if self.artifact.deployed(&record).await? {
+                debug!("replacing outdated file at {}", dst.display());
+                remove_file(dst).await?;
+            } else {
                 bail!(
                     "can not retrieve artifact to {}, refusing to overwrite",
                     dst.display()
                 );
             }
+        }
 
-            trace!(
-                "found valid artifact at {}, skipping retrieval",
-                dst.display()
-            );
-
-            self.artifact.add_or_update(art.clone()).await?;
-
-            return Ok(());
+        if dst.is_symlink() {
+            // dangling, e.g. the artifact storage has been relocated, or outdated
+            remove_file(dst).await?;
         }
 
         let src = self.retrieve_artifact(art).await?;
 
         if let Some(parent) = dst.parent() {
-            create_dir_all(parent).await?;
+            ensure_dir(parent).await?;
         }
 
-        symlink_auto(src, dst).await?;
+        deploy(src, dst, mode, art.executable).await?;
+
+        self.artifact.record_deployed(&record, &art.blake3).await?;
 
         Ok(())
     }
@@ -366,30 +555,192 @@ impl Creeper {
 
         let b3 = blake3(file).await?;
 
-        if let Some(art) = self.artifact.get(&b3).await? {
-            return Ok(art);
+        self.store_hashed(file, b3).await
+    }
+
+    /// Like [`Self::store_artifact`], but with the BLAKE3 hash of `file` already computed.
+    pub(crate) async fn store_hashed(
+        &self,
+        file: impl AsRef<Path>,
+        b3: String,
+    ) -> anyhow::Result<Artifact> {
+        self.artifact.store_hashed(file, b3).await
+    }
+
+    /// Affix to the stored `art` the `claimed` checksums matching its content, e.g. published as sidecar files,
+    /// and index them.
+    ///
+    /// Checksums not matching are ignored with a warning.
+    pub async fn affix_claimed(
+        &self,
+        art: &mut Artifact,
+        claimed: Vec<Checksum>,
+    ) -> anyhow::Result<()> {
+        let file = self.retrieve_artifact(art).await?;
+
+        if affix_verified(&file, art, claimed).await? {
+            self.artifact.add_or_update(art.clone()).await?;
         }
 
-        let name = file
-            .file_name()
-            .ok_or(anyhow!("missing filename"))?
-            .to_str()
-            .ok_or(anyhow!("invalid filename"))?;
+        Ok(())
+    }
 
-        let metadata = metadata(file).await?;
-        let len = metadata.len();
+    /// Check whether an artifact is present in storage with matching content.
+    pub async fn verify_artifact(&self, art: &Artifact) -> anyhow::Result<bool> {
+        self.artifact.check_storage(&art.blake3).await
+    }
 
-        let art = Artifact::new(b3, name.into(), None, len);
+    /// Move a file already known to match `art` into storage and index it.
+    pub(crate) async fn store_moved(
+        &self,
+        file: impl AsRef<Path>,
+        art: &Artifact,
+    ) -> anyhow::Result<()> {
+        let file = file.as_ref();
 
-        if !self.artifact.has_storage(&art.blake3).await? {
-            let storage = art.path()?;
-            create_dir_all(storage.parent().unwrap()).await?;
-            copy(file, &storage).await?;
-            set_readonly(&storage).await?;
+        if self.artifact.has_storage(&art.blake3).await? {
+            remove_file(file).await?;
+        } else {
+            let storage = self.artifact.storage_path(&art.blake3);
+            set_readonly(file).await?;
+            mv(file, &storage).await?;
+            self.artifact.apply_store_mode(&storage).await?;
         }
 
-        self.artifact.insert(&art).await?;
+        self.artifact.add_or_update(art.clone()).await?;
 
-        Ok(art)
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl ArtifactManager {
+    /// An artifact manager on an in-memory index, for tests not touching the artifact storage itself.
+    pub(crate) async fn in_memory() -> Self {
+        let index = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        index.execute(DB_INIT_QUERY).await.unwrap();
+
+        Self {
+            offline: false,
+            verify: true,
+            http: Client::new(),
+            fetch: FetchOption::default(),
+            dir: PathBuf::new(),
+            index,
+            semaphore: Semaphore::new(4),
+            explain: false,
+            store_mode: None,
+        }
+    }
+
+    /// An artifact manager with its storage and index under `dir`, like a separate creeper process.
+    pub(crate) async fn in_dir(dir: &Path) -> Self {
+        let config = crate::Config {
+            storage: Some(dir.into()),
+            ..Default::default()
+        };
+        ensure_dir(dir).await.unwrap();
+        Self::new(
+            Client::new(),
+            FetchOption::default(),
+            &config,
+            &Args::default(),
+        )
+        .await
+        .unwrap()
+    }
+}
+
+/// Affix to `art` the `claimed` checksums of `file` it does not have yet, returning whether any was affixed.
+///
+/// Checksums not matching the content of `file` are ignored with a warning.
+async fn affix_verified(
+    file: &Path,
+    art: &mut Artifact,
+    claimed: Vec<Checksum>,
+) -> anyhow::Result<bool> {
+    let claimed = claimed
+        .into_iter()
+        .filter(|sum| !art.has_checksum(sum.function))
+        .collect();
+
+    let (matched, mismatched) = checksum::verify_claimed(file, claimed).await?;
+
+    for sum in mismatched {
+        warn!(
+            "ignoring {} checksum of {}, which does not match its content",
+            sum.function,
+            file.display()
+        );
+    }
+
+    let affixed = !matched.is_empty();
+    for sum in matched {
+        debug!("affixing checksum {sum} to {art}");
+        art.affix_checksum(sum);
+    }
+
+    Ok(affixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn store_mode_never_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stored");
+        tokio::fs::write(&path, "content").await.unwrap();
+
+        let manager = ArtifactManager {
+            store_mode: Some(0o666),
+            ..ArtifactManager::in_memory().await
+        };
+        manager.apply_store_mode(&path).await.unwrap();
+
+        let mode = metadata(&path).await.unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o444);
+    }
+
+    #[tokio::test]
+    async fn no_verify_trusts_index() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let content = b"content";
+        let art = Artifact::new(
+            blake3::hash(content).to_hex().to_string(),
+            "file".into(),
+            None,
+            content.len() as u64,
+        );
+
+        let manager = ArtifactManager::in_dir(dir.path()).await;
+        let path = manager.storage_path(&art.blake3);
+        create_dir_all(path.parent().unwrap()).await.unwrap();
+        tokio::fs::write(&path, "tampered").await.unwrap();
+        manager.add_or_update(art.clone()).await.unwrap();
+
+        // offline, so that retrieving fails exactly if it would download again
+        let trusting = ArtifactManager {
+            verify: false,
+            offline: true,
+            ..ArtifactManager::in_dir(dir.path()).await
+        };
+        assert_eq!(trusting.retrieve(&art).await.unwrap(), path);
+
+        let verifying = ArtifactManager {
+            verify: true,
+            offline: true,
+            ..ArtifactManager::in_dir(dir.path()).await
+        };
+        assert!(verifying.retrieve(&art).await.is_err());
     }
 }
@@ -2,30 +2,65 @@ use std::{
     collections::HashMap,
     hash::Hash,
     path::{Path, PathBuf},
+    pin::pin,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
 };
 
-use futures::{StreamExt, TryStreamExt, stream};
-use tracing::debug;
+use anyhow::bail;
+use futures::{
+    StreamExt, TryStreamExt,
+    future::{Either, select},
+    stream,
+};
+use tokio::time::interval;
+use tracing::{debug, error, info};
 
-use crate::{Artifact, Checksum, Creeper};
+use crate::{
+    Artifact, Checksum, Creeper,
+    artifact::ArtifactManager,
+    checksum::blake3,
+    path::{creeper_cache_dir, creeper_data_dir, creeper_tmp_dir},
+    util::{check_relative, check_within},
+};
 
 impl Creeper {
     /// Parallel retrieve artifacts and create soft links.
     /// Each artifact is keyed by its relative path under the base path.
     ///
+    /// Nothing is retrieved if any path is absolute or escapes the base path with `..`,
+    /// or if the base path is outside the directories managed by creeper, see [`Self::deploy_roots`].
+    ///
     /// See [`Self::retrieve_artifact_to`] for details and caveats.
     pub async fn batch_retrieve_artifact_to(
         &self,
         map: HashMap<PathBuf, Artifact>,
         base: impl AsRef<Path>,
+    ) -> anyhow::Result<()> {
+        let roots = self.deploy_roots().await?;
+        self.batch_retrieve_artifact_within(map, base, &roots).await
+    }
+
+    /// Like [`Self::batch_retrieve_artifact_to`], but the base path must be inside one of `roots` instead.
+    pub async fn batch_retrieve_artifact_within(
+        &self,
+        map: HashMap<PathBuf, Artifact>,
+        base: impl AsRef<Path>,
+        roots: &[PathBuf],
     ) -> anyhow::Result<()> {
         let base = base.as_ref();
 
+        check_within(base, roots)?;
+
+        for path in map.keys() {
+            check_relative(path)?;
+        }
+
         let count = stream::iter(map)
             .map(
                 |(path, art)| async move { self.retrieve_artifact_to(&art, base.join(path)).await },
             )
-            .buffer_unordered(self.config.parallel_download)
+            .buffer_unordered(self.config.parallel_download())
             .try_collect::<Vec<_>>()
             .await?
             .len();
@@ -35,9 +70,33 @@ impl Creeper {
         Ok(())
     }
 
+    /// Directories artifacts may be deployed into, i.e. the current game instance if any,
+    /// the data, cache and temporary directories of creeper, and [`crate::Config::natives_dir`].
+    pub async fn deploy_roots(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut roots = vec![
+            creeper_data_dir()?,
+            creeper_cache_dir()?,
+            creeper_tmp_dir()?,
+        ];
+
+        // e.g. running a forge installer outside any instance
+        if let Ok(dir) = self.game_dir().await {
+            roots.push(dir.clone());
+        }
+
+        roots.extend(self.config.natives_dir.clone());
+
+        Ok(roots)
+    }
+
     /// Parallel download a batch of files keyed by `K` and store them in the artifact storage.
     /// Each file is described by a 4-tuple of `(name, src, len, checksum)`,
     /// as specified in [`Self::download`].
+    ///
+    /// Progress is logged every [`HEARTBEAT_INTERVAL`] so that long downloads show liveness without progress bars.
+    ///
+    /// The number of concurrent downloads is bounded by [`crate::Config::parallel_download()`].
+    /// A failed download does not abort the others, and the error lists all files that failed.
     pub async fn batch_download<K>(
         &self,
         download: HashMap<
@@ -53,16 +112,182 @@ impl Creeper {
     where
         K: Eq + Hash,
     {
-        let map = stream::iter(download)
-            .map(|(k, (name, src, len, checksum))| async move {
-                self.download(name, src, len, checksum)
-                    .await
-                    .map(|a| (k, a))
+        let total = download.len();
+        let started = AtomicUsize::new(0);
+        let done = AtomicUsize::new(0);
+        let bytes = AtomicU64::new(0);
+
+        let work = stream::iter(download)
+            .map(|(k, (name, src, len, checksum))| {
+                let started = &started;
+                let done = &done;
+                let bytes = &bytes;
+                async move {
+                    started.fetch_add(1, Ordering::Relaxed);
+                    let res = self.download(name.clone(), src, len, checksum).await;
+                    done.fetch_add(1, Ordering::Relaxed);
+                    if let Ok(art) = &res {
+                        bytes.fetch_add(art.len, Ordering::Relaxed);
+                    }
+                    (k, name, res)
+                }
             })
-            .buffer_unordered(self.config.parallel_download)
-            .try_collect::<HashMap<_, _>>()
-            .await?;
+            .buffer_unordered(self.config.parallel_download())
+            .collect::<Vec<_>>();
+
+        let heartbeat = async {
+            let mut interval = interval(HEARTBEAT_INTERVAL);
+            // the first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let done = done.load(Ordering::Relaxed);
+                info!(
+                    "downloaded {done}/{total} files, {:.1} MB, {} in flight",
+                    bytes.load(Ordering::Relaxed) as f64 / 1e6,
+                    started.load(Ordering::Relaxed) - done
+                );
+            }
+        };
+
+        let result = match select(pin!(work), pin!(heartbeat)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => unreachable!(),
+        };
+
+        let mut map = HashMap::new();
+        let mut failed = vec![];
+
+        for (k, name, res) in result {
+            match res {
+                Ok(art) => {
+                    map.insert(k, art);
+                }
+                Err(e) => {
+                    error!("failed to download {name}: {e}");
+                    failed.push(name);
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            failed.sort();
+            bail!(
+                "failed to download {} of {total} files: {}",
+                failed.len(),
+                failed.join(", ")
+            );
+        }
 
         Ok(map)
     }
 }
+
+/// Summary of [`Creeper::batch_store_artifact`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StoreStat {
+    /// Number of files stored.
+    pub count: usize,
+    /// Number of distinct contents among the files.
+    pub unique: usize,
+    /// Number of distinct contents not in storage before.
+    pub new: usize,
+}
+
+impl Creeper {
+    /// Parallel store local files to the artifact storage, see [`Self::store_artifact`].
+    ///
+    /// Files are hashed first, so that each distinct content is stored only once.
+    pub async fn batch_store_artifact(
+        &self,
+        files: Vec<PathBuf>,
+    ) -> anyhow::Result<(HashMap<PathBuf, Artifact>, StoreStat)> {
+        self.artifact
+            .batch_store(files, self.config.parallel_download())
+            .await
+    }
+}
+
+impl ArtifactManager {
+    /// Like [`Creeper::batch_store_artifact`], storing at most `parallel` files at once.
+    pub(crate) async fn batch_store(
+        &self,
+        files: Vec<PathBuf>,
+        parallel: usize,
+    ) -> anyhow::Result<(HashMap<PathBuf, Artifact>, StoreStat)> {
+        let count = files.len();
+
+        let hashed = stream::iter(files)
+            .map(|file| async move {
+                let b3 = blake3(&file).await?;
+                anyhow::Ok((file, b3))
+            })
+            .buffer_unordered(parallel)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut content = HashMap::<_, Vec<_>>::new();
+        for (file, b3) in hashed {
+            content.entry(b3).or_default().push(file);
+        }
+
+        let unique = content.len();
+
+        let stored = stream::iter(content)
+            .map(|(b3, files)| async move {
+                let new = self.get(&b3).await?.is_none();
+                let art = self.store_hashed(&files[0], b3).await?;
+                anyhow::Ok((files, art, new))
+            })
+            .buffer_unordered(parallel)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let new = stored.iter().filter(|(_, _, new)| *new).count();
+
+        let map = stored
+            .into_iter()
+            .flat_map(|(files, art, _)| files.into_iter().map(move |f| (f, art.clone())))
+            .collect();
+
+        let stat = StoreStat { count, unique, new };
+
+        Ok((map, stat))
+    }
+}
+
+/// Interval of progress logs during [`Creeper::batch_download`].
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs::write;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn batch_store_registered() {
+        let dir = tempfile::tempdir().unwrap();
+        let files =
+            ["sodium.jar", "lithium.jar", "sodium-copy.jar"].map(|name| dir.path().join(name));
+        for (file, content) in files.iter().zip(["sodium", "lithium", "sodium"]) {
+            write(file, content).await.unwrap();
+        }
+
+        let manager = ArtifactManager::in_dir(&dir.path().join("storage")).await;
+        let (map, stat) = manager.batch_store(files.to_vec(), 2).await.unwrap();
+        assert_eq!((stat.count, stat.unique, stat.new), (3, 2, 2));
+
+        // files of identical content share the stored artifact
+        assert_eq!(map[&files[0]], map[&files[2]]);
+
+        for art in map.values() {
+            assert_eq!(manager.get(&art.blake3).await.unwrap().as_ref(), Some(art));
+            assert!(manager.check_storage(&art.blake3).await.unwrap());
+        }
+
+        // importing again stores nothing new
+        let (_, stat) = manager.batch_store(files.to_vec(), 2).await.unwrap();
+        assert_eq!(stat.new, 0);
+    }
+}
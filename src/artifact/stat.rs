@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, query_as};
+
+use crate::{Artifact, artifact::ArtifactManager};
+
+/// An indexed artifact along with its bookkeeping in the index.
+#[derive(Clone, Debug, FromRow)]
+pub struct StoredArtifact {
+    #[sqlx(flatten)]
+    pub artifact: Artifact,
+
+    /// Size of the compressed file in storage, if compressed.
+    pub stored_len: Option<i64>,
+
+    /// Unix timestamp the artifact was last retrieved at, to within an hour.
+    ///
+    /// This is `None` for artifacts not retrieved since the index started recording it.
+    pub last_accessed: Option<i64>,
+}
+
+impl StoredArtifact {
+    /// See [`Self::last_accessed`].
+    pub fn last_accessed(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.last_accessed?, 0)
+    }
+}
+
+/// Summary of the whole artifact storage, see [`ArtifactManager::stat`].
+#[derive(Clone, Debug, FromRow)]
+pub struct StorageStat {
+    /// Number of indexed artifacts.
+    pub count: i64,
+
+    /// Total size of the artifacts uncompressed.
+    pub len: i64,
+
+    /// Number of compressed artifacts.
+    pub compressed: i64,
+
+    /// Total size of the artifacts in storage, i.e. with compressed ones at their compressed size.
+    pub stored_len: i64,
+
+    /// Number of artifacts never retrieved since the index started recording accesses.
+    pub never_accessed: i64,
+
+    /// Unix timestamp of the least recent access among artifacts ever accessed.
+    pub oldest_access: Option<i64>,
+}
+
+impl StorageStat {
+    /// See [`Self::oldest_access`].
+    pub fn oldest_access(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.oldest_access?, 0)
+    }
+}
+
+impl ArtifactManager {
+    /// Find indexed artifacts whose name or any checksum equals `query`, or whose blake3 starts with it.
+    pub async fn find(&self, query: &str) -> anyhow::Result<Vec<StoredArtifact>> {
+        let found = query_as(
+            "SELECT * FROM artifact WHERE name = ?1 OR blake3 LIKE ?1 || '%' OR sha1 = ?1 OR sha256 = ?1 OR md5 = ?1 ORDER BY name",
+        )
+        .bind(query)
+        .fetch_all(&self.index)
+        .await?;
+
+        Ok(found)
+    }
+
+    /// Summarize the artifact storage from the index, without touching stored files.
+    pub async fn stat(&self) -> anyhow::Result<StorageStat> {
+        let stat = query_as(
+            "SELECT
+                COUNT(*) AS count,
+                COALESCE(SUM(len), 0) AS len,
+                COUNT(stored_len) AS compressed,
+                COALESCE(SUM(COALESCE(stored_len, len)), 0) AS stored_len,
+                COUNT(*) - COUNT(last_accessed) AS never_accessed,
+                MIN(last_accessed) AS oldest_access
+            FROM artifact",
+        )
+        .fetch_one(&self.index)
+        .await?;
+
+        Ok(stat)
+    }
+}
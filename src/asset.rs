@@ -1,15 +1,31 @@
-use std::{collections::HashMap, iter::once, path::PathBuf};
+use std::{
+    collections::HashMap,
+    iter::once,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, ensure};
 use mc_launchermeta::version as mc_version;
 use serde::{Deserialize, Serialize};
-use tokio::fs::read_to_string;
+use tokio::fs::{create_dir_all, read_to_string, write};
 
 use crate::{Artifact, Checksum, Creeper, Install};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AssetIndex {
     pub objects: HashMap<PathBuf, Object>,
+
+    /// Whether the game expects objects by their logical path under `virtual/<id>/`.
+    #[serde(
+        default,
+        rename = "virtual",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub is_virtual: bool,
+
+    /// Whether the game expects objects by their logical path under `resources/` of the game directory.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub map_to_resources: bool,
 }
 
 impl AssetIndex {
@@ -29,7 +45,11 @@ impl AssetIndex {
             );
         }
 
-        Ok(Self { objects })
+        Ok(Self {
+            objects,
+            is_virtual: false,
+            map_to_resources: false,
+        })
     }
 }
 
@@ -66,28 +86,114 @@ impl Creeper {
     }
 
     pub async fn vanilla_asset_install(&self, index: AssetIndex) -> anyhow::Result<Install> {
-        let mut map = HashMap::new();
+        let AssetIndex {
+            objects,
+            is_virtual,
+            map_to_resources,
+        } = index;
+
+        // objects with identical content are referenced by multiple paths, download each only once
+        let mut path_of = HashMap::<String, Vec<PathBuf>>::new();
+
+        let mut download = HashMap::new();
+
+        for (path, obj) in objects {
+            path_of
+                .entry(obj.sha1.clone())
+                .or_default()
+                .push(path.clone());
+
+            if download.contains_key(&obj.sha1) {
+                continue;
+            }
 
-        for (path, obj) in index.objects {
             let name = path.display().to_string();
 
             let src = asset_download_url(&obj.sha1)?;
 
-            map.insert(
-                path,
+            download.insert(
+                obj.sha1.clone(),
                 (name, src, Some(obj.size), once(Checksum::sha1(obj.sha1))),
             );
         }
 
-        let map = self.batch_download(map).await?;
+        let mut map = HashMap::new();
+
+        for (sha1, art) in self.batch_download(download).await? {
+            for path in path_of.remove(&sha1).unwrap_or_default() {
+                map.insert(path, art.clone());
+            }
+        }
 
         let value = Install {
             mc_asset: map,
+            mc_asset_virtual: is_virtual,
+            mc_asset_map_to_resources: map_to_resources,
             ..Default::default()
         };
 
         Ok(value)
     }
+
+    /// Deploy assets into `dir` and return the path to pass as `--assetsDir`.
+    ///
+    /// Objects are placed at `objects/<sha1[..2]>/<sha1>` and the index at `indexes/<id>.json`,
+    /// regardless of how they are addressed in the artifact storage.
+    /// Virtual assets are additionally placed by their logical path under `virtual/<id>/`,
+    /// which is then used as the assets directory,
+    /// and assets mapped to resources by their logical path under `resources/` of the game directory.
+    pub async fn deploy_asset(
+        &self,
+        asset: HashMap<PathBuf, Artifact>,
+        is_virtual: bool,
+        map_to_resources: bool,
+        dir: impl AsRef<Path>,
+        id: &str,
+    ) -> anyhow::Result<PathBuf> {
+        let dir = dir.as_ref();
+
+        let mut objects = HashMap::new();
+
+        for art in asset.values() {
+            let sha1 = art.sha1.as_ref().ok_or(anyhow!("missing SHA-1 checksum"))?;
+            objects.insert(object_path(sha1)?, art.clone());
+        }
+
+        self.batch_retrieve_artifact_to(objects, dir.join("objects"))
+            .await?;
+
+        let mut assets_dir = dir.to_path_buf();
+
+        if is_virtual {
+            assets_dir = dir.join("virtual").join(id);
+            self.batch_retrieve_artifact_to(asset.clone(), &assets_dir)
+                .await?;
+        }
+
+        if map_to_resources {
+            let resources = self.game_dir().await?.join("resources");
+            self.batch_retrieve_artifact_to(asset.clone(), resources)
+                .await?;
+        }
+
+        let mut index = AssetIndex::from_map(asset)?;
+        index.is_virtual = is_virtual;
+        index.map_to_resources = map_to_resources;
+
+        let json = serde_json::to_string(&index)?;
+        let path = dir.join("indexes").join(id).with_added_extension("json");
+        create_dir_all(path.parent().unwrap()).await?;
+        write(path, json).await?;
+
+        Ok(assets_dir)
+    }
+}
+
+/// Path of an asset object relative to the `objects` directory, i.e. `<sha1[..2]>/<sha1>`.
+pub fn object_path(sha1: &str) -> anyhow::Result<PathBuf> {
+    ensure!(sha1.len() == 40, "invalid sha1 length");
+    let first2 = &sha1[0..2];
+    Ok(PathBuf::from(first2).join(sha1))
 }
 
 pub fn asset_download_url(sha1: &str) -> anyhow::Result<String> {
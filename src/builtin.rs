@@ -137,6 +137,7 @@ impl Creeper {
             "vanilla-server" => self.vanilla_server_install(version).await?,
             "neoforge" => self.neoforge_install(version).await?,
             "neoforge-server" => self.neoforge_server_install(version).await?,
+            "forge" => self.forge_install(version).await?,
             "fabric" => self.fabric_install(version).await?,
             "intermediary" => self.intermediary_install(version).await?,
             p => todo!("install builtin package {p}"),
@@ -150,6 +151,7 @@ impl Creeper {
         self.vanilla_server.update_index().await?;
         self.neoforge.update_index().await?;
         self.neoforge_server.update_index().await?;
+        self.forge.update_index().await?;
         self.fabric.update_index().await?;
         self.intermediary.update_index().await?;
 
@@ -166,6 +168,7 @@ impl Creeper {
             "vanilla-server" => self.vanilla_server.get_index().await?,
             "neoforge" => self.neoforge.get_index().await?,
             "neoforge-server" => self.neoforge_server.get_index().await?,
+            "forge" => self.forge.get_index().await?,
             "fabric" => self.fabric.get_index().await?,
             "intermediary" => self.intermediary.get_index().await?,
             p => todo!("index builtin package {p}"),
@@ -184,6 +187,7 @@ impl Creeper {
             "vanilla-server" => self.vanilla_server.blocking_get_index()?,
             "neoforge" => self.neoforge.blocking_get_index()?,
             "neoforge-server" => self.neoforge_server.blocking_get_index()?,
+            "forge" => self.forge.blocking_get_index()?,
             "fabric" => self.fabric.blocking_get_index()?,
             "intermediary" => self.intermediary.blocking_get_index()?,
             p => todo!("blocking index builtin package {p}"),
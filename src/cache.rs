@@ -0,0 +1,116 @@
+use std::{path::Path, time::Duration};
+
+use tokio::fs::{remove_file, try_exists};
+use tracing::{debug, trace};
+use walkdir::WalkDir;
+
+use crate::path::{creeper_download_cache_dir, creeper_extract_cache_dir};
+
+/// Default of [`crate::Config::stale_cache_hours`].
+pub const DEFAULT_STALE_CACHE_HOURS: u64 = 24;
+
+/// Summary of [`clean_stale_cache`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CleanStat {
+    /// Number of files removed.
+    pub count: usize,
+    /// Total size of the removed files.
+    pub len: u64,
+}
+
+/// Minimum age of a stale partial download, which may still be resumed by a later run.
+const PARTIAL_DOWNLOAD_MIN_AGE: Duration = Duration::from_hours(30 * 24);
+
+/// Remove temporary files in the cache not modified for `older_than`,
+/// e.g. downloads left behind by a killed process.
+///
+/// Such files are not indexed anywhere, so staleness is decided by modification time alone.
+/// Downloads in progress are written continuously, and are thus never considered stale.
+/// Partial downloads (`.part`) are resumed by the next attempt, so they are kept for at least [`PARTIAL_DOWNLOAD_MIN_AGE`].
+pub async fn clean_stale_cache(older_than: Duration) -> anyhow::Result<CleanStat> {
+    let mut stat = CleanStat::default();
+
+    for dir in [creeper_download_cache_dir()?, creeper_extract_cache_dir()?] {
+        clean_stale_dir(&dir, older_than, &mut stat).await?;
+    }
+
+    debug!("removed {} stale cache files", stat.count);
+
+    Ok(stat)
+}
+
+/// See [`clean_stale_cache`].
+async fn clean_stale_dir(
+    dir: &Path,
+    older_than: Duration,
+    stat: &mut CleanStat,
+) -> anyhow::Result<()> {
+    if !try_exists(dir).await? {
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        let path = entry.path();
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let older_than = if path.extension().is_some_and(|ext| ext == "part") {
+            older_than.max(PARTIAL_DOWNLOAD_MIN_AGE)
+        } else {
+            older_than
+        };
+
+        let meta = entry.metadata()?;
+        // a modification time in the future is as fresh as it gets
+        if meta
+            .modified()?
+            .elapsed()
+            .map_or(true, |age| age < older_than)
+        {
+            continue;
+        }
+
+        trace!("removing stale cache file {}", path.display());
+        remove_file(path).await?;
+
+        stat.count += 1;
+        stat.len += meta.len();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::SystemTime};
+
+    use super::*;
+
+    fn touch(path: &Path, age: Duration) {
+        let file = File::create(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keep_partial_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = Duration::from_hours(24);
+
+        touch(&dir.path().join("stale"), 2 * day);
+        touch(&dir.path().join("fresh"), Duration::ZERO);
+        touch(&dir.path().join("resumable.part"), 2 * day);
+        touch(&dir.path().join("abandoned.part"), 60 * day);
+
+        let mut stat = CleanStat::default();
+        clean_stale_dir(dir.path(), day, &mut stat).await.unwrap();
+
+        assert_eq!(stat.count, 2);
+        assert!(!dir.path().join("stale").exists());
+        assert!(dir.path().join("fresh").exists());
+        assert!(dir.path().join("resumable.part").exists());
+        assert!(!dir.path().join("abandoned.part").exists());
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt::Display,
     fs::File,
     io::{BufReader, Read},
@@ -8,6 +9,7 @@ use std::{
 
 use anyhow::anyhow;
 use const_hex::ToHexExt;
+use md5::{Digest, Md5};
 use ring::digest::{Algorithm, Context, SHA1_FOR_LEGACY_USE_ONLY, SHA256};
 use tokio::task::spawn_blocking;
 use tracing::debug;
@@ -34,6 +36,25 @@ pub async fn sha256(file: impl AsRef<Path>) -> anyhow::Result<String> {
     spawn_blocking(|| ring(file, &SHA256)).await?
 }
 
+/// MD5 is broken and only supported to verify files from legacy sources publishing nothing stronger.
+pub async fn md5(file: impl AsRef<Path>) -> anyhow::Result<String> {
+    fn calc(file: impl AsRef<Path>) -> anyhow::Result<String> {
+        let mut reader = BufReader::new(File::open(file)?);
+        let mut hasher = Md5::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hasher.finalize().encode_hex())
+    }
+    let file = file.as_ref().to_owned();
+    spawn_blocking(|| calc(file)).await?
+}
+
 fn ring(file: impl AsRef<Path>, algorithm: &'static Algorithm) -> anyhow::Result<String> {
     let mut reader = BufReader::new(File::open(file)?);
     let mut ctx = Context::new(algorithm);
@@ -49,6 +70,135 @@ fn ring(file: impl AsRef<Path>, algorithm: &'static Algorithm) -> anyhow::Result
     Ok(digest.encode_hex())
 }
 
+/// Streaming state of any supported hash function.
+enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Ring(Context),
+    Md5(Md5),
+}
+
+impl Hasher {
+    fn new(func: HashFunc) -> Self {
+        match func {
+            HashFunc::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+            HashFunc::Sha1 => Self::Ring(Context::new(&SHA1_FOR_LEGACY_USE_ONLY)),
+            HashFunc::Sha256 => Self::Ring(Context::new(&SHA256)),
+            HashFunc::Md5 => Self::Md5(Md5::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Self::Ring(ctx) => ctx.update(data),
+            Self::Md5(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Self::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Self::Ring(ctx) => ctx.finish().encode_hex(),
+            Self::Md5(hasher) => hasher.finalize().encode_hex(),
+        }
+    }
+}
+
+/// Calculate several hashes of a file in a single pass over its content.
+///
+/// Prefer the single-hash helpers like [`blake3`] when only one is needed,
+/// since they use faster specialized implementations.
+pub async fn calc_multi(
+    file: impl AsRef<Path>,
+    funcs: &[HashFunc],
+) -> anyhow::Result<HashMap<HashFunc, String>> {
+    fn calc(file: &Path, funcs: Vec<HashFunc>) -> anyhow::Result<HashMap<HashFunc, String>> {
+        let mut reader = BufReader::new(File::open(file)?);
+        let mut hashers = funcs
+            .into_iter()
+            .map(|func| (func, Hasher::new(func)))
+            .collect::<HashMap<_, _>>();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            for hasher in hashers.values_mut() {
+                hasher.update(&buf[..n]);
+            }
+        }
+        let sums = hashers
+            .into_iter()
+            .map(|(func, hasher)| (func, hasher.finish()))
+            .collect();
+        Ok(sums)
+    }
+    let file = file.as_ref().to_owned();
+    let funcs = funcs.to_vec();
+    let path = file.clone();
+    let sums = spawn_blocking(move || calc(&path, funcs)).await??;
+    debug!("calculated {} hashes of {}", sums.len(), file.display());
+    Ok(sums)
+}
+
+/// Hash functions of sidecar checksum files like `foo.jar.sha1`, named by their extension, strongest first.
+pub const SIDECAR: [HashFunc; 3] = [HashFunc::Sha256, HashFunc::Sha1, HashFunc::Md5];
+
+/// Parse the content of a sidecar checksum file of `function`, i.e. a hex hash optionally followed by the file name.
+///
+/// The hash must be as long as `function` produces, so that e.g. an HTML error page is never taken for a hash.
+pub fn parse_sidecar(content: &str, function: HashFunc) -> Option<String> {
+    let hash = content.split_whitespace().next()?.to_ascii_lowercase();
+    (hash.len() == function.hex_len() && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .then_some(hash)
+}
+
+/// Split the `claimed` checksums, e.g. from sidecar files, into those matching the content of `file` and those not.
+pub async fn verify_claimed(
+    file: impl AsRef<Path>,
+    claimed: Vec<Checksum>,
+) -> anyhow::Result<(Vec<Checksum>, Vec<Checksum>)> {
+    if claimed.is_empty() {
+        return Ok((vec![], vec![]));
+    }
+
+    let funcs = claimed.iter().map(|sum| sum.function).collect::<Vec<_>>();
+    let actual = calc_multi(file, &funcs).await?;
+
+    let split = claimed
+        .into_iter()
+        .partition(|sum| actual.get(&sum.function) == Some(&sum.hex_hash));
+
+    Ok(split)
+}
+
+/// Checksums claimed by the sidecar files next to `file`, e.g. `foo.jar.sha1` for `foo.jar`.
+///
+/// These are not verified, and invalid sidecars are ignored.
+pub async fn read_sidecar(file: impl AsRef<Path>) -> anyhow::Result<Vec<Checksum>> {
+    let file = file.as_ref();
+
+    let mut checksum = vec![];
+
+    for function in SIDECAR {
+        let path = file.with_added_extension(function.to_string());
+
+        if !tokio::fs::try_exists(&path).await? {
+            continue;
+        }
+
+        match parse_sidecar(&tokio::fs::read_to_string(&path).await?, function) {
+            Some(hex_hash) => checksum.push(Checksum { function, hex_hash }),
+            None => debug!("ignoring invalid sidecar {}", path.display()),
+        }
+    }
+
+    Ok(checksum)
+}
+
 #[derive(Clone, Hash)]
 pub struct Checksum {
     pub function: HashFunc,
@@ -77,6 +227,13 @@ impl Checksum {
         }
     }
 
+    pub fn md5(hex_hash: String) -> Self {
+        Self {
+            function: HashFunc::Md5,
+            hex_hash,
+        }
+    }
+
     pub async fn check(&self, file: impl AsRef<Path>) -> anyhow::Result<bool> {
         let hash = self.function.calc(file).await?;
         Ok(self.hex_hash == hash)
@@ -94,9 +251,19 @@ pub enum HashFunc {
     Blake3,
     Sha1,
     Sha256,
+    Md5,
 }
 
 impl HashFunc {
+    /// Length of the hashes of this function in hex digits.
+    pub fn hex_len(&self) -> usize {
+        match self {
+            HashFunc::Blake3 | HashFunc::Sha256 => 64,
+            HashFunc::Sha1 => 40,
+            HashFunc::Md5 => 32,
+        }
+    }
+
     pub async fn calc(&self, file: impl AsRef<Path>) -> anyhow::Result<String> {
         let file = file.as_ref();
 
@@ -104,6 +271,7 @@ impl HashFunc {
             HashFunc::Blake3 => blake3(file).await,
             HashFunc::Sha1 => sha1(file).await,
             HashFunc::Sha256 => sha256(file).await,
+            HashFunc::Md5 => md5(file).await,
         }?;
 
         debug!("calculated {} {} = {}", self, file.display(), sum);
@@ -117,6 +285,7 @@ impl Display for HashFunc {
             HashFunc::Blake3 => "blake3",
             HashFunc::Sha1 => "sha1",
             HashFunc::Sha256 => "sha256",
+            HashFunc::Md5 => "md5",
         };
         write!(f, "{name}")
     }
@@ -130,7 +299,46 @@ impl FromStr for HashFunc {
             "blake3" => Ok(Self::Blake3),
             "sha1" => Ok(Self::Sha1),
             "sha256" => Ok(Self::Sha256),
+            "md5" => Ok(Self::Md5),
             _ => Err(anyhow!("unknown hash function: {s}")),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_length() {
+        let sha1 = "2FD4E1C67A2D28FCED849EE1BB76E7391B93EB12";
+
+        assert_eq!(
+            parse_sidecar(&format!("{sha1}  foo.jar\n"), HashFunc::Sha1).as_deref(),
+            Some(sha1.to_ascii_lowercase().as_str())
+        );
+        assert_eq!(parse_sidecar(sha1, HashFunc::Sha256), None);
+        assert_eq!(parse_sidecar(&sha1[..39], HashFunc::Sha1), None);
+        assert_eq!(parse_sidecar("deadbeef", HashFunc::Md5), None);
+        assert_eq!(parse_sidecar("<html>", HashFunc::Sha1), None);
+    }
+
+    #[tokio::test]
+    async fn claimed_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("foo.jar");
+        tokio::fs::write(&file, "The quick brown fox jumps over the lazy dog")
+            .await
+            .unwrap();
+
+        let good = Checksum::sha1("2fd4e1c67a2d28fced849ee1bb76e7391b93eb12".into());
+        let bad = Checksum::md5("0".repeat(32));
+
+        let (matched, mismatched) = verify_claimed(&file, vec![good.clone(), bad.clone()])
+            .await
+            .unwrap();
+        let hex = |sums: Vec<Checksum>| sums.into_iter().map(|s| s.hex_hash).collect::<Vec<_>>();
+        assert_eq!(hex(matched), [good.hex_hash]);
+        assert_eq!(hex(mismatched), [bad.hex_hash]);
+    }
+}
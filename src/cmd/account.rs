@@ -0,0 +1,84 @@
+use clap::Parser;
+use colored::Colorize;
+
+use crate::{Creeper, cmd::Execute};
+
+/// Manage the users logged in with `creeper login`.
+///
+/// Users are given by player name, authlib-injector account or UUID.
+#[derive(Clone, Debug, Parser)]
+pub enum Account {
+    List(List),
+
+    Remove(Remove),
+
+    Default(Default),
+}
+
+impl Execute for Account {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        match self {
+            Account::List(list) => lib.execute(list).await,
+            Account::Remove(remove) => lib.execute(remove).await,
+            Account::Default(default) => lib.execute(default).await,
+        }
+    }
+}
+
+/// List all users, the default one first.
+#[derive(Clone, Debug, Parser)]
+pub struct List;
+
+impl Execute for List {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let config = lib.user.list().await?;
+
+        if let Some(user) = config.default {
+            println!("{user} {}", "(default)".dimmed());
+        }
+
+        for user in config.user {
+            println!("{user}");
+        }
+
+        Ok(())
+    }
+}
+
+/// Remove a user, without logging out of its session.
+#[derive(Clone, Debug, Parser)]
+pub struct Remove {
+    #[arg(value_name = "NAME")]
+    pub name: String,
+}
+
+impl Execute for Remove {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let user = lib.find_user(&self.name).await?;
+
+        lib.user.remove(&user).await?;
+
+        eprintln!("{} {user}", "Removed".bold().green());
+
+        Ok(())
+    }
+}
+
+/// Set the user to launch games with, unless overridden with `creeper launch --user`.
+#[derive(Clone, Debug, Parser)]
+pub struct Default {
+    #[arg(value_name = "NAME")]
+    pub name: String,
+}
+
+impl Execute for Default {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let user = lib.find_user(&self.name).await?;
+
+        lib.user.set_default(user.clone()).await?;
+
+        eprintln!("{} {user} as default", "Set".bold().green());
+
+        Ok(())
+    }
+}
@@ -1,12 +1,17 @@
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use clap::Parser;
+use tokio::fs::read_to_string;
+use toml_edit::{DocumentMut, Item, Table, value};
 
 use crate::{
+    Id,
     cmd::{self, Execute},
     id::IdVersionReq,
 };
 
 /// Add dependencies to the current game instance.
+///
+/// The manifest is only rewritten if the dependencies resolve, keeping its comments and formatting.
 #[derive(Clone, Debug, Parser)]
 pub struct Add {
     /// The dependencies to add.
@@ -26,7 +31,13 @@ impl Execute for Add {
     async fn execute(self, lib: &crate::Creeper) -> anyhow::Result<()> {
         let mut pack = lib.game_pack().await?;
 
-        for IdVersionReq { id, version_req } in self.req {
+        for IdVersionReq { id, version_req } in &self.req {
+            if id == &Id::vanilla() {
+                lib.check_vanilla_version_req(version_req).await?;
+            }
+        }
+
+        for IdVersionReq { id, version_req } in &self.req {
             if let Some(exist) = pack.node.dep.insert(id.clone(), version_req.clone()) {
                 if !self.overwrite {
                     bail!(
@@ -36,12 +47,68 @@ impl Execute for Add {
             }
         }
 
-        lib.set_game_pack(pack).await?;
+        // verify that the packages exist and are compatible before touching the manifest
+        lib.update().await?;
+        let lock = lib
+            .resolve_lock(&pack)
+            .map_err(|e| anyhow!("cannot add dependencies: {e}"))?;
+
+        let path = lib.game.pack_path().await?;
+        let toml = add_dep(&read_to_string(&path).await?, &self.req)?;
+        lib.game.set_pack_toml(&toml).await?;
+
+        // the lock satisfies the new manifest, so installing does not resolve again
+        lib.set_game_lock(Some(lock)).await?;
 
-        let install = cmd::Install { update: true };
+        let install = cmd::Install {
+            update: false,
+            timeout_retry_budget: None,
+        };
 
         lib.execute(install).await?;
 
         Ok(())
     }
 }
+
+/// Insert `req` into the `dependencies` table of the manifest `toml`, keeping everything else as written.
+fn add_dep(toml: &str, req: &[IdVersionReq]) -> anyhow::Result<String> {
+    let mut doc = toml.parse::<DocumentMut>()?;
+
+    let dep = doc
+        .entry("dependencies")
+        .or_insert(Item::Table(Table::new()))
+        .as_table_like_mut()
+        .ok_or(anyhow!("dependencies in the manifest is not a table"))?;
+
+    for IdVersionReq { id, version_req } in req {
+        dep.insert(&id.to_string(), value(version_req.to_string()));
+    }
+
+    Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_dep_keeps_comments() {
+        let toml = "# my pack\nid = \"pack\"\n\n[dependencies]\n# pinned for a reason\nminecraft = \"=1.20.1\"\n";
+        let req = ["fabric@^0.16".parse().unwrap()];
+
+        let toml = add_dep(toml, &req).unwrap();
+
+        assert!(toml.starts_with("# my pack\n"));
+        assert!(toml.contains("# pinned for a reason\nminecraft = \"=1.20.1\"\n"));
+        assert!(toml.contains("fabric = \"^0.16\"\n"));
+    }
+
+    #[test]
+    fn add_dep_creates_table() {
+        let toml = add_dep("id = \"pack\"\n", &["fabric".parse().unwrap()]).unwrap();
+
+        let doc = toml.parse::<DocumentMut>().unwrap();
+        assert_eq!(doc["dependencies"]["fabric"].as_str(), Some("*"));
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use clap::Parser;
+use colored::Colorize;
+
+use crate::{
+    Creeper,
+    cache::{CleanStat, clean_stale_cache},
+    cmd::Execute,
+};
+
+/// Manage the cache of creeper.
+#[derive(Clone, Debug, Parser)]
+pub enum Cache {
+    Clean(Clean),
+}
+
+impl Execute for Cache {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        match self {
+            Cache::Clean(clean) => lib.execute(clean).await,
+        }
+    }
+}
+
+/// Remove temporary files left in the cache, e.g. by interrupted downloads.
+///
+/// Cached metadata like version manifests is kept.
+#[derive(Clone, Debug, Parser)]
+pub struct Clean {
+    /// Only remove files not modified for this many hours,
+    /// sparing downloads in progress of other creeper processes.
+    #[arg(long, value_name = "HOURS", default_value_t = 1)]
+    pub older_than: u64,
+}
+
+impl Execute for Clean {
+    async fn execute(self, _: &Creeper) -> anyhow::Result<()> {
+        let CleanStat { count, len } =
+            clean_stale_cache(Duration::from_hours(self.older_than)).await?;
+
+        eprintln!(
+            "{} {count} stale cache files of {:.1} MB",
+            "Removed".bold().green(),
+            len as f64 / 1e6
+        );
+
+        Ok(())
+    }
+}
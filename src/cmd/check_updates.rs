@@ -0,0 +1,48 @@
+use clap::Parser;
+use colored::Colorize;
+
+use crate::{Creeper, cmd::Execute};
+
+/// Check whether a new minecraft release or snapshot is out since the last check.
+///
+/// The first check only records the latest versions.
+#[derive(Clone, Debug, Parser)]
+pub struct CheckUpdates {}
+
+impl Execute for CheckUpdates {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let update = lib.check_vanilla_update().await?;
+
+        if update.previous.is_none() {
+            eprintln!(
+                "{} latest release {} and snapshot {}",
+                "Recorded".bold().green(),
+                update.latest.release,
+                update.latest.snapshot
+            );
+            return Ok(());
+        }
+
+        let release = update.new_release();
+        let snapshot = update.new_snapshot();
+
+        if let Some(release) = release {
+            eprintln!("{} release {release}", "New".bold().green());
+        }
+
+        if let Some(snapshot) = snapshot {
+            eprintln!("{} snapshot {snapshot}", "New".bold().green());
+        }
+
+        if release.is_none() && snapshot.is_none() {
+            eprintln!(
+                "{} latest release {} and snapshot {}",
+                "Unchanged".bold().green(),
+                update.latest.release,
+                update.latest.snapshot
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use clap::{Parser, ValueEnum};
+
+use crate::{Creeper, cmd::Execute, lock::lock_file};
+
+/// Show the registry and package changes between two lock files, e.g. when reviewing an update.
+#[derive(Clone, Debug, Parser)]
+pub struct DiffLock {
+    /// The lock file before the changes.
+    #[arg(value_name = "OLD")]
+    pub old: PathBuf,
+
+    /// The lock file after the changes.
+    #[arg(value_name = "NEW")]
+    pub new: PathBuf,
+
+    #[arg(long, default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    /// One line per added, removed or changed package, and for a changed registry.
+    Text,
+    /// A JSON object of added, removed and changed packages, and the changed registry if any.
+    Json,
+}
+
+impl Execute for DiffLock {
+    async fn execute(self, _lib: &Creeper) -> anyhow::Result<()> {
+        let old = lock_file()
+            .read(&self.old)
+            .await?
+            .ok_or(anyhow!("{} does not exist", self.old.display()))?;
+
+        let new = lock_file()
+            .read(&self.new)
+            .await?
+            .ok_or(anyhow!("{} does not exist", self.new.display()))?;
+
+        let diff = old.diff(&new);
+
+        match self.format {
+            Format::Text => print!("{diff}"),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+        }
+
+        Ok(())
+    }
+}
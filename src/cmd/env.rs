@@ -0,0 +1,109 @@
+use std::{collections::BTreeMap, env::var, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use tracing::level_filters::LevelFilter;
+
+use crate::{
+    Creeper, VERSION,
+    cmd::Execute,
+    path::{creeper_cache_dir, creeper_data_dir, creeper_mc_dir},
+};
+
+/// Print the environment creeper runs in, i.e. the resolved directories and settings.
+#[derive(Clone, Debug, Parser)]
+pub struct Env {
+    #[arg(long, default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    /// One `key: value` line per entry.
+    Text,
+    /// A JSON object.
+    Json,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Environment {
+    version: &'static str,
+    config: PathBuf,
+    data_dir: PathBuf,
+    cache_dir: PathBuf,
+    storage_dir: PathBuf,
+    mc_dir: PathBuf,
+    instance_dir: Option<PathBuf>,
+    log_level: String,
+    offline: bool,
+    registry: String,
+    use_bmclapi: bool,
+    /// Proxy for each destination host, or the system proxy variables if none is configured.
+    proxy: BTreeMap<String, String>,
+}
+
+/// Proxy settings from the environment, which apply unless [`crate::Config::proxy`] is set.
+fn system_proxy() -> BTreeMap<String, String> {
+    ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY", "NO_PROXY"]
+        .into_iter()
+        .filter_map(|key| {
+            let value = var(key).or_else(|_| var(key.to_lowercase())).ok()?;
+            Some((key.to_lowercase(), value))
+        })
+        .collect()
+}
+
+impl Execute for Env {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let proxy = if lib.config.proxy.is_empty() {
+            system_proxy()
+        } else {
+            lib.config.proxy.clone()
+        };
+
+        let env = Environment {
+            version: VERSION,
+            config: Creeper::config_path(&lib.args)?,
+            data_dir: creeper_data_dir()?,
+            cache_dir: creeper_cache_dir()?,
+            storage_dir: lib.artifact.storage_dir().to_owned(),
+            mc_dir: creeper_mc_dir()?,
+            instance_dir: lib.game_dir().await.ok().cloned(),
+            log_level: LevelFilter::current().to_string(),
+            offline: lib.args.offline,
+            registry: lib.config.registry.to_string(),
+            use_bmclapi: lib.config.use_bmclapi,
+            proxy,
+        };
+
+        match self.format {
+            Format::Text => {
+                let display = |path: &PathBuf| path.display().to_string();
+                println!("version: {}", env.version);
+                println!("config: {}", display(&env.config));
+                println!("data-dir: {}", display(&env.data_dir));
+                println!("cache-dir: {}", display(&env.cache_dir));
+                println!("storage-dir: {}", display(&env.storage_dir));
+                println!("mc-dir: {}", display(&env.mc_dir));
+                println!(
+                    "instance-dir: {}",
+                    env.instance_dir.as_ref().map_or("none".into(), display)
+                );
+                println!("log-level: {}", env.log_level);
+                println!("offline: {}", env.offline);
+                println!("registry: {}", env.registry);
+                println!("use-bmclapi: {}", env.use_bmclapi);
+                if env.proxy.is_empty() {
+                    println!("proxy: none");
+                }
+                for (host, proxy) in &env.proxy {
+                    println!("proxy.{host}: {proxy}");
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&env)?),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use std::path::Path;
+
+use anyhow::bail;
+use clap::Parser;
+use colored::Colorize;
+use tokio::fs::read_to_string;
+use toml_edit::{Decor, DocumentMut, Item, Key, RawString, Table};
+
+use crate::{Creeper, Package, cmd::Execute, util::write_atomic};
+
+/// Tables of [`crate::Install`] serialized from hash maps, whose keys are sorted alphabetically instead.
+const HASH_MAP: [&str; 5] = [
+    "java-lib-class",
+    "java-lib-mod",
+    "java-lib-file",
+    "native",
+    "mc-asset",
+];
+
+/// Rewrite `creeper.toml` of the current game instance in canonical form.
+///
+/// Keys are ordered as creeper writes them and whitespace is normalized, while comments are kept.
+#[derive(Clone, Debug, Parser)]
+pub struct Fmt {
+    /// To fail if the manifest is not in canonical form, without rewriting it.
+    #[arg(long, default_value_t = false)]
+    pub check: bool,
+}
+
+impl Execute for Fmt {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let path = lib.game.pack_path().await?;
+
+        if path == Path::new("-") {
+            bail!("cannot format a manifest read from standard input");
+        }
+
+        let toml = read_to_string(&path).await?;
+
+        let canonical = canonicalize(&toml)?;
+
+        if toml == canonical {
+            eprintln!("{} {}", "Formatted".bold().green(), path.display());
+            return Ok(());
+        }
+
+        if self.check {
+            bail!("{} is not in canonical form", path.display());
+        }
+
+        write_atomic(&path, canonical).await?;
+
+        eprintln!("{} {}", "Formatted".bold().green(), path.display());
+
+        Ok(())
+    }
+}
+
+/// Rewrite the manifest `toml` in canonical form, keeping its comments.
+///
+/// Keys are ordered as [`Package`] is serialized, tables following the values they belong to.
+fn canonicalize(toml: &str) -> anyhow::Result<String> {
+    let pack = toml::from_str::<Package>(toml)?;
+    let order = toml::to_string(&pack)?.parse::<DocumentMut>()?;
+
+    let mut doc = toml.parse::<DocumentMut>()?;
+
+    sort_table(doc.as_table_mut(), Some(order.as_table()), false);
+
+    let root = doc.as_table_mut();
+    root.decor_mut().clear();
+    normalize_table(root);
+
+    let trailing = comment(doc.trailing().as_str().unwrap_or_default());
+    doc.set_trailing(trailing);
+
+    Ok(doc.to_string())
+}
+
+/// Sort `table` recursively by the key order of `order`, or alphabetically if `by_name`.
+///
+/// Keys missing from `order` go last in their original order.
+fn sort_table(table: &mut Table, order: Option<&Table>, by_name: bool) {
+    let pos = |key: &Key| {
+        order
+            .and_then(|order| order.iter().position(|(k, _)| k == key.get()))
+            .unwrap_or(usize::MAX)
+    };
+
+    if by_name {
+        table.sort_values_by(|k1, _, k2, _| k1.get().cmp(k2.get()));
+    } else {
+        table.sort_values_by(|k1, _, k2, _| pos(k1).cmp(&pos(k2)));
+    }
+
+    // tables are written in the order they are visited
+    table.set_position(None);
+
+    for (key, item) in table.iter_mut() {
+        let by_name = HASH_MAP.contains(&key.get());
+        let order = order.and_then(|order| order.get(key.get()));
+
+        match item {
+            Item::Table(sub) => sort_table(sub, order.and_then(Item::as_table), by_name),
+            Item::ArrayOfTables(array) => {
+                let order = order.and_then(Item::as_array_of_tables);
+                for (i, sub) in array.iter_mut().enumerate() {
+                    sort_table(sub, order.and_then(|order| order.get(i)), by_name);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Normalize whitespace around the keys, values and headers in `table` recursively.
+fn normalize_table(table: &mut Table) {
+    for (mut key, item) in table.iter_mut() {
+        match item {
+            Item::Value(value) => {
+                let decor = key.leaf_decor_mut();
+                let prefix = comment(raw(decor.prefix()));
+                *decor = Decor::new(prefix, " ");
+
+                let decor = value.decor_mut();
+                let suffix = match raw(decor.suffix()).trim() {
+                    "" => String::new(),
+                    suffix => format!(" {suffix}"),
+                };
+                *decor = Decor::new(" ", suffix);
+            }
+            Item::Table(sub) => normalize_header(sub),
+            Item::ArrayOfTables(array) => array.iter_mut().for_each(normalize_header),
+            Item::None => {}
+        }
+    }
+}
+
+/// Like [`normalize_table`], and separate the header of `table` from what precedes it by a blank line.
+fn normalize_header(table: &mut Table) {
+    let decor = table.decor_mut();
+    let prefix = format!("\n{}", comment(raw(decor.prefix())));
+    let suffix = match raw(decor.suffix()).trim() {
+        "" => String::new(),
+        suffix => format!(" {suffix}"),
+    };
+    *decor = Decor::new(prefix, suffix);
+
+    normalize_table(table);
+}
+
+fn raw(s: Option<&RawString>) -> &str {
+    s.and_then(RawString::as_str).unwrap_or_default()
+}
+
+/// The comment lines of `decor`, each ending with a line break, dropping blank lines and indentation.
+fn comment(decor: &str) -> String {
+    decor
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('#'))
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNFORMATTED: &str = r#"
+# my modpack
+version   =   "1.0.0"
+id="pack"
+
+[dependencies]
+sodium = "^0.5"    # faster rendering
+  # pinned for a reason
+vanilla = "=1.20.1"
+
+
+[package]
+    description = "A pack."
+name = "Pack"
+"#;
+
+    const CANONICAL: &str = r#"id = "pack"
+# my modpack
+version = "1.0.0"
+
+[package]
+name = "Pack"
+description = "A pack."
+
+[dependencies]
+sodium = "^0.5" # faster rendering
+# pinned for a reason
+vanilla = "=1.20.1"
+"#;
+
+    #[test]
+    fn fmt_keeps_comments() {
+        assert_eq!(canonicalize(UNFORMATTED).unwrap(), CANONICAL);
+    }
+
+    #[test]
+    fn fmt_check() {
+        // `--check` fails exactly if formatting changes the manifest
+        assert_ne!(canonicalize(UNFORMATTED).unwrap(), UNFORMATTED);
+        assert_eq!(canonicalize(CANONICAL).unwrap(), CANONICAL);
+    }
+
+    #[test]
+    fn fmt_hash_map_sorted() {
+        let artifact = |name: &str| {
+            format!(
+                "[install.native.\"{name}\"]\nname = \"{name}\"\nblake3 = \"{}\"\nlen = 0\n",
+                "0".repeat(64)
+            )
+        };
+        let toml = format!(
+            "id = \"pack\"\nversion = \"1.0.0\"\n[package]\nname = \"Pack\"\n{}{}{}",
+            artifact("c.so"),
+            artifact("a.so"),
+            artifact("b.so")
+        );
+
+        let canonical = canonicalize(&toml).unwrap();
+        let a = canonical.find("a.so").unwrap();
+        let b = canonical.find("b.so").unwrap();
+        let c = canonical.find("c.so").unwrap();
+        assert!(a < b && b < c);
+
+        // fields of each artifact follow the struct
+        assert!(canonical.contains(&format!("blake3 = \"{}\"\nname = \"a.so\"", "0".repeat(64))));
+        assert_eq!(canonicalize(&canonical).unwrap(), canonical);
+    }
+}
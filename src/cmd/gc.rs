@@ -0,0 +1,352 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use colored::Colorize;
+use tokio::fs::{canonicalize, read_dir, read_to_string, remove_file, try_exists};
+use tracing::{debug, warn};
+use walkdir::WalkDir;
+
+use crate::{
+    Creeper, Install,
+    cmd::Execute,
+    display_package,
+    lock::lock_file,
+    path::{creeper_cache_dir, creeper_mc_dir},
+    usage::{locked_mc_version, stale_versions, version_usage},
+};
+
+/// Delete stored artifacts not referenced by any game instance to reclaim disk space.
+///
+/// Instances are those under the creeper minecraft directory, the current one, and any given.
+/// With `--keep-versions`, instances of minecraft versions not launched recently are not kept either,
+/// so that artifacts exclusive to them are removed, while those shared with other instances remain.
+/// Artifacts still deployed anywhere are kept, unless in a pruned instance.
+/// Removed artifacts are downloaded again when next needed, invalidating installations cached with them,
+/// so those without a download source are only removed with `--force`.
+#[derive(Clone, Debug, Parser)]
+pub struct Gc {
+    /// Additional game instances whose artifacts are kept.
+    #[arg(value_name = "DIR")]
+    pub root: Vec<PathBuf>,
+
+    /// Only keep artifacts of instances using the N most recently launched minecraft versions.
+    ///
+    /// The current instance, instances without a minecraft dependency,
+    /// and those of versions never recorded as launched are always kept.
+    #[arg(long, value_name = "N")]
+    pub keep_versions: Option<usize>,
+
+    /// Only print what would be removed.
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Also remove unused artifacts without a download source, which cannot be downloaded again.
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+/// Game instances directly under `dir`, i.e. subdirectories containing a `creeper.toml`.
+async fn find_instance(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut found = vec![];
+
+    if !try_exists(dir).await? {
+        return Ok(found);
+    }
+
+    let mut entries = read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if try_exists(entry.path().join("creeper.toml")).await? {
+            found.push(entry.path());
+        }
+    }
+
+    Ok(found)
+}
+
+/// Instances in `instance` locked to one of the `stale` minecraft versions, except `current`,
+/// with the version of each.
+async fn stale_instance(
+    instance: &BTreeSet<PathBuf>,
+    stale: &BTreeSet<String>,
+    current: Option<&Path>,
+) -> anyhow::Result<BTreeMap<PathBuf, String>> {
+    let mut found = BTreeMap::new();
+
+    if stale.is_empty() {
+        return Ok(found);
+    }
+
+    for dir in instance {
+        if Some(dir.as_path()) == current {
+            continue;
+        }
+
+        if let Some(lock) = lock_file().read(dir.join("creeper.lock")).await?
+            && let Some(version) = locked_mc_version(&lock)
+            && stale.contains(&version)
+        {
+            found.insert(dir.clone(), version);
+        }
+    }
+
+    Ok(found)
+}
+
+impl Creeper {
+    /// Remove cached installations referencing any of the `removed` artifacts,
+    /// which are otherwise reused by later installations without checking that the artifacts still exist.
+    async fn invalidate_install_cache(&self, removed: &HashSet<String>) -> anyhow::Result<usize> {
+        let dir = creeper_cache_dir()?.join("install");
+
+        if !try_exists(&dir).await? {
+            return Ok(0);
+        }
+
+        let mut count = 0;
+
+        for entry in WalkDir::new(&dir) {
+            let entry = entry?;
+            let path = entry.path();
+
+            if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let stale = match serde_json::from_str::<Install>(&read_to_string(path).await?) {
+                Ok(install) => install.artifacts().any(|art| removed.contains(&art.blake3)),
+                Err(e) => {
+                    warn!("removing invalid install cache {}: {e}", path.display());
+                    true
+                }
+            };
+
+            if stale {
+                debug!("invalidating install cache {}", path.display());
+                remove_file(path).await?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Mark the artifacts used by the game instance at `dir` in `keep`,
+    /// i.e. those of its deployed installation and of the packages in its lock file.
+    async fn mark_instance(&self, dir: &Path, keep: &mut HashSet<String>) -> anyhow::Result<()> {
+        let deployed = dir.join(".creeper").join("install.json");
+
+        if try_exists(&deployed).await? {
+            let install = serde_json::from_str::<Install>(&read_to_string(&deployed).await?)?;
+            keep.extend(install.artifacts().map(|art| art.blake3.clone()));
+        }
+
+        let Some(lock) = lock_file().read(dir.join("creeper.lock")).await? else {
+            debug!("no lock file in {}", dir.display());
+            return Ok(());
+        };
+
+        for (id, version) in lock.package {
+            match self.get_install_cache(&id, &version).await? {
+                Some(install) => keep.extend(install.artifacts().map(|art| art.blake3.clone())),
+                None => warn!(
+                    "{} is not installed, its artifacts are not kept",
+                    display_package(&id, &version.version, version.rev)
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Execute for Gc {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let mut instance = BTreeSet::new();
+
+        for dir in find_instance(&creeper_mc_dir()?)
+            .await?
+            .into_iter()
+            .chain(lib.game_dir().await.ok().cloned())
+            .chain(self.root)
+        {
+            instance.insert(canonicalize(&dir).await?);
+        }
+
+        let stale = match self.keep_versions {
+            Some(n) => stale_versions(&version_usage().await?, n),
+            None => BTreeSet::new(),
+        };
+
+        let current = match lib.game_dir().await {
+            Ok(dir) => Some(canonicalize(dir).await?),
+            Err(_) => None,
+        };
+
+        let stale = stale_instance(&instance, &stale, current.as_deref()).await?;
+
+        let mut keep = HashSet::new();
+        let mut kept = 0;
+        let mut pruned = vec![];
+
+        for dir in &instance {
+            if let Some(version) = stale.get(dir) {
+                eprintln!(
+                    "{} {} of minecraft {version}, not launched recently",
+                    "Pruning".bold().yellow(),
+                    dir.display()
+                );
+                pruned.push(dir.clone());
+                continue;
+            }
+
+            debug!("marking artifacts of {}", dir.display());
+            lib.mark_instance(dir, &mut keep).await?;
+            kept += 1;
+        }
+
+        keep.extend(lib.artifact.deployed_roots(&pruned).await?);
+
+        let mut garbage = lib.artifact.find_garbage(&keep).await?;
+
+        if !self.force {
+            garbage.artifact.retain(|(art, _)| {
+                if art.src.is_some() {
+                    return true;
+                }
+                eprintln!(
+                    "{} {art} without download source, use --force to remove",
+                    "Keeping".bold().yellow()
+                );
+                false
+            });
+        }
+
+        if garbage.is_empty() {
+            eprintln!(
+                "{} artifacts of {} instances, nothing to remove",
+                "Kept".bold().green(),
+                kept
+            );
+            return Ok(());
+        }
+
+        for (art, _) in &garbage.artifact {
+            eprintln!("{} {art}", "Unused".bold().yellow());
+        }
+
+        for (path, _) in &garbage.orphan {
+            eprintln!("{} {}", "Unindexed".bold().yellow(), path.display());
+        }
+
+        let size = garbage.size() as f64 / 1e6;
+        let count = garbage.artifact.len() + garbage.orphan.len();
+
+        if self.dry_run {
+            eprintln!(
+                "{} {count} files of {size:.1} MB, kept artifacts of {} instances",
+                "Would remove".bold().green(),
+                kept
+            );
+            return Ok(());
+        }
+
+        for (art, _) in &garbage.artifact {
+            lib.artifact.remove(art).await?;
+        }
+
+        let removed = garbage
+            .artifact
+            .iter()
+            .map(|(art, _)| art.blake3.clone())
+            .collect();
+        let invalid = lib.invalidate_install_cache(&removed).await?;
+        debug!("invalidated {invalid} cached installations");
+
+        for (path, _) in &garbage.orphan {
+            remove_file(path).await?;
+        }
+
+        eprintln!(
+            "{} {count} files of {size:.1} MB, kept artifacts of {} instances",
+            "Removed".bold().green(),
+            kept
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs::{create_dir_all, write};
+
+    use super::*;
+    use crate::{Artifact, artifact::ArtifactManager};
+
+    #[tokio::test]
+    async fn keep_two_versions() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // launched in this order, the oldest first
+        let version = ["1.19.4", "1.20.1", "1.21.1"];
+        let usage = version
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v.to_string(), i as u64))
+            .collect();
+
+        let mut instance = BTreeSet::new();
+        for v in version {
+            let game = dir.path().join(v);
+            create_dir_all(&game).await.unwrap();
+            let lock = format!(
+                "registry = \"https://creeper-registry.pages.dev/\"\n[package]\nvanilla = \"{v}\"\n"
+            );
+            write(game.join("creeper.lock"), lock).await.unwrap();
+            instance.insert(game);
+        }
+
+        let stale = stale_instance(&instance, &stale_versions(&usage, 2), None)
+            .await
+            .unwrap();
+        let old = dir.path().join("1.19.4");
+        assert_eq!(stale, [(old.clone(), "1.19.4".into())].into());
+
+        // the current instance is never pruned
+        let current = stale_instance(&instance, &stale_versions(&usage, 2), Some(&old))
+            .await
+            .unwrap();
+        assert!(current.is_empty());
+
+        // artifacts exclusive to the pruned instance are garbage, shared ones are not
+        let artifact = ArtifactManager::in_dir(&dir.path().join("storage")).await;
+
+        let used = [
+            ("1.19.4", vec!["old", "shared"]),
+            ("1.20.1", vec!["shared"]),
+            ("1.21.1", vec!["shared", "new"]),
+        ];
+        let b3 = |name: &str| blake3::hash(name.as_bytes()).to_hex().to_string();
+        for name in ["old", "shared", "new"] {
+            let art = Artifact::new(b3(name), name.into(), None, 0);
+            artifact.insert(&art).await.unwrap();
+        }
+
+        let keep = used
+            .iter()
+            .filter(|(v, _)| !stale.contains_key(&dir.path().join(v)))
+            .flat_map(|(_, names)| names.iter().map(|name| b3(name)))
+            .collect();
+
+        let garbage = artifact.find_garbage(&keep).await.unwrap();
+        let garbage = garbage
+            .artifact
+            .iter()
+            .map(|(art, _)| art.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(garbage, ["old"]);
+    }
+}
@@ -28,18 +28,7 @@ impl Execute for Init {
                 .to_string(),
         );
 
-        let id = name
-            .to_ascii_lowercase()
-            .chars()
-            .map(|c| {
-                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
-                    c
-                } else {
-                    '_'
-                }
-            })
-            .collect::<String>()
-            .parse::<Id>()?;
+        let id = Id::from_name(&name)?;
 
         let package = Package {
             id: id.clone(),
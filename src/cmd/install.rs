@@ -1,11 +1,14 @@
-use std::iter::once;
+use std::time::Duration;
 
 use anyhow::bail;
 use clap::Parser;
-use tokio::fs::{create_dir_all, write};
+use tokio::{
+    fs::{create_dir_all, write},
+    time::timeout,
+};
 use tracing::info;
 
-use crate::{cmd::Execute, lock::Lock};
+use crate::{cmd::Execute, telemetry::Report};
 
 /// Install the current game instance as described in `creeper.toml`.
 #[derive(Clone, Debug, Parser)]
@@ -13,10 +16,31 @@ pub struct Install {
     /// To update dependencies, even if the current lock file satisfies all requirements.
     #[arg(long, default_value_t = false)]
     pub update: bool,
+
+    /// Abort the installation if not finished within this many seconds, including all downloads.
+    ///
+    /// Overrides `timeout-budget` in config.
+    #[arg(long, value_name = "SECONDS")]
+    pub timeout_retry_budget: Option<u64>,
 }
 
 impl Execute for Install {
     async fn execute(self, lib: &crate::Creeper) -> anyhow::Result<()> {
+        let budget = self.timeout_retry_budget.or(lib.config.timeout_budget);
+
+        let Some(budget) = budget else {
+            return self.install(lib).await;
+        };
+
+        match timeout(Duration::from_secs(budget), self.install(lib)).await {
+            Ok(result) => result,
+            Err(_) => bail!("installation aborted after exceeding time budget of {budget}s"),
+        }
+    }
+}
+
+impl Install {
+    async fn install(self, lib: &crate::Creeper) -> anyhow::Result<()> {
         if self.update && lib.args.offline {
             bail!("updating dependencies is blocked by offline mode");
         }
@@ -34,22 +58,17 @@ impl Execute for Install {
                 info!("ignoring package lock file");
 
                 lib.update().await?;
-                let sol = lib.resolve(package.node.dep.clone())?;
-
-                let lock = Lock {
-                    registry: lib.config.registry.clone(),
-                    package: sol.clone(),
-                };
+                let lock = lib.resolve_lock(&package)?;
+                let sol = lock.package.clone();
                 lib.game.set_lock(Some(lock)).await?;
 
                 sol
             }
         };
 
-        let sorted = lib.sort_dependency(dep)?;
+        let report = Report::new(&dep);
 
-        let mut install = lib.install_all(sorted).await?;
-        install.extend(once(package.install.clone()));
+        let install = lib.game_install(&package, dep).await?;
 
         let json = serde_json::to_string(&install)?;
 
@@ -57,6 +76,8 @@ impl Execute for Install {
         create_dir_all(path.parent().unwrap()).await?;
         write(path, json).await?;
 
+        lib.send_telemetry(&report).await;
+
         Ok(())
     }
 }
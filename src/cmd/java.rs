@@ -0,0 +1,43 @@
+use clap::Parser;
+use colored::Colorize;
+
+use crate::{Creeper, cmd::Execute};
+
+/// Manage Java runtimes.
+#[derive(Clone, Debug, Parser)]
+pub enum Java {
+    Install(InstallJava),
+}
+
+impl Execute for Java {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        match self {
+            Java::Install(install) => lib.execute(install).await,
+        }
+    }
+}
+
+/// Download a Java runtime published by Mojang for the current platform.
+///
+/// Set `auto = true` in `java.toml` to install and use the runtime of the game automatically when launching.
+#[derive(Clone, Debug, Parser)]
+pub struct InstallJava {
+    /// Name of the runtime, e.g. `java-runtime-delta` for Java 21.
+    #[arg(value_name = "COMPONENT")]
+    pub component: String,
+}
+
+impl Execute for InstallJava {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let java = lib.install_java_runtime(&self.component).await?;
+
+        eprintln!(
+            "{} {} at {}",
+            "Installed".bold().green(),
+            self.component,
+            java.display()
+        );
+
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
+use std::{collections::HashMap, path::PathBuf, process::ExitStatus};
+
+use anyhow::{bail, ensure};
 use clap::Parser;
+use colored::Colorize;
 
-use crate::{Creeper, cmd::Execute};
+use crate::{Config, Creeper, Install, cmd::Execute, launch::LaunchOption, log4j::McLogLevel};
 
 /// Launch the current game instance.
 #[derive(Clone, Debug, Parser)]
@@ -8,21 +12,260 @@ pub struct Launch {
     /// To preview the launch command without executing it.
     #[arg(long, default_value_t = false)]
     pub preview: bool,
+
+    /// Start the JVM with a JDWP debug agent listening on the given port.
+    #[arg(long, value_name = "PORT", num_args = 0..=1, default_missing_value = "5005")]
+    pub debug: Option<u16>,
+
+    /// To suspend the JVM until a debugger attaches, requires `--debug`.
+    #[arg(long, requires = "debug", default_value_t = false)]
+    pub suspend: bool,
+
+    /// Copy all worlds, or only the given one, into a snapshot under `.creeper/snapshot` before launching.
+    #[arg(long, value_name = "WORLD")]
+    pub capture_state: Option<Option<String>>,
+
+    /// Restore worlds from the latest snapshot, or the given one, before launching.
+    #[arg(long, value_name = "SNAPSHOT", conflicts_with = "capture_state")]
+    pub restore: Option<Option<String>>,
+
+    /// Deploy native libraries into a subdirectory of this directory, e.g. on a tmpfs,
+    /// overriding `natives-dir` in config.
+    #[arg(long, value_name = "PATH")]
+    pub natives_dir: Option<PathBuf>,
+
+    /// Log in as this configured user instead of the default one,
+    /// given by player name, authlib-injector account or UUID.
+    #[arg(long, value_name = "NAME")]
+    pub user: Option<String>,
+
+    /// Enable a launcher feature gating game arguments in the version metadata, e.g. `has_custom_resolution`.
+    ///
+    /// Features are usually enabled by the dedicated options instead.
+    #[arg(long, value_name = "NAME")]
+    pub feature: Vec<String>,
+
+    /// Launch the demo mode of the game, also enabled by `demo` in config.
+    #[arg(long, default_value_t = false)]
+    pub demo: bool,
+
+    /// Width of the game window, requires `--height`, overriding `width` and `height` in config.
+    #[arg(long, value_name = "PIXELS", requires = "height")]
+    pub width: Option<u32>,
+
+    /// Height of the game window, requires `--width`.
+    #[arg(long, value_name = "PIXELS", requires = "width")]
+    pub height: Option<u32>,
+
+    /// Join a singleplayer world right after startup, supported since 1.20.
+    #[arg(long, value_name = "WORLD", conflicts_with = "quick_play_multiplayer")]
+    pub quick_play_singleplayer: Option<String>,
+
+    /// Join a server right after startup, supported since 1.20.
+    #[arg(long, value_name = "ADDRESS")]
+    pub quick_play_multiplayer: Option<String>,
+
+    /// Level of the game's own logging, e.g. `debug` to troubleshoot mods.
+    ///
+    /// This replaces the log4j configuration of the game and its loader with one logging at this level.
+    #[arg(long, value_name = "LEVEL")]
+    pub mc_log_level: Option<McLogLevel>,
+}
+
+impl Launch {
+    /// Launcher features and placeholder values derived from the options,
+    /// falling back to the defaults in `config`.
+    fn feature(&self, config: &Config) -> (HashMap<String, bool>, HashMap<String, String>) {
+        let mut feature = self
+            .feature
+            .iter()
+            .map(|name| (name.clone(), true))
+            .collect::<HashMap<_, _>>();
+        let mut var = HashMap::new();
+
+        if self.demo || config.demo {
+            feature.insert("is_demo_user".into(), true);
+        }
+
+        let resolution = self
+            .width
+            .zip(self.height)
+            .or(config.width.zip(config.height));
+
+        if let Some((width, height)) = resolution {
+            feature.insert("has_custom_resolution".into(), true);
+            var.insert("resolution_width".into(), width.to_string());
+            var.insert("resolution_height".into(), height.to_string());
+        }
+
+        if let Some(world) = &self.quick_play_singleplayer {
+            feature.insert("is_quick_play_singleplayer".into(), true);
+            var.insert("quickPlaySingleplayer".into(), world.clone());
+        }
+
+        if let Some(address) = &self.quick_play_multiplayer {
+            feature.insert("is_quick_play_multiplayer".into(), true);
+            var.insert("quickPlayMultiplayer".into(), address.clone());
+        }
+
+        var.insert("quickPlayPath".into(), "./.creeper/quick_play.json".into());
+
+        (feature, var)
+    }
+
+    /// Installation merged on top of the instance's own, i.e. the JDWP agent if `--debug`.
+    fn extra(&self) -> Install {
+        let mut extra = Install::default();
+
+        if let Some(port) = self.debug {
+            let suspend = if self.suspend { "y" } else { "n" };
+            extra.java_flag.push(format!(
+                "-agentlib:jdwp=transport=dt_socket,server=y,suspend={suspend},address=*:{port}"
+            ));
+        }
+
+        extra
+    }
 }
 
 impl Execute for Launch {
     async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
-        let mut cmd = lib.launch().await?;
+        // before touching any world, which the running game may be writing to
+        if !self.preview
+            && let Some(pid) = lib.running_game().await?
+        {
+            bail!("the game instance is already running with PID {pid}");
+        }
+
+        let (feature, var) = self.feature(&lib.config);
+
+        let option = LaunchOption {
+            extra: self.extra(),
+            natives_dir: self.natives_dir,
+            user: self.user,
+            feature,
+            var,
+            mc_log_level: self.mc_log_level,
+        };
 
         if self.preview {
+            let cmd = lib.launch_command(option.clone()).await?;
             println!("{:?}", cmd.as_std());
+            // nothing is launched to use the deployed natives
+            lib.clean_native(&option).await?;
             return Ok(());
         }
 
-        let mut proc = cmd.spawn()?;
+        if let Some(world) = &self.capture_state {
+            let snapshot = lib.capture_state(world.as_deref()).await?;
+            eprintln!(
+                "{} state to {}",
+                "Captured".bold().green(),
+                snapshot.display()
+            );
+        }
+
+        if let Some(snapshot) = &self.restore {
+            let snapshot = lib.restore_state(snapshot.as_deref()).await?;
+            eprintln!(
+                "{} state from {}",
+                "Restored".bold().green(),
+                snapshot.display()
+            );
+        }
+
+        if let Some(port) = self.debug {
+            eprintln!("{} for debugger on port {port}", "Listening".bold().green());
+        }
+
+        let status = lib.launch_wait(option).await?;
+
+        check_status(status)
+    }
+}
+
+/// Fail unless the game process exited successfully.
+fn check_status(status: ExitStatus) -> anyhow::Result<()> {
+    ensure!(status.success(), "game process exited with {status}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_from_config() {
+        let config = Config {
+            demo: true,
+            width: Some(1280),
+            height: Some(720),
+            ..Default::default()
+        };
+
+        let (feature, var) = Launch::parse_from(["launch"]).feature(&config);
+        assert_eq!(feature.get("is_demo_user"), Some(&true));
+        assert_eq!(feature.get("has_custom_resolution"), Some(&true));
+        assert_eq!(var["resolution_width"], "1280");
+        assert_eq!(var["resolution_height"], "720");
+
+        // options override the window size in config
+        let launch = Launch::parse_from(["launch", "--width", "800", "--height", "600"]);
+        let (_, var) = launch.feature(&config);
+        assert_eq!(var["resolution_width"], "800");
+        assert_eq!(var["resolution_height"], "600");
+    }
+
+    #[test]
+    fn feature_default_off() {
+        let (feature, var) = Launch::parse_from(["launch"]).feature(&Config::default());
+        assert!(!feature.contains_key("is_demo_user"));
+        assert!(!feature.contains_key("has_custom_resolution"));
+        assert!(!var.contains_key("resolution_width"));
+    }
+
+    #[test]
+    fn debug_agent() {
+        let mut install = Install {
+            java_flag: vec!["-Xmx4G".into()],
+            ..Default::default()
+        };
+        install.extend([Launch::parse_from(["launch", "--debug", "8000", "--suspend"]).extra()]);
+
+        let agent = install
+            .java_flag
+            .iter()
+            .filter(|flag| flag.starts_with("-agentlib:jdwp"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            agent,
+            ["-agentlib:jdwp=transport=dt_socket,server=y,suspend=y,address=*:8000"]
+        );
+
+        let extra = Launch::parse_from(["launch", "--debug"]).extra();
+        assert_eq!(
+            extra.java_flag,
+            ["-agentlib:jdwp=transport=dt_socket,server=y,suspend=n,address=*:5005"]
+        );
+
+        assert!(Launch::parse_from(["launch"]).extra().java_flag.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exit_status() {
+        async fn status(code: i32) -> ExitStatus {
+            tokio::process::Command::new("sh")
+                .args(["-c", &format!("exit {code}")])
+                .status()
+                .await
+                .unwrap()
+        }
 
-        proc.wait().await?;
+        assert!(check_status(status(0).await).is_ok());
 
-        Ok(())
+        let err = check_status(status(1).await).unwrap_err();
+        assert!(err.to_string().contains("exit status: 1"));
     }
 }
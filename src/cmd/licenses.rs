@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail};
+use chrono::{SecondsFormat, Utc};
+use clap::{Parser, ValueEnum};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::{Value, json};
+use spdx::{Expression, Licensee};
+
+use crate::{
+    Artifact, Creeper, Id, Package, VERSION, VersionRev, cmd::Execute, display_package, lock::Lock,
+};
+
+/// List the licenses of all locked packages, optionally checking them against a policy.
+///
+/// This can also export a software bill of materials of the instance.
+#[derive(Clone, Debug, Parser)]
+pub struct Licenses {
+    /// Fail if any package can only be used under one of these licenses.
+    #[arg(long, value_name = "LICENSE")]
+    pub deny: Vec<Licensee>,
+
+    /// Fail if any package cannot be used under these licenses alone.
+    ///
+    /// Packages without a license are regarded as violating this policy.
+    #[arg(long, value_name = "LICENSE")]
+    pub allow: Vec<Licensee>,
+
+    /// Output format, where `json` and `spdx` additionally list the artifacts of each package.
+    #[arg(long, default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    /// One line per package.
+    Text,
+    /// A JSON array of packages.
+    Json,
+    /// An SPDX 2.3 software bill of materials in JSON.
+    Spdx,
+}
+
+impl Licenses {
+    /// Check a license against the policy, `None` meaning the package has no license.
+    ///
+    /// Builtin packages are not subject to the policy.
+    fn permits(&self, license: Option<&Expression>) -> bool {
+        let Some(license) = license else {
+            return self.allow.is_empty();
+        };
+
+        license.evaluate(|req| {
+            let denied = self.deny.iter().any(|l| l.satisfies(req));
+            let allowed = self.allow.is_empty() || self.allow.iter().any(|l| l.satisfies(req));
+            allowed && !denied
+        })
+    }
+}
+
+impl Creeper {
+    /// Query the registry for the manifests of all regular packages in a lock.
+    pub(crate) async fn locked_packages(
+        &self,
+        lock: &Lock,
+    ) -> anyhow::Result<BTreeMap<Id, Package>> {
+        let mut packages = BTreeMap::new();
+
+        for (id, version) in &lock.package {
+            if !id.is_regular() {
+                continue;
+            }
+
+            let package = self
+                .query_registry(id, &version.version, version.rev)
+                .await?;
+
+            packages.insert(id.clone(), package);
+        }
+
+        Ok(packages)
+    }
+}
+
+impl Execute for Licenses {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let lock = lib
+            .game_lock()
+            .await?
+            .ok_or(anyhow!("missing creeper.lock, run `creeper lock` first"))?;
+
+        let packages = lib.locked_packages(&lock).await?;
+
+        let mut locked = lock.package.into_iter().collect::<Vec<_>>();
+        locked.sort();
+
+        let mut violation = 0;
+        let mut sbom = vec![];
+
+        for (id, version) in locked {
+            let name = display_package(&id, &version.version, version.rev);
+
+            let package = packages.get(&id);
+            let license = package.and_then(|p| p.meta.license.as_ref());
+
+            let text = match (package, license) {
+                (None, _) => "builtin".dimmed().to_string(),
+                (Some(_), Some(license)) => license.to_string(),
+                (Some(_), None) => "unknown".into(),
+            };
+
+            let permitted = package.is_none() || self.permits(license);
+
+            if !permitted {
+                violation += 1;
+            }
+
+            match self.format {
+                Format::Text if permitted => println!("{name}: {text}"),
+                Format::Text => println!(
+                    "{name}: {} {}",
+                    text.red(),
+                    "(denied by policy)".bold().red()
+                ),
+                _ if !permitted => {
+                    eprintln!("{} {name} under {text}", "Denied".bold().red())
+                }
+                _ => {}
+            }
+
+            if self.format == Format::Text {
+                continue;
+            }
+
+            let install = lib.install(&id, &version.version, version.rev).await?;
+
+            sbom.push(SbomPackage {
+                id,
+                version,
+                license: license.map(|l| l.to_string()),
+                artifact: install.artifacts().cloned().collect(),
+            });
+        }
+
+        match self.format {
+            Format::Text => {}
+            Format::Json => println!("{}", serde_json::to_string_pretty(&sbom)?),
+            Format::Spdx => {
+                let root = lib.game_pack().await?;
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&spdx_document(&root, &sbom))?
+                );
+            }
+        }
+
+        if violation > 0 {
+            bail!("{violation} packages violate the license policy");
+        }
+
+        Ok(())
+    }
+}
+
+/// A locked package and its artifacts, as exported by [`Format::Json`].
+#[derive(Serialize)]
+struct SbomPackage {
+    id: Id,
+    version: VersionRev,
+    license: Option<String>,
+    artifact: Vec<Artifact>,
+}
+
+/// Create an [SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/) JSON document,
+/// with each locked package containing its artifacts.
+fn spdx_document(root: &Package, sbom: &[SbomPackage]) -> Value {
+    fn spdx_id(s: &str) -> String {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect()
+    }
+
+    let mut packages = vec![];
+    let mut relationships = vec![];
+
+    for SbomPackage {
+        id,
+        version,
+        license,
+        artifact,
+    } in sbom
+    {
+        let package_ref = format!("SPDXRef-Package-{}", spdx_id(&id.to_string()));
+        let license = license.as_deref().unwrap_or("NOASSERTION");
+
+        packages.push(json!({
+            "SPDXID": package_ref,
+            "name": id.to_string(),
+            "versionInfo": version.to_string(),
+            "downloadLocation": "NOASSERTION",
+            "licenseDeclared": license,
+            "licenseConcluded": license,
+            "filesAnalyzed": false,
+        }));
+
+        relationships.push(json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": package_ref,
+        }));
+
+        for art in artifact {
+            let artifact_ref = format!(
+                "SPDXRef-Artifact-{}-{}",
+                spdx_id(&id.to_string()),
+                art.blake3
+            );
+
+            let checksums = art
+                .clone()
+                .checksum()
+                .map(|c| {
+                    json!({
+                        "algorithm": c.function.to_string().to_uppercase(),
+                        "checksumValue": c.hex_hash,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            packages.push(json!({
+                "SPDXID": artifact_ref,
+                "name": art.name,
+                "downloadLocation": art.src.as_deref().unwrap_or("NOASSERTION"),
+                "licenseDeclared": license,
+                "licenseConcluded": license,
+                "filesAnalyzed": false,
+                "checksums": checksums,
+            }));
+
+            relationships.push(json!({
+                "spdxElementId": package_ref,
+                "relationshipType": "CONTAINS",
+                "relatedSpdxElement": artifact_ref,
+            }));
+        }
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("{}-{}", root.id, root.version),
+        "documentNamespace": format!(
+            "https://spdx.org/spdxdocs/{}-{}-{}",
+            root.id,
+            root.version,
+            Utc::now().timestamp()
+        ),
+        "creationInfo": {
+            "created": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            "creators": [format!("Tool: creeper-{VERSION}")],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn license(expr: &str) -> Expression {
+        Expression::parse(expr).unwrap()
+    }
+
+    #[test]
+    fn deny_gpl() {
+        let licenses = Licenses::parse_from(["licenses", "--deny", "GPL-3.0-only"]);
+
+        assert!(!licenses.permits(Some(&license("GPL-3.0-only"))));
+        assert!(licenses.permits(Some(&license("MIT"))));
+        // another license can be chosen
+        assert!(licenses.permits(Some(&license("MIT OR GPL-3.0-only"))));
+        assert!(!licenses.permits(Some(&license("MIT AND GPL-3.0-only"))));
+        assert!(licenses.permits(None));
+    }
+
+    #[test]
+    fn allow_only() {
+        let licenses =
+            Licenses::parse_from(["licenses", "--allow", "MIT", "--allow", "Apache-2.0"]);
+
+        assert!(licenses.permits(Some(&license("MIT"))));
+        assert!(licenses.permits(Some(&license("Apache-2.0 OR GPL-3.0-only"))));
+        assert!(!licenses.permits(Some(&license("GPL-3.0-only"))));
+        assert!(!licenses.permits(None));
+    }
+
+    #[test]
+    fn sbom_lists_artifacts() {
+        let artifact = |c: &str, name: &str| Artifact::new(c.repeat(64), name.into(), None, 0);
+
+        let sbom = vec![
+            SbomPackage {
+                id: "sodium".parse().unwrap(),
+                version: "0.5.8".parse().unwrap(),
+                license: Some("MIT".into()),
+                artifact: vec![artifact("a", "sodium.jar")],
+            },
+            SbomPackage {
+                id: "shaders".parse().unwrap(),
+                version: "1.0.0".parse().unwrap(),
+                license: None,
+                artifact: vec![artifact("b", "bsl.zip"), artifact("c", "complementary.zip")],
+            },
+        ];
+
+        let root = toml::from_str::<Package>(
+            "id = \"pack\"\nversion = \"1.0.0\"\n[package]\nname = \"Pack\"\n",
+        )
+        .unwrap();
+
+        let doc = spdx_document(&root, &sbom);
+        let packages = doc["packages"].as_array().unwrap();
+
+        for (b3, license) in [("a", "MIT"), ("b", "NOASSERTION"), ("c", "NOASSERTION")] {
+            let b3 = b3.repeat(64);
+            let package = packages
+                .iter()
+                .find(|p| {
+                    p["checksums"].as_array().is_some_and(|sums| {
+                        sums.contains(&json!({ "algorithm": "BLAKE3", "checksumValue": b3 }))
+                    })
+                })
+                .unwrap();
+            assert_eq!(package["licenseDeclared"], license);
+        }
+
+        // one entry per package and per artifact
+        assert_eq!(packages.len(), 5);
+
+        let json = serde_json::to_value(&sbom).unwrap();
+        assert_eq!(json[0]["license"], "MIT");
+        assert_eq!(json[1]["artifact"][1]["blake3"], "c".repeat(64));
+    }
+}
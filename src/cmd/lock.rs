@@ -0,0 +1,48 @@
+use anyhow::bail;
+use clap::Parser;
+use colored::Colorize;
+
+use crate::{Creeper, cmd::Execute};
+
+/// Resolve dependencies of the current game instance and write `creeper.lock`.
+#[derive(Clone, Debug, Parser)]
+pub struct Lock {
+    /// To print the changes to the lock file without writing it.
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+}
+
+impl Execute for Lock {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        if lib.args.offline {
+            bail!("resolving dependencies is blocked by offline mode");
+        }
+
+        let package = lib.game_pack().await?;
+
+        lib.update().await?;
+        let lock = lib.resolve_lock(&package)?;
+
+        let old = lib.game_lock().await?.unwrap_or(crate::Lock {
+            registry: lock.registry.clone(),
+            package: Default::default(),
+        });
+
+        let diff = old.diff(&lock);
+
+        if diff.is_empty() {
+            eprintln!("{} lock file is up to date", "Checked".bold().green());
+            return Ok(());
+        }
+
+        print!("{diff}");
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        lib.set_game_lock(Some(lock)).await?;
+
+        Ok(())
+    }
+}
@@ -7,11 +7,26 @@ use crate::cmd::Execute;
 ///
 /// This will start an interactive CLI.
 #[derive(Clone, Debug, Parser)]
-pub struct Login;
+pub struct Login {
+    /// Log in a Microsoft account with a device code,
+    /// which can be entered on another device when no browser is available.
+    #[arg(long, conflicts_with = "offline")]
+    pub device: bool,
+
+    /// Add an offline player with this name, without any authentication.
+    #[arg(long, value_name = "NAME")]
+    pub offline: Option<String>,
+}
 
 impl Execute for Login {
     async fn execute(self, lib: &crate::Creeper) -> anyhow::Result<()> {
-        let user = lib.prompt_new_user().await?;
+        let user = if let Some(name) = self.offline {
+            lib.new_offline_user(name).await?
+        } else if self.device {
+            lib.prompt_new_microsoft_user_device().await?
+        } else {
+            lib.prompt_new_user().await?
+        };
 
         eprintln!("{} {user}", "Login".bold().green());
 
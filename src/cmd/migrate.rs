@@ -0,0 +1,378 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use clap::{Parser, ValueEnum};
+use colored::Colorize;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use tokio::fs::{read_dir, read_to_string, try_exists};
+use tracing::{info, warn};
+
+use crate::{
+    Creeper, Id, Install, Package,
+    cmd::Execute,
+    lock::lock_file,
+    pack::{PackMeta, PackNode},
+    util::TomlFile,
+};
+
+/// Import an existing game instance of another launcher as a creeper package.
+#[derive(Clone, Debug, Parser)]
+pub struct Migrate {
+    /// The launcher that created the instance.
+    #[arg(long)]
+    pub from: Launcher,
+
+    /// Path to the instance, i.e. the MultiMC instance directory or the `.minecraft` directory.
+    #[arg(value_name = "PATH")]
+    pub path: PathBuf,
+
+    /// Directory to create the package in.
+    #[arg(short, long, default_value = ".")]
+    pub output: PathBuf,
+
+    /// Override the minecraft version detected from the instance.
+    #[arg(long)]
+    pub version: Option<Version>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Launcher {
+    /// MultiMC and its derivatives, e.g. Prism Launcher.
+    Multimc,
+    /// The official minecraft launcher.
+    Vanilla,
+}
+
+/// Settings and version information extracted from a foreign instance.
+struct Source {
+    name: String,
+    dep: BTreeMap<Id, VersionReq>,
+    java_flag: Vec<String>,
+    mod_dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct MmcPack {
+    components: Vec<MmcComponent>,
+}
+
+#[derive(Deserialize)]
+struct MmcComponent {
+    uid: String,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LauncherProfiles {
+    profiles: HashMap<String, LauncherProfile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LauncherProfile {
+    name: Option<String>,
+    last_version_id: String,
+    last_used: Option<String>,
+    java_args: Option<String>,
+}
+
+/// Parse the `key=value` lines of a MultiMC `instance.cfg`.
+fn parse_cfg(cfg: &str) -> HashMap<&str, &str> {
+    cfg.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .collect()
+}
+
+fn exact(version: &str) -> anyhow::Result<VersionReq> {
+    let version = version
+        .parse::<Version>()
+        .map_err(|e| anyhow!("unsupported version {version}: {e}"))?;
+    Ok(VersionReq::parse(&format!("={version}"))?)
+}
+
+impl Migrate {
+    async fn multimc(&self) -> anyhow::Result<Source> {
+        let cfg = read_to_string(self.path.join("instance.cfg")).await?;
+        let cfg = parse_cfg(&cfg);
+
+        let json = read_to_string(self.path.join("mmc-pack.json")).await?;
+        let pack = serde_json::from_str::<MmcPack>(&json)?;
+
+        let mut dep = BTreeMap::new();
+
+        for MmcComponent { uid, version } in pack.components {
+            let id = match uid.as_str() {
+                "net.minecraft" => Id::vanilla(),
+                "net.fabricmc.fabric-loader" => Id::fabric(),
+                "net.neoforged" => Id::neoforge(),
+                "net.minecraftforge" => Id::forge(),
+                // implied by the loaders above
+                "net.fabricmc.intermediary" | "org.lwjgl3" | "org.lwjgl" => continue,
+                _ => {
+                    warn!("ignoring unknown MultiMC component {uid}");
+                    continue;
+                }
+            };
+
+            let version = version.ok_or(anyhow!("missing version of component {uid}"))?;
+
+            dep.insert(id, exact(&version)?);
+        }
+
+        let mut java_flag = vec![];
+
+        if cfg.get("OverrideMemory") == Some(&"true") {
+            if let Some(min) = cfg.get("MinMemAlloc") {
+                java_flag.push(format!("-Xms{min}m"));
+            }
+            if let Some(max) = cfg.get("MaxMemAlloc") {
+                java_flag.push(format!("-Xmx{max}m"));
+            }
+        }
+
+        if cfg.get("OverrideJavaArgs") == Some(&"true")
+            && let Some(args) = cfg.get("JvmArgs")
+        {
+            java_flag.extend(args.split_whitespace().map(str::to_owned));
+        }
+
+        let name = cfg.get("name").unwrap_or(&"instance").to_string();
+
+        // MultiMC uses `.minecraft`, while Prism Launcher uses `minecraft`
+        let mut mod_dir = self.path.join(".minecraft").join("mods");
+        if !try_exists(&mod_dir).await? {
+            mod_dir = self.path.join("minecraft").join("mods");
+        }
+
+        Ok(Source {
+            name,
+            dep,
+            java_flag,
+            mod_dir,
+        })
+    }
+
+    async fn vanilla(&self, lib: &Creeper) -> anyhow::Result<Source> {
+        let json = read_to_string(self.path.join("launcher_profiles.json")).await?;
+        let profiles = serde_json::from_str::<LauncherProfiles>(&json)?;
+
+        let profile = profiles
+            .profiles
+            .into_values()
+            .max_by(|a, b| a.last_used.cmp(&b.last_used))
+            .ok_or(anyhow!("no launcher profile found"))?;
+
+        let version = match profile.last_version_id.as_str() {
+            "latest-release" => lib.vanilla_manifest().await?.latest.release.clone(),
+            "latest-snapshot" => lib.vanilla_manifest().await?.latest.snapshot.clone(),
+            id => id.to_owned(),
+        };
+
+        let mut dep = BTreeMap::new();
+        dep.insert(Id::vanilla(), exact(&version)?);
+
+        let java_flag = profile
+            .java_args
+            .map(|args| args.split_whitespace().map(str::to_owned).collect())
+            .unwrap_or_default();
+
+        Ok(Source {
+            name: profile.name.unwrap_or("minecraft".into()),
+            dep,
+            java_flag,
+            mod_dir: self.path.join("mods"),
+        })
+    }
+}
+
+impl Creeper {
+    /// Store all mod jars in a directory, in filename order.
+    async fn import_mod_dir(&self, dir: impl AsRef<Path>) -> anyhow::Result<Install> {
+        let dir = dir.as_ref();
+
+        let mut install = Install::default();
+
+        if !try_exists(dir).await? {
+            return Ok(install);
+        }
+
+        let mut file = vec![];
+
+        let mut entries = read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_file()
+                && path.extension().is_some_and(|ext| ext == "jar")
+            {
+                file.push(path);
+            }
+        }
+
+        file.sort();
+
+        for path in file {
+            info!("importing {}", path.display());
+            install.mc_mod.push(self.store_artifact(&path).await?);
+        }
+
+        Ok(install)
+    }
+}
+
+impl Source {
+    /// The creeper package equivalent to this instance, with the mods imported as `install`.
+    fn package(self, mut install: Install) -> anyhow::Result<Package> {
+        install.java_flag = self.java_flag;
+
+        Ok(Package {
+            id: Id::from_name(&self.name)?,
+            version: "0.1.0".parse().unwrap(),
+            rev: 0,
+            meta: PackMeta {
+                name: self.name,
+                authors: vec![],
+                desc: "".into(),
+                license: None,
+            },
+            node: PackNode {
+                dep: self.dep,
+                ..Default::default()
+            },
+            install,
+        })
+    }
+}
+
+impl Execute for Migrate {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let mut source = match self.from {
+            Launcher::Multimc => self.multimc().await?,
+            Launcher::Vanilla => self.vanilla(lib).await?,
+        };
+
+        if let Some(version) = &self.version {
+            source
+                .dep
+                .insert(Id::vanilla(), exact(&version.to_string())?);
+        }
+
+        if !source.dep.contains_key(&Id::vanilla()) {
+            bail!("cannot detect minecraft version, please specify with --version");
+        }
+
+        let toml = self.output.join("creeper.toml");
+
+        if try_exists(&toml).await? {
+            bail!(
+                "cannot migrate into existing creeper package {}",
+                self.output.display()
+            );
+        }
+
+        let install = lib.import_mod_dir(&source.mod_dir).await?;
+        let package = source.package(install)?;
+
+        TomlFile::new().write(&toml, Some(package.clone())).await?;
+
+        if lib.args.offline {
+            info!("skipping lock file generation because offline mode enabled");
+        } else {
+            lib.update().await?;
+            let lock = lib.resolve_lock(&package)?;
+            lock_file()
+                .write(self.output.join("creeper.lock"), Some(lock))
+                .await?;
+        }
+
+        eprintln!(
+            "{} {} into {}",
+            "Migrated".bold().green(),
+            self.path.display(),
+            self.output.display()
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSTANCE_CFG: &str = r#"InstanceType=OneSix
+name=My Pack
+OverrideMemory=true
+MinMemAlloc=512
+MaxMemAlloc=4096
+OverrideJavaArgs=true
+JvmArgs=-XX:+UseG1GC -Dfoo=bar
+"#;
+
+    const MMC_PACK: &str = r#"{
+    "components": [
+        { "uid": "org.lwjgl3", "version": "3.3.1" },
+        { "uid": "net.minecraft", "version": "1.20.1" },
+        { "uid": "net.fabricmc.intermediary", "version": "1.20.1" },
+        { "uid": "net.fabricmc.fabric-loader", "version": "0.15.11" }
+    ],
+    "formatVersion": 1
+}"#;
+
+    #[tokio::test]
+    async fn migrate_multimc() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance = dir.path().join("instance");
+
+        std::fs::create_dir_all(instance.join("minecraft").join("mods")).unwrap();
+        std::fs::write(instance.join("instance.cfg"), INSTANCE_CFG).unwrap();
+        std::fs::write(instance.join("mmc-pack.json"), MMC_PACK).unwrap();
+
+        let migrate = Migrate {
+            from: Launcher::Multimc,
+            path: instance.clone(),
+            output: dir.path().join("output"),
+            version: None,
+        };
+
+        let source = migrate.multimc().await.unwrap();
+
+        // Prism Launcher keeps the game in `minecraft`
+        assert_eq!(source.mod_dir, instance.join("minecraft").join("mods"));
+
+        let package = source.package(Install::default()).unwrap();
+
+        assert_eq!(package.meta.name, "My Pack");
+        assert_eq!(
+            package.node.dep,
+            BTreeMap::from([
+                (Id::vanilla(), VersionReq::parse("=1.20.1").unwrap()),
+                (Id::fabric(), VersionReq::parse("=0.15.11").unwrap()),
+            ])
+        );
+        assert_eq!(
+            package.install.java_flag,
+            ["-Xms512m", "-Xmx4096m", "-XX:+UseG1GC", "-Dfoo=bar"]
+        );
+
+        // the manifest reads back as written
+        let toml = migrate.output.join("creeper.toml");
+        TomlFile::new()
+            .write(&toml, Some(package.clone()))
+            .await
+            .unwrap();
+        let read = TomlFile::<Package>::new()
+            .read(&toml)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.id, package.id);
+        assert_eq!(read.node.dep, package.node.dep);
+        assert_eq!(read.install.java_flag, package.install.java_flag);
+    }
+}
@@ -1,13 +1,27 @@
 use crate::Creeper;
 
+mod account;
 mod add;
+mod cache;
+mod check_updates;
 mod complete;
+mod diff_lock;
+mod env;
+mod fmt;
+mod gc;
 mod init;
 mod install;
+mod java;
 mod launch;
+mod licenses;
+mod lock;
 mod login;
+mod migrate;
+mod mods;
 mod nuke;
+mod pack;
 mod prelude;
+mod verify;
 
 pub use prelude::*;
 
@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, bail};
+use clap::Parser;
+use colored::Colorize;
+use inquire::Confirm;
+use tokio::{process::Command, task::spawn_blocking};
+use tracing::{info, warn};
+
+use crate::{
+    Creeper, Id,
+    cmd::{self, Execute},
+    launch::LaunchOption,
+};
+
+/// Manage the mods of the current game instance.
+#[derive(Clone, Debug, Parser)]
+pub enum Mod {
+    Disable(Disable),
+
+    Enable(Enable),
+
+    Bisect(Bisect),
+}
+
+impl Execute for Mod {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        match self {
+            Mod::Disable(disable) => lib.execute(disable).await,
+            Mod::Enable(enable) => lib.execute(enable).await,
+            Mod::Bisect(bisect) => lib.execute(bisect).await,
+        }
+    }
+}
+
+/// Stop deploying the mods of dependencies, while keeping them in the manifest.
+#[derive(Clone, Debug, Parser)]
+pub struct Disable {
+    /// The dependencies to disable.
+    #[arg(value_name = "PACKAGE", required = true)]
+    pub id: Vec<Id>,
+}
+
+impl Execute for Disable {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let pack = lib.game_pack().await?;
+        let mut state = lib.game.state().await?;
+
+        for id in &self.id {
+            if !pack.node.dep.contains_key(id)
+                && !lib
+                    .game_lock()
+                    .await?
+                    .is_some_and(|lock| lock.package.contains_key(id))
+            {
+                bail!("cannot disable {id}: not a dependency of the current instance");
+            }
+
+            if !state.disabled.insert(id.clone()) {
+                warn!("{id} is already disabled");
+            }
+        }
+
+        lib.game.set_state(state).await?;
+
+        lib.execute(cmd::Install {
+            update: false,
+            timeout_retry_budget: None,
+        })
+        .await?;
+
+        for id in self.id {
+            eprintln!("{} {id}", "Disabled".bold().green());
+        }
+
+        Ok(())
+    }
+}
+
+/// Resume deploying the mods of dependencies disabled with `creeper mod disable`.
+#[derive(Clone, Debug, Parser)]
+pub struct Enable {
+    /// The dependencies to enable.
+    #[arg(value_name = "PACKAGE", required = true)]
+    pub id: Vec<Id>,
+}
+
+impl Execute for Enable {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let mut state = lib.game.state().await?;
+
+        for id in &self.id {
+            if !state.disabled.remove(id) {
+                warn!("{id} is not disabled");
+            }
+        }
+
+        lib.game.set_state(state).await?;
+
+        lib.execute(cmd::Install {
+            update: false,
+            timeout_retry_budget: None,
+        })
+        .await?;
+
+        for id in self.id {
+            eprintln!("{} {id}", "Enabled".bold().green());
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the mod causing an issue by repeatedly launching with half of the suspects disabled.
+///
+/// The issue is assumed to be caused by a single mod, which is reproduced whenever it is enabled.
+/// Note that disabling a library mod may break the mods depending on it.
+/// The disabled set of the instance is restored when finished.
+#[derive(Clone, Debug, Parser)]
+pub struct Bisect {
+    /// Run this shell command instead of launching the game and asking,
+    /// where a non-zero exit status means the issue reproduced.
+    #[arg(long, value_name = "COMMAND")]
+    pub run: Option<String>,
+}
+
+impl Bisect {
+    async fn reproduces(&self, lib: &Creeper) -> anyhow::Result<bool> {
+        if let Some(run) = &self.run {
+            #[cfg(unix)]
+            let status = Command::new("sh").arg("-c").arg(run).status().await?;
+            #[cfg(windows)]
+            let status = Command::new("cmd").arg("/C").arg(run).status().await?;
+
+            return Ok(!status.success());
+        }
+
+        lib.launch_wait(LaunchOption::default()).await?;
+
+        let reproduced =
+            spawn_blocking(|| Confirm::new("Did the issue reproduce?").prompt()).await??;
+
+        Ok(reproduced)
+    }
+
+    /// Deploy with `off` disabled in addition to `base`, and check whether the issue reproduces.
+    async fn try_disabled(
+        &self,
+        lib: &Creeper,
+        base: &BTreeSet<Id>,
+        off: &[Id],
+    ) -> anyhow::Result<bool> {
+        set_disabled(lib, base.iter().chain(off).cloned().collect()).await?;
+        self.reproduces(lib).await
+    }
+}
+
+/// Find the culprit among `suspect` by bisection,
+/// where `reproduces` deploys with the given suspects disabled and checks whether the issue reproduces.
+///
+/// Fails unless the issue reproduces with all suspects enabled, but not with all of them disabled.
+async fn bisect<F, Fut>(mut suspect: Vec<Id>, mut reproduces: F) -> anyhow::Result<Id>
+where
+    F: FnMut(Vec<Id>) -> Fut,
+    Fut: Future<Output = anyhow::Result<bool>>,
+{
+    info!(
+        "checking that the issue reproduces with all {} suspects enabled",
+        suspect.len()
+    );
+    if !reproduces(vec![]).await? {
+        bail!("the issue does not reproduce with all suspects enabled");
+    }
+
+    info!("checking that the issue does not reproduce with all suspects disabled");
+    if reproduces(suspect.clone()).await? {
+        bail!("the issue also reproduces with all suspects disabled, so none of them is the cause");
+    }
+
+    let mut step = 1;
+
+    while suspect.len() > 1 {
+        let (on, off) = suspect.split_at(suspect.len() / 2);
+
+        info!(
+            "step {step}: launching with {} of {} suspects enabled",
+            on.len(),
+            suspect.len()
+        );
+
+        let reproduced = reproduces(off.to_vec()).await?;
+
+        let result = if reproduced {
+            "reproduced".red()
+        } else {
+            "not reproduced".green()
+        };
+        eprintln!("{} step {step}: {result}", "Bisected".bold().green());
+
+        suspect = if reproduced { on } else { off }.to_vec();
+        step += 1;
+    }
+
+    suspect.pop().ok_or(anyhow!("no mod to bisect"))
+}
+
+/// Update the disabled set of the instance and reinstall.
+async fn set_disabled(lib: &Creeper, disabled: BTreeSet<Id>) -> anyhow::Result<()> {
+    let mut state = lib.game.state().await?;
+    state.disabled = disabled;
+    lib.game.set_state(state).await?;
+
+    lib.execute(cmd::Install {
+        update: false,
+        timeout_retry_budget: None,
+    })
+    .await
+}
+
+impl Execute for Bisect {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let base = lib.game.state().await?.disabled;
+
+        let lock = lib
+            .game_lock()
+            .await?
+            .ok_or(anyhow!("missing creeper.lock, run `creeper install` first"))?;
+
+        let mut suspect = vec![];
+
+        for (id, version) in lock.package.into_iter().collect::<BTreeMap<_, _>>() {
+            if base.contains(&id) {
+                continue;
+            }
+
+            let install = lib.install(&id, &version.version, version.rev).await?;
+
+            if install.mods().next().is_some() {
+                suspect.push(id);
+            }
+        }
+
+        if suspect.is_empty() {
+            bail!("no enabled dependency provides mods");
+        }
+
+        let result = {
+            let (this, base) = (&self, &base);
+            bisect(suspect, |off| async move {
+                this.try_disabled(lib, base, &off).await
+            })
+            .await
+        };
+
+        set_disabled(lib, base).await?;
+
+        let culprit = result?;
+
+        eprintln!("{} {culprit} causing the issue", "Found".bold().green());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suspect() -> Vec<Id> {
+        ["a", "b", "c", "d", "e", "f", "g"]
+            .map(|id| id.parse().unwrap())
+            .into()
+    }
+
+    #[tokio::test]
+    async fn bisect_converge() {
+        for bad in suspect() {
+            let mut launch = 0;
+            let culprit = bisect(suspect(), |off| {
+                launch += 1;
+                let reproduced = !off.contains(&bad);
+                async move { Ok(reproduced) }
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(culprit, bad);
+            // both endpoints, then one launch per halving
+            assert!(launch <= 2 + 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn bisect_no_culprit() {
+        // reproduces regardless of the mods
+        assert!(bisect(suspect(), |_| async { Ok(true) }).await.is_err());
+
+        // never reproduces
+        assert!(bisect(suspect(), |_| async { Ok(false) }).await.is_err());
+    }
+}
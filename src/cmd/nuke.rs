@@ -15,8 +15,12 @@ pub struct Nuke {
 }
 
 impl Execute for Nuke {
-    async fn execute(self, _lib: &crate::Creeper) -> anyhow::Result<()> {
+    async fn execute(self, lib: &crate::Creeper) -> anyhow::Result<()> {
         prompt_remove(creeper_cache_dir()?).await?;
+        let storage_dir = lib.artifact.storage_dir();
+        if storage_dir != creeper_data_dir()? {
+            prompt_remove(storage_dir).await?;
+        }
         prompt_remove(creeper_data_dir()?).await?;
         Ok(())
     }
@@ -0,0 +1,216 @@
+use std::{io::IsTerminal, path::PathBuf};
+
+use anyhow::{anyhow, bail};
+use clap::Parser;
+use colored::Colorize;
+use spdx::Expression;
+use tokio::fs::{create_dir_all, try_exists, write};
+use tracing::info;
+
+use crate::{
+    Creeper, Id, Package,
+    checksum::{Checksum, HashFunc, calc_multi},
+    cmd::Execute,
+    pack::PackMeta,
+    util::prompt_valid,
+};
+
+/// Manage the package of the current game instance.
+#[derive(Clone, Debug, Parser)]
+pub enum Pack {
+    New(New),
+    Publish(Publish),
+}
+
+impl Execute for Pack {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        match self {
+            Pack::New(new) => lib.execute(new).await,
+            Pack::Publish(publish) => lib.execute(publish).await,
+        }
+    }
+}
+
+/// Scaffold a new package for publishing in a new directory.
+///
+/// Unlike `creeper init`, the package ID is given rather than derived from the directory name.
+#[derive(Clone, Debug, Parser)]
+pub struct New {
+    /// ID of the package.
+    pub id: Id,
+
+    /// Directory to create the package in, defaults to the package ID.
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+
+    /// Display name of the package, defaults to the package ID.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// License of the package in SPDX expression, prompted for if omitted in a terminal.
+    #[arg(long)]
+    pub license: Option<String>,
+}
+
+impl Execute for New {
+    async fn execute(self, _lib: &Creeper) -> anyhow::Result<()> {
+        if !self.id.is_regular() {
+            bail!("cannot create reserved package {}", self.id);
+        }
+
+        let path = self.path.unwrap_or(PathBuf::from(self.id.to_string()));
+
+        if try_exists(&path).await? {
+            bail!("cannot create package in existing {}", path.display());
+        }
+
+        let license = match self.license {
+            Some(license) => Some(
+                license
+                    .parse::<Expression>()
+                    .map_err(|e| anyhow!("invalid SPDX license expression {license}: {e}"))?,
+            ),
+            None if std::io::stdin().is_terminal() => {
+                Some(prompt_valid("License of the package in SPDX expression, e.g. MIT:").await?)
+            }
+            None => None,
+        };
+
+        let package = Package {
+            id: self.id.clone(),
+            version: "0.1.0".parse().unwrap(),
+            rev: 0,
+            node: Default::default(),
+            meta: PackMeta {
+                name: self.name.unwrap_or(self.id.to_string()),
+                authors: vec![],
+                desc: "".into(),
+                license,
+            },
+            install: Default::default(),
+        };
+
+        create_dir_all(&path).await?;
+
+        write(path.join("creeper.toml"), toml::to_string_pretty(&package)?).await?;
+        // installed files are regenerated from the manifest
+        write(path.join(".gitignore"), "/.creeper/\n").await?;
+
+        eprintln!(
+            "{} package {} in {}",
+            "Created".bold().green(),
+            self.id,
+            path.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// Upload the package to the configured registry.
+///
+/// Artifacts without a source URL are uploaded alongside the manifest.
+#[derive(Clone, Debug, Parser)]
+pub struct Publish {
+    /// Access token for the registry.
+    #[arg(long)]
+    pub token: Option<String>,
+
+    /// Validate the package and print the manifest to upload, without uploading anything.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Check that a package has the metadata required by a registry.
+fn validate(package: &Package) -> anyhow::Result<()> {
+    if !package.id.is_regular() {
+        bail!("cannot publish reserved package {}", package.id);
+    }
+
+    if package.meta.name.trim().is_empty() {
+        bail!("missing package name");
+    }
+
+    // the expression itself has been validated when parsing the manifest
+    if package.meta.license.is_none() {
+        bail!("missing license, which is required for publishing");
+    }
+
+    Ok(())
+}
+
+impl Execute for Publish {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let mut package = lib.game_pack().await?;
+
+        validate(&package)?;
+
+        let mut upload = vec![];
+
+        for art in package.install.artifacts_mut() {
+            let path = lib.retrieve_artifact(art).await?;
+
+            let funcs = [HashFunc::Sha1, HashFunc::Sha256]
+                .into_iter()
+                .filter(|f| !art.has_checksum(*f))
+                .collect::<Vec<_>>();
+            for (function, hex_hash) in calc_multi(&path, &funcs).await? {
+                art.affix_checksum(Checksum { function, hex_hash });
+            }
+
+            if art.src.is_none() {
+                art.src = Some(lib.registry.artifact_url(&art.blake3)?.to_string());
+                upload.push((art.clone(), path));
+            }
+        }
+
+        if self.dry_run {
+            println!("{}", toml::to_string_pretty(&package)?);
+            info!("{} artifacts would be uploaded", upload.len());
+            return Ok(());
+        }
+
+        if lib.args.offline {
+            bail!("offline mode enabled, cannot publish");
+        }
+
+        lib.registry
+            .publish(&package, &upload, self.token.as_deref())
+            .await
+            .map_err(|e| anyhow!("failed to publish {}: {e}", package.id))?;
+
+        eprintln!(
+            "{} {}@{} to {}",
+            "Published".bold().green(),
+            package.id,
+            package.version,
+            lib.registry.url
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(license: Option<&str>) -> String {
+        let license = license
+            .map(|license| format!("license = \"{license}\"\n"))
+            .unwrap_or_default();
+        format!("id = \"pack\"\nversion = \"1.0.0\"\n[package]\nname = \"Pack\"\n{license}")
+    }
+
+    #[test]
+    fn validate_license() {
+        let package = toml::from_str::<Package>(&manifest(Some("MIT OR Apache-2.0"))).unwrap();
+        assert!(validate(&package).is_ok());
+
+        // rejected before anything is uploaded
+        assert!(toml::from_str::<Package>(&manifest(Some("MIT OR"))).is_err());
+
+        let package = toml::from_str::<Package>(&manifest(None)).unwrap();
+        assert!(validate(&package).is_err());
+    }
+}
@@ -1,7 +1,21 @@
+pub use super::account::Account;
 pub use super::add::Add;
+pub use super::cache::Cache;
+pub use super::check_updates::CheckUpdates;
 pub use super::complete::Complete;
+pub use super::diff_lock::DiffLock;
+pub use super::env::Env;
+pub use super::fmt::Fmt;
+pub use super::gc::Gc;
 pub use super::init::Init;
 pub use super::install::Install;
+pub use super::java::Java;
 pub use super::launch::Launch;
+pub use super::licenses::Licenses;
+pub use super::lock::Lock;
 pub use super::login::Login;
+pub use super::migrate::Migrate;
+pub use super::mods::Mod;
 pub use super::nuke::Nuke;
+pub use super::pack::Pack;
+pub use super::verify::Verify;
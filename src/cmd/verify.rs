@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, bail};
+use clap::Parser;
+use colored::Colorize;
+use tracing::debug;
+
+use crate::{Creeper, Install, cmd::Execute, display_package};
+
+/// Verify the installed artifacts of the current game instance.
+#[derive(Clone, Debug, Parser)]
+pub struct Verify {
+    /// To additionally cross-check the cached installation of each package against the registry.
+    ///
+    /// This detects packages whose published artifacts changed since they were locked.
+    #[arg(long, default_value_t = false)]
+    pub online: bool,
+}
+
+impl Execute for Verify {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        if self.online && lib.args.offline {
+            bail!("online verification is blocked by offline mode");
+        }
+
+        let lock = lib
+            .game_lock()
+            .await?
+            .ok_or(anyhow!("missing creeper.lock, run `creeper install` first"))?;
+
+        if self.online && lock.registry != lib.config.registry {
+            bail!(
+                "creeper.lock was resolved against {}, but the configured registry is {}",
+                lock.registry,
+                lib.config.registry
+            );
+        }
+
+        let mut problem = 0;
+
+        for (id, version) in lock.package {
+            let name = display_package(&id, &version.version, version.rev);
+
+            let Some(install) = lib.get_install_cache(&id, &version).await? else {
+                eprintln!("{} {name} is not installed", "Missing".bold().red());
+                problem += 1;
+                continue;
+            };
+
+            for art in install.artifacts() {
+                if !lib.verify_artifact(art).await? {
+                    eprintln!("{} {art} of {name}", "Missing".bold().red());
+                    problem += 1;
+                }
+            }
+
+            if !self.online {
+                continue;
+            }
+
+            if !id.is_regular() {
+                debug!("skipping online verification of builtin package {name}");
+                continue;
+            }
+
+            let remote = lib
+                .query_registry(&id, &version.version, version.rev)
+                .await?;
+
+            for (b3, side) in diverged(&install, &remote.install) {
+                eprintln!(
+                    "{} {name}: artifact #{} only in {side} installation",
+                    "Diverged".bold().red(),
+                    &b3[..8]
+                );
+                problem += 1;
+            }
+        }
+
+        if problem > 0 {
+            bail!("verification found {problem} problems");
+        }
+
+        eprintln!("{} all packages", "Verified".bold().green());
+
+        Ok(())
+    }
+}
+
+/// Hashes of the artifacts in only one of the `local` and `remote` installations,
+/// each with the side it is found in, i.e. `"local"` or `"registry"`.
+fn diverged<'a>(local: &'a Install, remote: &'a Install) -> Vec<(&'a str, &'static str)> {
+    let local = local
+        .artifacts()
+        .map(|art| art.blake3.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let remote = remote
+        .artifacts()
+        .map(|art| art.blake3.as_str())
+        .collect::<BTreeSet<_>>();
+
+    local
+        .symmetric_difference(&remote)
+        .map(|b3| {
+            let side = if remote.contains(b3) {
+                "registry"
+            } else {
+                "local"
+            };
+            (*b3, side)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Artifact;
+
+    use super::*;
+
+    fn install(hash: &[&str]) -> Install {
+        Install {
+            mc_mod: hash
+                .iter()
+                .map(|b3| Artifact::new(b3.repeat(64), "mod.jar".into(), None, 0))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn registry_hash_diverged() {
+        let local = install(&["a", "b"]);
+
+        assert!(diverged(&local, &install(&["b", "a"])).is_empty());
+
+        let remote = install(&["a", "c"]);
+        assert_eq!(
+            diverged(&local, &remote),
+            [
+                ("b".repeat(64).as_str(), "local"),
+                ("c".repeat(64).as_str(), "registry"),
+            ]
+        );
+    }
+}
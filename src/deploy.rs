@@ -0,0 +1,242 @@
+use std::{io, path::Path};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{copy, hard_link, metadata, set_permissions},
+    task::spawn_blocking,
+};
+use tracing::trace;
+
+use crate::{symlink_auto, util::set_executable};
+
+/// How artifacts from the storage are placed into game instances.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeployMode {
+    /// Soft link to the storage, which costs no space but exposes the stored file.
+    #[default]
+    Symlink,
+
+    /// Copy-on-write clone where the filesystem supports it, e.g. btrfs and XFS,
+    /// falling back to a copy, which is just as independent of the storage.
+    ///
+    /// Cloned files can be modified without affecting the storage.
+    Reflink,
+
+    /// Hard link to the storage, so identical artifacts across instances share the same file,
+    /// falling back to a copy across filesystems.
+    ///
+    /// Unlike soft links, this survives relocating the storage, but the files are readonly.
+    Hardlink,
+
+    /// Independent copy, for files modified in place, e.g. mods rewritten by a loader.
+    Copy,
+}
+
+impl DeployMode {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Place the stored file `src` at `dst` according to `mode`, making it executable if `exec`.
+///
+/// Links share the permissions of the stored file, so executable files are always copied or cloned,
+/// leaving the storage untouched.
+/// `dst` must not exist.
+pub async fn deploy(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    mode: DeployMode,
+    exec: bool,
+) -> anyhow::Result<()> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+
+    let mode = match mode {
+        DeployMode::Symlink | DeployMode::Hardlink if exec => DeployMode::Copy,
+        mode => mode,
+    };
+
+    match mode {
+        DeployMode::Symlink => symlink_auto(src, dst).await?,
+        DeployMode::Reflink => {
+            let (s, d) = (src.to_owned(), dst.to_owned());
+            match spawn_blocking(move || reflink(&s, &d)).await? {
+                Ok(()) => trace!("reflinked {} to {}", src.display(), dst.display()),
+                Err(e) => {
+                    trace!("cannot reflink {}: {e}", dst.display());
+                    copy_writable(src, dst).await?;
+                }
+            }
+        }
+        DeployMode::Hardlink => {
+            if try_hard_link(src, dst).await {
+                return Ok(());
+            }
+
+            copy_writable(src, dst).await?;
+        }
+        DeployMode::Copy => copy_writable(src, dst).await?,
+    }
+
+    if exec {
+        set_executable(dst).await?;
+    }
+
+    Ok(())
+}
+
+/// Hard link `src` to `dst`, returning whether it succeeded, e.g. not across filesystems.
+async fn try_hard_link(src: &Path, dst: &Path) -> bool {
+    match hard_link(src, dst).await {
+        Ok(()) => {
+            trace!("hard linked {} to {}", src.display(), dst.display());
+            true
+        }
+        Err(e) => {
+            trace!("cannot hard link {}: {e}", dst.display());
+            false
+        }
+    }
+}
+
+async fn copy_writable(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    copy(src, dst).await?;
+
+    // stored files are readonly, but the copy should be independently modifiable by its owner
+    let mut perm = metadata(dst).await?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        perm = PermissionsExt::from_mode((perm.mode() & 0o7777) | 0o200);
+    }
+    // only clears the readonly attribute, there are no permissions for others to widen
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    perm.set_readonly(false);
+    set_permissions(dst, perm).await?;
+
+    trace!("copied {} to {}", src.display(), dst.display());
+
+    Ok(())
+}
+
+/// Clone `src` to a new file `dst` sharing the same extents, with the `FICLONE` ioctl.
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::{fs::File, os::fd::AsRawFd};
+
+    let from = File::open(src)?;
+    let to = File::create_new(dst)?;
+
+    // SAFETY: both file descriptors are valid for the duration of the call
+    let ret = unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) };
+
+    if ret != 0 {
+        let e = io::Error::last_os_error();
+        drop(to);
+        std::fs::remove_file(dst)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn copy_private_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::util::set_mode;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("stored.jar");
+        let dst = dir.path().join("deployed.jar");
+
+        tokio::fs::write(&src, "mod").await.unwrap();
+        set_mode(&src, 0o555).await.unwrap();
+
+        deploy(&src, &dst, DeployMode::Copy, false).await.unwrap();
+
+        let mode = metadata(&dst).await.unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode, 0o755);
+        assert_eq!(mode & 0o022, 0);
+
+        // the stored file is left readonly
+        let mode = metadata(&src).await.unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode, 0o555);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn deploy_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::util::set_mode;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("stored");
+        tokio::fs::write(&src, "#!/bin/sh").await.unwrap();
+        set_mode(&src, 0o444).await.unwrap();
+
+        for (i, mode) in DeployMode::value_variants().iter().enumerate() {
+            let dst = dir.path().join(format!("tool-{i}"));
+
+            deploy(&src, &dst, *mode, true).await.unwrap();
+
+            assert!(!dst.is_symlink(), "{mode:?}");
+            let mode = metadata(&dst).await.unwrap().permissions().mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+
+        // the stored file is shared by other deployments, which are not executable
+        let mode = metadata(&src).await.unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode, 0o444);
+
+        let dst = dir.path().join("data");
+        deploy(&src, &dst, DeployMode::Symlink, false)
+            .await
+            .unwrap();
+        assert!(dst.is_symlink());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reflink_fallback_copy() {
+        use std::os::unix::fs::MetadataExt;
+
+        use crate::util::set_mode;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("stored.jar");
+        let dst = dir.path().join("deployed.jar");
+
+        tokio::fs::write(&src, "mod").await.unwrap();
+        set_mode(&src, 0o444).await.unwrap();
+
+        // either cloned or copied, depending on the filesystem of the temporary directory
+        deploy(&src, &dst, DeployMode::Reflink, false)
+            .await
+            .unwrap();
+
+        let (src_meta, dst_meta) = (metadata(&src).await.unwrap(), metadata(&dst).await.unwrap());
+        assert_ne!(src_meta.ino(), dst_meta.ino());
+        assert_eq!(src_meta.nlink(), 1);
+
+        // modifying the deployed file leaves the storage intact
+        tokio::fs::write(&dst, "patched").await.unwrap();
+        assert_eq!(tokio::fs::read_to_string(&src).await.unwrap(), "mod");
+    }
+}
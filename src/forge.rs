@@ -0,0 +1,485 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    iter::once,
+    mem::take,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use mc_launchermeta::version::library::Library;
+use neoforge::install::{DataValue, Processor};
+use reqwest::Client;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, trace};
+
+use crate::{
+    Artifact, Checksum, Creeper, Id, Install,
+    builtin::{GetIndex, SyncBuiltinIndex},
+    checksum::{HashFunc, parse_sidecar},
+    http::{FetchOption, fetch_json, fetch_json_raw},
+    index::{Index, VersionRev},
+    pack::PackNode,
+    path::creeper_cache_dir,
+    vanilla::McVersionExt,
+    zip::{extract_zip, extract_zip_to, zip_entries},
+};
+
+/// The oldest minecraft version whose Forge installer runs processors, which is the only format supported.
+const MIN_MC_VERSION: Version = Version::new(1, 13, 0);
+
+fn cache_path() -> anyhow::Result<PathBuf> {
+    let path = creeper_cache_dir()?.join("builtin").join("forge");
+    Ok(path)
+}
+
+pub struct ForgeManager {
+    http: Client,
+    fetch: FetchOption,
+}
+
+impl ForgeManager {
+    pub fn new(http: Client, fetch: FetchOption) -> Self {
+        Self { http, fetch }
+    }
+}
+
+/// Query Forge versions, as a map from minecraft versions to `<minecraft>-<forge>` versions.
+async fn query_forge_versions(
+    http: &Client,
+    fetch: &FetchOption,
+) -> anyhow::Result<BTreeMap<String, Vec<String>>> {
+    const VERSIONS_URL: &str =
+        "https://files.minecraftforge.net/net/minecraftforge/forge/maven-metadata.json";
+
+    let versions = fetch_json(http, fetch, VERSIONS_URL, None).await?;
+
+    Ok(versions)
+}
+
+/// Build the index of Forge versions from `versions` as given by [`query_forge_versions`],
+/// ignoring those not [supported](MIN_MC_VERSION).
+fn forge_index(versions: BTreeMap<String, Vec<String>>) -> anyhow::Result<Index> {
+    let mut index = Index::new();
+
+    for (mc, versions) in versions {
+        let Ok(mc_version) = mc.parse::<Version>() else {
+            trace!("ignoring Forge for invalid minecraft version {mc}");
+            continue;
+        };
+
+        if mc_version < MIN_MC_VERSION {
+            continue;
+        }
+
+        for version in versions {
+            let Some(forge) = version
+                .strip_prefix(&format!("{mc}-"))
+                .and_then(|v| v.parse::<Version>().ok())
+            else {
+                trace!("ignoring invalid Forge version {version}");
+                continue;
+            };
+
+            let req = format!("={mc_version}").parse()?;
+            let node = PackNode {
+                dep: once((Id::vanilla(), req)).collect(),
+                ..Default::default()
+            };
+
+            index.entry(VersionRev::new(forge)).or_insert(node);
+        }
+    }
+
+    Ok(index)
+}
+
+impl SyncBuiltinIndex for ForgeManager {
+    fn package(&self) -> Id {
+        Id::forge()
+    }
+
+    async fn sync_index(&self) -> anyhow::Result<Index> {
+        info!("updating Forge metadata");
+
+        let versions = query_forge_versions(&self.http, &self.fetch).await?;
+
+        let index = forge_index(versions)?;
+
+        debug!("retrieved {} Forge versions", index.len());
+
+        Ok(index)
+    }
+
+    fn cache_expiry(&self) -> Duration {
+        Duration::from_hours(72)
+    }
+}
+
+/// The subset of Forge's `install_profile.json` needed to install the client.
+///
+/// Unlike NeoForge's, this may contain comments and processor outputs, which are ignored.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForgeInstallProfile {
+    data: HashMap<String, DataValue>,
+    processors: Vec<ForgeProcessor>,
+    libraries: Vec<Library>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForgeProcessor {
+    sides: Option<Vec<String>>,
+    jar: String,
+    #[serde(default)]
+    classpath: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl From<ForgeProcessor> for Processor {
+    fn from(value: ForgeProcessor) -> Self {
+        Self {
+            sides: value.sides,
+            jar: value.jar,
+            classpath: value.classpath,
+            args: value.args,
+        }
+    }
+}
+
+/// Split `libs` into those without a download source, and the others.
+fn split_local_lib(libs: Vec<Library>) -> (Vec<Library>, Vec<Library>) {
+    libs.into_iter().partition(|lib| {
+        lib.downloads
+            .as_ref()
+            .and_then(|d| d.artifact.as_ref())
+            .is_some_and(|a| a.url.is_empty())
+    })
+}
+
+/// Whether a processor has to run when installing the client.
+fn runs_on_client(proc: &Processor) -> bool {
+    proc.sides
+        .as_ref()
+        .is_none_or(|x| x.contains(&"client".into()))
+}
+
+impl Creeper {
+    /// The minecraft version a Forge version is built for, as recorded in the index.
+    async fn forge_mc_version(&self, version: &Version) -> anyhow::Result<Version> {
+        let index = self.forge.get_index().await?;
+
+        let comparator = index
+            .get(&VersionRev::new(version.clone()))
+            .and_then(|node| node.dep.get(&Id::vanilla()))
+            .and_then(|req| req.comparators.first())
+            .ok_or(anyhow!("unknown Forge version {version}"))?;
+
+        let mc_version = Version::new(
+            comparator.major,
+            comparator.minor.unwrap_or(0),
+            comparator.patch.unwrap_or(0),
+        );
+
+        Ok(mc_version)
+    }
+
+    async fn forge_installer_jar(
+        &self,
+        mc_version: &Version,
+        version: &Version,
+    ) -> anyhow::Result<Artifact> {
+        let path = format!(
+            "net/minecraftforge/forge/{mc_version}-{version}/forge-{mc_version}-{version}-installer.jar"
+        );
+
+        let url = if self.config.use_bmclapi {
+            format!("https://bmclapi2.bangbang93.com/maven/{path}")
+        } else {
+            format!("https://maven.minecraftforge.net/{path}")
+        };
+
+        let sha1_url = format!("{url}.sha1");
+
+        let sha1 = fetch_json_raw(&self.http, &self.fetch, &sha1_url, None).await?;
+        let sha1 = parse_sidecar(&String::from_utf8_lossy(&sha1), HashFunc::Sha1)
+            .ok_or(anyhow!("invalid checksum from {sha1_url}"))?;
+
+        let name = format!("forge-{mc_version}-{version}-installer.jar");
+        let installer = self
+            .download(name, url, None, once(Checksum::sha1(sha1)))
+            .await?;
+
+        Ok(installer)
+    }
+
+    /// Take libraries without a download source out of `libs`,
+    /// storing those embedded in the installer under `maven/`.
+    ///
+    /// The others are produced by the processors, and collected afterwards.
+    async fn forge_embedded_lib(
+        &self,
+        installer: &Path,
+        libs: &mut Vec<Library>,
+    ) -> anyhow::Result<HashMap<PathBuf, Artifact>> {
+        let mut embedded = HashMap::new();
+
+        let (local, remote) = split_local_lib(take(libs));
+
+        *libs = remote;
+
+        let entries = zip_entries(installer)
+            .await?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<HashSet<_>>();
+
+        for lib in local {
+            let path = lib.downloads.unwrap().artifact.unwrap().path;
+            let entry = PathBuf::from("maven").join(&path);
+
+            if !entries.contains(&entry) {
+                trace!("library {path} is to be produced by processors");
+                continue;
+            }
+
+            let art = self.store_zip_entry(installer, entry).await?;
+            trace!("found embedded library {path}");
+            embedded.insert(path.into(), art);
+        }
+
+        Ok(embedded)
+    }
+
+    /// Install the Forge client by running the processors of its installer,
+    /// which patch the minecraft jar and place the result under java libraries.
+    pub(crate) async fn forge_install(&self, version: &Version) -> anyhow::Result<Install> {
+        let mc_version = self.forge_mc_version(version).await?;
+
+        let installer = self.forge_installer_jar(&mc_version, version).await?;
+
+        let installer = self.retrieve_artifact(&installer).await?;
+
+        // handle install as defined in `version.json`
+
+        let mc = extract_zip(&installer, "version.json").await?;
+        let mut mc = serde_json::from_str::<McVersionExt>(&mc)?;
+
+        let embedded = self
+            .forge_embedded_lib(&installer, &mut mc.libraries)
+            .await?;
+
+        let mut install = self.mc_version_install(mc).await?;
+
+        install.java_lib_file.extend(embedded);
+
+        // handle install as defined in `install_profile.json`
+
+        let mut container =
+            self.new_install_container(cache_path()?.join("tmp").join(version.to_string()));
+        container.init().await?;
+
+        let install_profile = extract_zip(&installer, "install_profile.json").await?;
+        let mut install_profile = serde_json::from_str::<ForgeInstallProfile>(&install_profile)?;
+
+        let embedded = self
+            .forge_embedded_lib(&installer, &mut install_profile.libraries)
+            .await?;
+
+        let mut java_lib_file = self.vanilla_lib(install_profile.libraries).await?;
+        java_lib_file.extend(embedded);
+
+        container.add_lib_file(java_lib_file.clone());
+
+        info!("preparing forge install environment");
+
+        let vanilla_install = {
+            // repeat code from [`Self::install`] to avoid async recursion
+            if let Some(install) = self
+                .get_install_cache(&Id::vanilla(), &mc_version.clone().into())
+                .await?
+            {
+                install
+            } else {
+                let install = self.vanilla_install(&mc_version).await?;
+                self.set_install_cache(&Id::vanilla(), &mc_version.clone().into(), Some(&install))
+                    .await?;
+                install
+            }
+        };
+
+        let mc_jar = vanilla_install
+            .mc_jar
+            .ok_or(anyhow!("missing minecraft jar in vanilla install"))?;
+        let mc_jar = self.retrieve_artifact(&mc_jar).await?;
+
+        // prepare variables
+        let mut vars = install_profile
+            .data
+            .into_iter()
+            .map(|(k, v)| (k, v.client))
+            .chain(once(("SIDE".into(), "client".into())))
+            .chain(once(("MINECRAFT_JAR".into(), mc_jar.display().to_string())))
+            .chain(once(("MINECRAFT_VERSION".into(), mc_version.to_string())))
+            .chain(once((
+                "ROOT".into(),
+                container.path().display().to_string(),
+            )))
+            .chain(once(("INSTALLER".into(), installer.display().to_string())))
+            .chain(once((
+                "LIBRARY_DIR".into(),
+                container.lib_dir().display().to_string(),
+            )))
+            .collect::<HashMap<_, _>>();
+
+        // special case: BINPATCH /data/client.lzma is packaged in the installer jar
+        // extract it first
+        let binpatch = container
+            .path()
+            .join(".installer")
+            .join("data")
+            .join("client.lzma");
+        extract_zip_to(&installer, "data/client.lzma", &binpatch).await?;
+        vars.insert("BINPATCH".into(), binpatch.display().to_string());
+
+        container.add_var(vars);
+        container.deploy_lib().await?;
+
+        info!("running forge install processors");
+
+        for proc in install_profile.processors {
+            let proc = Processor::from(proc);
+
+            if !runs_on_client(&proc) {
+                debug!("skipping a processor because side mismatch: {proc}");
+                continue;
+            }
+
+            container.run(&proc).await?;
+        }
+
+        info!("collecting forge install result");
+
+        let collect = container
+            .collect_lib_file(
+                java_lib_file
+                    .keys()
+                    .chain(install.java_lib_class.keys())
+                    .chain(install.java_lib_mod.keys())
+                    .chain(install.java_lib_file.keys())
+                    .chain(vanilla_install.java_lib_class.keys())
+                    .chain(vanilla_install.java_lib_mod.keys())
+                    .chain(vanilla_install.java_lib_file.keys())
+                    .map(|k| k.as_path()),
+            )
+            .await?;
+
+        container.deinit().await?;
+
+        java_lib_file.extend(collect);
+
+        install.extend(once(Install {
+            java_lib_file,
+            ..Default::default()
+        }));
+
+        install.simplify();
+
+        install.disable_mc_jar = true;
+
+        Ok(install)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_from_metadata() {
+        let versions = serde_json::from_str::<BTreeMap<String, Vec<String>>>(
+            r#"{
+                "1.12.2": ["1.12.2-14.23.5.2860"],
+                "1.20.1": ["1.20.1-47.2.0", "1.20.1-47.1.0", "invalid"],
+                "1.19.4": ["1.19.4-45.1.0"]
+            }"#,
+        )
+        .unwrap();
+
+        let index = forge_index(versions).unwrap();
+
+        // versions without processors are not supported
+        assert_eq!(index.len(), 3);
+        let node = &index[&VersionRev::new("47.2.0".parse().unwrap())];
+        assert_eq!(node.dep[&Id::vanilla()], "=1.20.1".parse().unwrap());
+        let node = &index[&VersionRev::new("45.1.0".parse().unwrap())];
+        assert_eq!(node.dep[&Id::vanilla()], "=1.19.4".parse().unwrap());
+    }
+
+    const INSTALL_PROFILE: &str = r#"{
+        "_comment_": ["Please do not automate the download and installation of Forge."],
+        "spec": 1,
+        "profile": "forge",
+        "version": "1.20.1-forge-47.2.0",
+        "path": null,
+        "minecraft": "1.20.1",
+        "serverJarPath": "{LIBRARY_DIR}/net/minecraft/server/1.20.1/server-1.20.1.jar",
+        "data": {
+            "BINPATCH": { "client": "/data/client.lzma", "server": "/data/server.lzma" }
+        },
+        "processors": [
+            { "sides": ["server"], "jar": "net.minecraftforge:installertools:1.3.0", "args": ["--task", "EXTRACT_FILES"] },
+            { "jar": "net.minecraftforge:installertools:1.3.0", "classpath": ["net.md-5:SpecialSource:1.11.0"], "args": ["--task", "MCP_DATA"] },
+            {
+                "sides": ["client"],
+                "jar": "net.minecraftforge:binarypatcher:1.1.1",
+                "args": ["--patch", "{BINPATCH}"],
+                "outputs": { "{PATCHED}": "{PATCHED_SHA}" }
+            }
+        ],
+        "libraries": [
+            {
+                "name": "net.minecraftforge:forge:1.20.1-47.2.0:universal",
+                "downloads": { "artifact": { "path": "net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-universal.jar", "sha1": "0", "size": 0, "url": "" } }
+            },
+            {
+                "name": "net.minecraftforge:binarypatcher:1.1.1",
+                "downloads": { "artifact": { "path": "net/minecraftforge/binarypatcher/1.1.1/binarypatcher-1.1.1.jar", "sha1": "1", "size": 0, "url": "https://maven.minecraftforge.net/net/minecraftforge/binarypatcher/1.1.1/binarypatcher-1.1.1.jar" } }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn install_profile_client() {
+        let profile = serde_json::from_str::<ForgeInstallProfile>(INSTALL_PROFILE).unwrap();
+
+        assert_eq!(profile.data["BINPATCH"].client, "/data/client.lzma");
+
+        let client = profile
+            .processors
+            .into_iter()
+            .map(Processor::from)
+            .filter(runs_on_client)
+            .map(|proc| proc.jar)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            client,
+            [
+                "net.minecraftforge:installertools:1.3.0",
+                "net.minecraftforge:binarypatcher:1.1.1",
+            ]
+        );
+
+        // libraries without a source are embedded in the installer or produced by processors
+        let (local, remote) = split_local_lib(profile.libraries);
+        assert_eq!(
+            local[0].name,
+            "net.minecraftforge:forge:1.20.1-47.2.0:universal"
+        );
+        assert_eq!(remote[0].name, "net.minecraftforge:binarypatcher:1.1.1");
+        assert_eq!((local.len(), remote.len()), (1, 1));
+    }
+}
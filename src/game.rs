@@ -1,35 +1,70 @@
 use std::{
+    collections::BTreeSet,
     env::current_dir,
     path::{Path, PathBuf},
     sync::OnceLock,
 };
 
 use anyhow::anyhow;
-use tokio::fs::try_exists;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{canonicalize, try_exists};
 
-use crate::{Creeper, Package, lock::Lock, util::TomlFile};
+use crate::{
+    Creeper, Id, Package,
+    lock::{Lock, lock_file},
+    util::TomlFile,
+};
+
+/// State local to a game instance, kept under `.creeper` instead of the manifest, which may be published.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct InstanceState {
+    /// Dependencies whose mods are not deployed, while still being resolved and installed.
+    ///
+    /// This is useful to temporarily rule out a mod when debugging conflicts.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub disabled: BTreeSet<Id>,
+}
 
 pub struct GameManager {
     dir: OnceLock<PathBuf>,
+    manifest: Option<PathBuf>,
     pack: TomlFile<Package>,
     lock: TomlFile<Lock>,
+    state: TomlFile<InstanceState>,
 }
 
 impl GameManager {
-    pub fn new(dir: Option<PathBuf>) -> Self {
+    pub fn new(dir: Option<PathBuf>, manifest: Option<PathBuf>) -> Self {
         let d = OnceLock::new();
         if let Some(dir) = dir {
             d.set(dir).unwrap();
+        } else if let Some(manifest) = &manifest {
+            let dir = if manifest == Path::new("-") {
+                PathBuf::from(".")
+            } else {
+                match manifest.parent() {
+                    Some(parent) if parent != Path::new("") => parent.into(),
+                    _ => PathBuf::from("."),
+                }
+            };
+            d.set(dir).unwrap();
         }
         Self {
             dir: d,
+            manifest,
             pack: TomlFile::new(),
-            lock: TomlFile::new(),
+            lock: lock_file(),
+            state: TomlFile::new(),
         }
     }
 
+    /// Find the nearest ancestor of `start` containing a `creeper.toml`.
+    ///
+    /// The start path is canonicalized first, which resolves all symlinks and fails on a symlink cycle,
+    /// so that walking up the parents always terminates.
     async fn find_dir(start: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
-        let mut curr = start.as_ref().to_path_buf();
+        let mut curr = canonicalize(start).await?;
         loop {
             if try_exists(curr.join("creeper.toml")).await? {
                 break Ok(curr);
@@ -49,6 +84,10 @@ impl GameManager {
     }
 
     pub async fn pack_path(&self) -> anyhow::Result<PathBuf> {
+        if let Some(manifest) = &self.manifest {
+            return Ok(manifest.clone());
+        }
+
         let dir = self.dir().await?;
         Ok(dir.join("creeper.toml"))
     }
@@ -78,6 +117,15 @@ impl GameManager {
         Ok(())
     }
 
+    /// Like [`GameManager::set_pack`], but write the manifest from TOML text kept as written.
+    pub async fn set_pack_toml(&self, toml: &str) -> anyhow::Result<()> {
+        let path = self.pack_path().await?;
+
+        self.pack.write_str(path, toml).await?;
+
+        Ok(())
+    }
+
     pub async fn lock(&self) -> anyhow::Result<Option<Lock>> {
         let path = self.lock_path().await?;
 
@@ -95,6 +143,30 @@ impl GameManager {
     }
 }
 
+impl GameManager {
+    pub async fn state_path(&self) -> anyhow::Result<PathBuf> {
+        let dir = self.dir().await?;
+        Ok(dir.join(".creeper").join("instance.toml"))
+    }
+
+    /// The local state of the instance, the default if none is recorded yet.
+    pub async fn state(&self) -> anyhow::Result<InstanceState> {
+        let path = self.state_path().await?;
+
+        let state = self.state.read(path).await?.unwrap_or_default();
+
+        Ok(state)
+    }
+
+    pub async fn set_state(&self, state: InstanceState) -> anyhow::Result<()> {
+        let path = self.state_path().await?;
+
+        self.state.write(path, Some(state)).await?;
+
+        Ok(())
+    }
+}
+
 impl Creeper {
     pub async fn game_dir(&self) -> anyhow::Result<&PathBuf> {
         self.game.dir().await
@@ -136,3 +208,56 @@ impl Creeper {
         self.game.set_lock(lock).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn find_dir_symlink_cycle() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("creeper.toml"), "").unwrap();
+
+        // a link back to an ancestor is resolved rather than walked
+        std::fs::create_dir(root.join("sub")).unwrap();
+        symlink(&root, root.join("sub").join("loop")).unwrap();
+        let start = root.join("sub/loop/sub/loop/sub");
+        assert_eq!(GameManager::find_dir(&start).await.unwrap(), root);
+
+        // links pointing at each other cannot be resolved at all
+        symlink(root.join("b"), root.join("a")).unwrap();
+        symlink(root.join("a"), root.join("b")).unwrap();
+        assert!(GameManager::find_dir(root.join("a")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn disable_enable() {
+        let dir = tempfile::tempdir().unwrap();
+        let game = GameManager::new(Some(dir.path().into()), None);
+        let sodium = "sodium".parse::<Id>().unwrap();
+
+        assert!(game.state().await.unwrap().disabled.is_empty());
+
+        let mut state = game.state().await.unwrap();
+        state.disabled.insert(sodium.clone());
+        game.set_state(state).await.unwrap();
+
+        // kept out of the manifest, and read back by another run
+        let path = dir.path().join(".creeper").join("instance.toml");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "disabled = [\"sodium\"]\n"
+        );
+        let game = GameManager::new(Some(dir.path().into()), None);
+        assert!(game.state().await.unwrap().disabled.contains(&sodium));
+
+        let mut state = game.state().await.unwrap();
+        state.disabled.remove(&sodium);
+        game.set_state(state).await.unwrap();
+        assert!(game.state().await.unwrap().disabled.is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+use std::{borrow::Cow, collections::BTreeMap, time::Duration};
+
+use anyhow::{anyhow, bail};
+use const_hex::ToHexExt;
+use reqwest::{
+    Client, Proxy, Response, StatusCode,
+    header::{CONTENT_TYPE, RETRY_AFTER},
+};
+use ring::digest::{SHA1_FOR_LEGACY_USE_ONLY, digest};
+use serde::de::DeserializeOwned;
+use tokio::time::sleep;
+use tracing::{debug, trace, warn};
+use url::Url;
+
+use crate::{
+    Config,
+    checksum::{Checksum, SIDECAR, parse_sidecar},
+};
+
+/// Build the HTTP client according to config.
+///
+/// If [`Config::proxy`] is non-empty, each request selects its proxy by destination host,
+/// otherwise the system proxy settings apply.
+pub fn build_client(config: &Config) -> anyhow::Result<Client> {
+    let mut builder = Client::builder();
+
+    if !config.proxy.is_empty() {
+        let mut rule = BTreeMap::new();
+
+        for (host, proxy) in &config.proxy {
+            let proxy = match proxy.as_str() {
+                "none" => None,
+                url => Some(
+                    url.parse::<Url>()
+                        .map_err(|e| anyhow!("invalid proxy {url} for {host}: {e}"))?,
+                ),
+            };
+            rule.insert(host.clone(), proxy);
+        }
+
+        builder = builder.proxy(Proxy::custom(move |url| {
+            let host = url.host_str()?;
+            let proxy = rule.get(host).or_else(|| rule.get("*"))?.clone();
+            debug!(
+                "using proxy {} for {host}",
+                proxy.as_ref().map(Url::as_str).unwrap_or("none")
+            );
+            proxy
+        }));
+    }
+
+    let client = builder.build()?;
+
+    Ok(client)
+}
+
+/// Default of [`crate::Config::fetch_attempt`].
+pub const DEFAULT_FETCH_ATTEMPT: u32 = 3;
+
+/// Wait before the second attempt of a fetch, doubled for each further attempt.
+pub(crate) const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest wait requested by `Retry-After` that is honored, beyond which the usual backoff applies.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How resources are fetched from the network, as configured by [`Config::mirror`] and [`Config::fetch_attempt`].
+#[derive(Clone, Debug)]
+pub struct FetchOption {
+    /// Rules rewriting URLs, from a URL prefix to its replacement.
+    pub mirror: BTreeMap<String, String>,
+
+    /// Number of attempts of [`fetch_json_raw`] and artifact downloads, at least one.
+    pub attempt: u32,
+}
+
+impl FetchOption {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            mirror: config.mirror.clone(),
+            attempt: config.fetch_attempt.max(1),
+        }
+    }
+
+    /// Rewrite `url` with the longest matching prefix in [`Self::mirror`], if any.
+    pub fn mirror<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        let Some((from, to)) = self
+            .mirror
+            .iter()
+            .filter(|(from, _)| url.starts_with(from.as_str()))
+            .max_by_key(|(from, _)| from.len())
+        else {
+            return Cow::Borrowed(url);
+        };
+
+        let rewritten = format!("{to}{}", &url[from.len()..]);
+        trace!("rewriting {url} to {rewritten}");
+
+        Cow::Owned(rewritten)
+    }
+}
+
+impl Default for FetchOption {
+    fn default() -> Self {
+        Self {
+            mirror: BTreeMap::new(),
+            attempt: DEFAULT_FETCH_ATTEMPT,
+        }
+    }
+}
+
+/// Whether a failed request may succeed if retried, i.e. on connection failures, timeouts and server errors.
+pub(crate) fn is_transient(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => e.is_connect() || e.is_timeout() || e.is_request() || e.is_body(),
+    }
+}
+
+/// The wait requested by a rate limited or unavailable server in seconds, if any.
+pub(crate) fn retry_after(res: &Response) -> Option<Duration> {
+    if !matches!(
+        res.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+
+    let secs = res
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+
+    Some(Duration::from_secs(secs)).filter(|wait| *wait <= MAX_RETRY_AFTER)
+}
+
+/// Fetch a JSON document and return its raw body, e.g. to cache it on disk.
+///
+/// The URL is rewritten according to [`FetchOption::mirror`].
+/// Transient failures and checksum mismatches are retried with exponential backoff,
+/// or after the wait in `Retry-After` if the server is rate limiting.
+/// HTTP errors are returned as [`reqwest::Error`], so that callers can tell a missing document by its status.
+/// The body must match `sha1` if specified, and must not be HTML,
+/// which is usually an error page of a proxy or captive portal.
+pub async fn fetch_json_raw(
+    http: &Client,
+    option: &FetchOption,
+    url: &str,
+    sha1: Option<&str>,
+) -> anyhow::Result<Vec<u8>> {
+    let max = option.attempt;
+
+    let url = &*option.mirror(url);
+
+    let mut delay = INITIAL_BACKOFF;
+
+    for attempt in 1..=max {
+        let res = match http.get(url).send().await {
+            Ok(res) => res,
+            Err(e) if is_transient(&e) && attempt < max => {
+                warn!("failed to fetch {url}, retrying in {delay:?}: {e}");
+                sleep(delay).await;
+                delay *= 2;
+                continue;
+            }
+            Err(e) => Err(e)?,
+        };
+
+        let wait = retry_after(&res);
+
+        let res = match res.error_for_status() {
+            Ok(res) => res,
+            Err(e) if is_transient(&e) && attempt < max => {
+                let wait = wait.unwrap_or(delay);
+                warn!("failed to fetch {url}, retrying in {wait:?}: {e}");
+                sleep(wait).await;
+                delay *= 2;
+                continue;
+            }
+            Err(e) => Err(e)?,
+        };
+
+        if let Some(content_type) = res.headers().get(CONTENT_TYPE)
+            && content_type.to_str().is_ok_and(|t| t.contains("html"))
+        {
+            bail!("expected JSON from {url}, got {content_type:?}");
+        }
+
+        let body = match res.bytes().await {
+            Ok(body) => body.to_vec(),
+            Err(e) if attempt < max => {
+                warn!("failed to fetch {url}, retrying in {delay:?}: {e}");
+                sleep(delay).await;
+                delay *= 2;
+                continue;
+            }
+            Err(e) => Err(e)?,
+        };
+
+        if let Some(sha1) = sha1 {
+            let actual = digest(&SHA1_FOR_LEGACY_USE_ONLY, &body).encode_hex();
+
+            if actual != sha1 {
+                if attempt < max {
+                    warn!("checksum mismatch of {url}, retrying in {delay:?}");
+                    sleep(delay).await;
+                    delay *= 2;
+                    continue;
+                }
+
+                bail!("checksum mismatch of {url}: expected sha1 {sha1}, got {actual}");
+            }
+        }
+
+        return Ok(body);
+    }
+
+    unreachable!()
+}
+
+/// Like [`fetch_json_raw`], but deserialize the body.
+pub async fn fetch_json<T: DeserializeOwned>(
+    http: &Client,
+    option: &FetchOption,
+    url: &str,
+    sha1: Option<&str>,
+) -> anyhow::Result<T> {
+    let body = fetch_json_raw(http, option, url, sha1).await?;
+
+    let value =
+        serde_json::from_slice(&body).map_err(|e| anyhow!("invalid JSON from {url}: {e}"))?;
+
+    Ok(value)
+}
+
+/// Whether an error from [`fetch_json_raw`] means the document does not exist.
+pub fn is_not_found(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        == Some(StatusCode::NOT_FOUND)
+}
+
+/// Checksums published next to the file at `url`, e.g. `foo.jar.sha1` for `foo.jar`.
+///
+/// Sidecars absent or unparsable are skipped, so that this is empty for hosts publishing none.
+pub async fn fetch_sidecar(
+    http: &Client,
+    option: &FetchOption,
+    url: &str,
+) -> anyhow::Result<Vec<Checksum>> {
+    let mut checksum = vec![];
+
+    for function in SIDECAR {
+        let sidecar = format!("{url}.{function}");
+        let res = http.get(&*option.mirror(&sidecar)).send().await?;
+
+        if !res.status().is_success() {
+            trace!("no sidecar {sidecar}: {}", res.status());
+            continue;
+        }
+
+        match parse_sidecar(&res.text().await?, function) {
+            Some(hex_hash) => checksum.push(Checksum { function, hex_hash }),
+            None => debug!("ignoring invalid sidecar {sidecar}"),
+        }
+    }
+
+    Ok(checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serve each response in turn to a request, and return the URL.
+    async fn serve(res: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/version.json", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            for res in res {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut req = vec![];
+                let mut buf = [0; 1024];
+                while !req.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                }
+
+                stream.write_all(res.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        url
+    }
+
+    const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nretry-after: 0\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+    #[tokio::test]
+    async fn fetch_retry() {
+        let url = serve(vec![
+            UNAVAILABLE,
+            UNAVAILABLE,
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}",
+        ])
+        .await;
+
+        let body = fetch_json_raw(&Client::new(), &FetchOption::default(), &url, None)
+            .await
+            .unwrap();
+        assert_eq!(body, b"{}");
+    }
+
+    #[tokio::test]
+    async fn fetch_no_retry_client_error() {
+        let url = serve(vec![
+            "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        ])
+        .await;
+
+        let e = fetch_json_raw(&Client::new(), &FetchOption::default(), &url, None)
+            .await
+            .unwrap_err();
+        assert!(is_not_found(&e));
+    }
+
+    #[tokio::test]
+    async fn fetch_give_up() {
+        let url = serve(vec![UNAVAILABLE; 2]).await;
+
+        let option = FetchOption {
+            attempt: 2,
+            ..FetchOption::default()
+        };
+        assert!(
+            fetch_json_raw(&Client::new(), &option, &url, None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn mirror_empty() {
+        let option = FetchOption::default();
+        let url = "https://libraries.minecraft.net/foo.jar";
+
+        assert!(matches!(option.mirror(url), Cow::Borrowed(u) if u == url));
+    }
+
+    #[test]
+    fn mirror_longest_prefix() {
+        let option = FetchOption {
+            mirror: [
+                ("https://".into(), "https://proxy.example/".into()),
+                (
+                    "https://libraries.minecraft.net/".into(),
+                    "https://bmclapi2.bangbang93.com/maven/".into(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..FetchOption::default()
+        };
+
+        assert_eq!(
+            option.mirror("https://libraries.minecraft.net/foo.jar"),
+            "https://bmclapi2.bangbang93.com/maven/foo.jar"
+        );
+        assert_eq!(
+            option.mirror("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json"),
+            "https://proxy.example/piston-meta.mojang.com/mc/game/version_manifest_v2.json"
+        );
+        assert_eq!(
+            option.mirror("http://example.com/foo"),
+            "http://example.com/foo"
+        );
+    }
+}
@@ -10,6 +10,9 @@ use anyhow::{anyhow, bail, ensure};
 use semver::{Version, VersionReq};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 
+/// Default number of directory levels in a sparse package index.
+pub const DEFAULT_INDEX_DEPTH: usize = 2;
+
 /// A package identifier.
 ///
 /// # Format
@@ -28,18 +31,47 @@ pub struct Id(String);
 
 impl Id {
     /// **Relative** storage path of this package to the storage root,
-    /// sparsely indexed by the initial characters.
+    /// sparsely indexed by the initial characters at [`DEFAULT_INDEX_DEPTH`].
     pub fn indexed_path(&self) -> impl AsRef<Path> {
-        let head4 = self
+        self.indexed_path_at(DEFAULT_INDEX_DEPTH)
+    }
+
+    /// **Relative** storage path of this package to the storage root,
+    /// with `depth` levels of directories each named by the next two initial characters.
+    pub fn indexed_path_at(&self, depth: usize) -> PathBuf {
+        let mut path = PathBuf::from(".");
+        for segment in self.index_segments(depth) {
+            path.push(segment);
+        }
+        path.join(self.as_str())
+    }
+
+    /// Names of the `depth` index directories containing this package.
+    ///
+    /// Only lowercase letters are taken into account, padded with `x` for short ids.
+    fn index_segments(&self, depth: usize) -> Vec<String> {
+        let head = self
             .chars()
             .filter(char::is_ascii_lowercase)
             .chain(repeat('x'))
-            .take(4)
-            .collect::<String>();
-        PathBuf::from(".")
-            .join(&head4[0..2])
-            .join(&head4[2..4])
-            .join(&self.as_str())
+            .take(depth * 2)
+            .collect::<Vec<_>>();
+        head.chunks(2).map(|c| c.iter().collect()).collect()
+    }
+
+    /// Derive an id from a human-readable name, replacing invalid characters with underscores.
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        name.to_ascii_lowercase()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+            .parse()
     }
 
     pub fn minecraft() -> Self {
@@ -114,14 +146,9 @@ impl Id {
             .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
     }
 
-    pub fn is_of_index(&self, lv1: &str, lv2: &str) -> bool {
-        let head4 = self
-            .chars()
-            .filter(char::is_ascii_lowercase)
-            .chain(repeat('x'))
-            .take(4)
-            .collect::<String>();
-        &head4[0..2] == lv1 && &head4[2..4] == lv2
+    /// Whether this package belongs to the index directories `index`, outermost first.
+    pub fn is_of_index(&self, index: &[&str]) -> bool {
+        self.index_segments(index.len()) == index
     }
 }
 
@@ -216,7 +243,7 @@ impl FromStr for IdVersionReq {
 
         let (id, version_req) = match pieces.len() {
             1 => (pieces[0].parse()?, VersionReq::STAR),
-            2 => (pieces[0].parse()?, pieces[1].parse()?),
+            2 => (pieces[0].parse()?, parse_version_req(pieces[1])?),
             _ => bail!("expected <PACKAGE>[@<VERSION_REQ>], found {s}"),
         };
 
@@ -226,6 +253,17 @@ impl FromStr for IdVersionReq {
     }
 }
 
+/// Parse a version requirement, where `latest` is accepted as an alias of `*`.
+///
+/// Either way the newest version compatible with the other dependencies is chosen and pinned in the lock.
+pub fn parse_version_req(s: &str) -> anyhow::Result<VersionReq> {
+    if s.trim() == "latest" {
+        return Ok(VersionReq::STAR);
+    }
+    let req = s.parse()?;
+    Ok(req)
+}
+
 pub fn display_package(id: &Id, version: &Version, rev: u32) -> String {
     if rev == 0 {
         return format!("{id}@{version}");
@@ -233,3 +271,42 @@ pub fn display_package(id: &Id, version: &Version, rev: u32) -> String {
 
     format!("{id}@{version}#{rev}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed_path_depth() {
+        let id = "fabric-api".parse::<Id>().unwrap();
+        assert_eq!(id.indexed_path_at(1), Path::new("./fa/fabric-api"));
+        assert_eq!(id.indexed_path_at(2), Path::new("./fa/br/fabric-api"));
+        assert_eq!(id.indexed_path_at(3), Path::new("./fa/br/ic/fabric-api"));
+
+        // short ids and non-letters are padded with `x`
+        let id = "a1b".parse::<Id>().unwrap();
+        assert_eq!(id.indexed_path_at(1), Path::new("./ab/a1b"));
+        assert_eq!(id.indexed_path_at(2), Path::new("./ab/xx/a1b"));
+        assert_eq!(id.indexed_path_at(3), Path::new("./ab/xx/xx/a1b"));
+    }
+
+    #[test]
+    fn indexed_path_is_of_index() {
+        for id in ["a", "a1b", "fabric-api", "sodium"] {
+            let id = id.parse::<Id>().unwrap();
+            for depth in 1..=3 {
+                let path = id.indexed_path_at(depth);
+                let index = path
+                    .iter()
+                    .skip(1)
+                    .take(depth)
+                    .map(|s| s.to_str().unwrap())
+                    .collect::<Vec<_>>();
+                assert!(id.is_of_index(&index), "{id} at depth {depth}");
+            }
+        }
+
+        let id = "sodium".parse::<Id>().unwrap();
+        assert!(!id.is_of_index(&["so", "xx"]));
+    }
+}
@@ -245,8 +245,11 @@ impl IndexCache {
     }
 }
 
-impl Creeper {
-    pub fn blocking_get_reachable_package(
+/// Where package indices are looked up during dependency resolution, i.e. [`Creeper`] and its registries.
+pub trait IndexSource {
+    fn blocking_get_index(&self, package: &Id) -> anyhow::Result<Index>;
+
+    fn blocking_get_reachable_package(
         &self,
         origin: impl IntoIterator<Item = Id>,
     ) -> anyhow::Result<HashSet<Id>> {
@@ -270,7 +273,15 @@ impl Creeper {
 
         Ok(found.into_keys().collect())
     }
+}
 
+impl IndexSource for Creeper {
+    fn blocking_get_index(&self, package: &Id) -> anyhow::Result<Index> {
+        Creeper::blocking_get_index(self, package)
+    }
+}
+
+impl Creeper {
     pub async fn get_index(&self, package: &Id) -> anyhow::Result<Index> {
         if let Some(index) = self.index_cache.map.read().unwrap().get(package) {
             return Ok(index.clone());
@@ -1,15 +1,16 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    iter::once,
     path::PathBuf,
 };
 
 use anyhow::bail;
 use futures::{StreamExt, TryStreamExt, stream};
 use semver::{Version, VersionReq};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de::Error};
 use serde_inline_default::serde_inline_default;
 use tokio::fs::{create_dir_all, read_to_string, remove_file, try_exists, write};
-use tracing::{Span, debug, instrument};
+use tracing::{Span, debug, info, instrument, warn};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 
 use crate::{
@@ -71,19 +72,51 @@ pub struct Install {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub mc_flag: Vec<String>,
 
+    /// Like [`Self::mc_flag`], but only passed when the launch features match, e.g. `--demo`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mc_flag_feature: Vec<FeatureFlag>,
+
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub mc_asset: HashMap<PathBuf, Artifact>,
 
+    /// Whether assets are additionally laid out by their logical path under `virtual/`, as required by old versions.
+    #[serde_inline_default(false)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub mc_asset_virtual: bool,
+
+    /// Whether assets are additionally laid out by their logical path under `resources/` of the game directory.
+    #[serde_inline_default(false)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub mc_asset_map_to_resources: bool,
+
     /// Minecraft mod files to be added to the `mods` folder.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub mc_mod: Vec<Artifact>,
 
+    /// Like [`Self::mc_mod`], but only added on clients, e.g. minimaps.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mc_mod_client: Vec<Artifact>,
+
+    /// Like [`Self::mc_mod`], but only added on dedicated servers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mc_mod_server: Vec<Artifact>,
+
+    /// Whether this installs a dedicated server instead of a client,
+    /// which decides between [`Self::mc_mod_client`] and [`Self::mc_mod_server`].
+    #[serde_inline_default(false)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub server: bool,
+
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub resource_pack: Vec<Artifact>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub shader_pack: Vec<Artifact>,
 
+    /// Archives to be expanded into the game directory, e.g. config bundles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archive: Vec<Archive>,
+
     #[serde_inline_default(false)]
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub user: bool,
@@ -91,6 +124,10 @@ pub struct Install {
     #[serde_inline_default(VersionReq::STAR)]
     #[serde(skip_serializing_if = "is_star")]
     pub require_java: VersionReq,
+
+    /// Name of the Mojang java runtime suitable for the game, e.g. `java-runtime-gamma`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub java_runtime: Option<String>,
 }
 
 fn is_star(v: &VersionReq) -> bool {
@@ -110,12 +147,20 @@ impl Default for Install {
             mc_jar: None,
             disable_mc_jar: false,
             mc_flag: vec![],
+            mc_flag_feature: vec![],
             mc_asset: HashMap::new(),
+            mc_asset_virtual: false,
+            mc_asset_map_to_resources: false,
             mc_mod: vec![],
+            mc_mod_client: vec![],
+            mc_mod_server: vec![],
+            server: false,
             resource_pack: vec![],
             shader_pack: vec![],
+            archive: vec![],
             user: false,
             require_java: VersionReq::STAR,
+            java_runtime: None,
         }
     }
 }
@@ -136,6 +181,67 @@ impl Install {
     pub fn is_empty(&self) -> bool {
         self == &Self::default()
     }
+
+    /// Mod files to be added on the side of this installation, see [`Self::server`].
+    pub fn side_mod(&self) -> impl Iterator<Item = &Artifact> {
+        let side = if self.server {
+            &self.mc_mod_server
+        } else {
+            &self.mc_mod_client
+        };
+        self.mc_mod.iter().chain(side)
+    }
+
+    /// Iterate over all mod files regardless of side.
+    pub fn mods(&self) -> impl Iterator<Item = &Artifact> {
+        self.mc_mod
+            .iter()
+            .chain(self.mc_mod_client.iter())
+            .chain(self.mc_mod_server.iter())
+    }
+
+    /// Retain only the mod files, regardless of side, for which `keep` returns `true`.
+    pub fn retain_mod(&mut self, keep: impl Fn(&Artifact) -> bool) {
+        self.mc_mod.retain(&keep);
+        self.mc_mod_client.retain(&keep);
+        self.mc_mod_server.retain(&keep);
+    }
+
+    /// Iterate over all artifacts referenced by this installation.
+    pub fn artifacts(&self) -> impl Iterator<Item = &Artifact> {
+        self.java_lib_class
+            .values()
+            .chain(self.java_lib_mod.values())
+            .chain(self.java_lib_file.values())
+            .chain(self.java_agent.iter().map(|agent| &agent.file))
+            .chain(self.native.values())
+            .chain(self.mc_jar.iter())
+            .chain(self.mc_asset.values())
+            .chain(self.mc_mod.iter())
+            .chain(self.mc_mod_client.iter())
+            .chain(self.mc_mod_server.iter())
+            .chain(self.resource_pack.iter())
+            .chain(self.shader_pack.iter())
+            .chain(self.archive.iter().map(|archive| &archive.file))
+    }
+
+    /// Like [`Self::artifacts`], but mutable.
+    pub fn artifacts_mut(&mut self) -> impl Iterator<Item = &mut Artifact> {
+        self.java_lib_class
+            .values_mut()
+            .chain(self.java_lib_mod.values_mut())
+            .chain(self.java_lib_file.values_mut())
+            .chain(self.java_agent.iter_mut().map(|agent| &mut agent.file))
+            .chain(self.native.values_mut())
+            .chain(self.mc_jar.iter_mut())
+            .chain(self.mc_asset.values_mut())
+            .chain(self.mc_mod.iter_mut())
+            .chain(self.mc_mod_client.iter_mut())
+            .chain(self.mc_mod_server.iter_mut())
+            .chain(self.resource_pack.iter_mut())
+            .chain(self.shader_pack.iter_mut())
+            .chain(self.archive.iter_mut().map(|archive| &mut archive.file))
+    }
 }
 
 impl Extend<Self> for Install {
@@ -152,12 +258,20 @@ impl Extend<Self> for Install {
                 mc_jar,
                 disable_mc_jar,
                 mc_flag,
+                mc_flag_feature,
                 mc_asset,
+                mc_asset_virtual,
+                mc_asset_map_to_resources,
                 mc_mod,
+                mc_mod_client,
+                mc_mod_server,
+                server,
                 resource_pack,
                 shader_pack,
+                archive,
                 user,
                 require_java,
+                java_runtime,
             } = next;
             self.java_lib_class.extend(java_lib_class);
             self.java_lib_mod.extend(java_lib_mod);
@@ -169,14 +283,23 @@ impl Extend<Self> for Install {
             self.mc_jar = mc_jar.or(self.mc_jar.take());
             self.disable_mc_jar = self.disable_mc_jar || disable_mc_jar;
             self.mc_flag.extend(mc_flag);
+            self.mc_flag_feature.extend(mc_flag_feature);
             self.mc_asset.extend(mc_asset);
+            self.mc_asset_virtual = self.mc_asset_virtual || mc_asset_virtual;
+            self.mc_asset_map_to_resources =
+                self.mc_asset_map_to_resources || mc_asset_map_to_resources;
             self.mc_mod.extend(mc_mod);
+            self.mc_mod_client.extend(mc_mod_client);
+            self.mc_mod_server.extend(mc_mod_server);
+            self.server = self.server || server;
             self.resource_pack.extend(resource_pack);
             self.shader_pack.extend(shader_pack);
+            self.archive.extend(archive);
             self.user = self.user || user;
             self.require_java
                 .comparators
                 .extend(require_java.comparators);
+            self.java_runtime = java_runtime.or(self.java_runtime.take());
         }
 
         self.simplify();
@@ -278,10 +401,19 @@ impl Creeper {
 
     /// Install all specified packages in the input.
     /// Automatically merging them with the latter overriding the former.
-    #[instrument(skip(self, packages))]
     pub async fn install_all(
         &self,
         packages: impl IntoIterator<Item = (Id, VersionRev)>,
+    ) -> anyhow::Result<Install> {
+        self.install_all_enabled(packages, &BTreeSet::new()).await
+    }
+
+    /// Like [`Self::install_all`], but without the mods of the packages in `disabled`.
+    #[instrument(skip(self, packages, disabled))]
+    pub async fn install_all_enabled(
+        &self,
+        packages: impl IntoIterator<Item = (Id, VersionRev)>,
+        disabled: &BTreeSet<Id>,
     ) -> anyhow::Result<Install> {
         let packages = packages.into_iter().collect::<Vec<_>>();
 
@@ -295,18 +427,46 @@ impl Creeper {
                 let result = self
                     .install(&id, &version.version, version.rev)
                     .await
-                    .map(|x| (idx, x));
+                    .map(|x| (idx, (id, x)));
 
                 let span = Span::current();
                 span.pb_inc(1);
 
                 result
             })
-            .buffer_unordered(self.config.parallel_download)
+            .buffer_unordered(self.config.parallel_download())
             .try_collect::<BTreeMap<_, _>>()
             .await?;
 
-        let install = map.into_values().collect();
+        let install = merge_enabled(map.into_values(), disabled);
+
+        Ok(install)
+    }
+
+    /// The complete installation of the current game instance, i.e. `package` merged over its resolved dependencies `dep`.
+    ///
+    /// Mods of the dependencies disabled in the [instance state](crate::game::InstanceState) are excluded.
+    pub async fn game_install(
+        &self,
+        package: &Package,
+        dep: HashMap<Id, VersionRev>,
+    ) -> anyhow::Result<Install> {
+        let disabled = self.game.state().await?.disabled;
+
+        for id in &disabled {
+            if !dep.contains_key(id) {
+                warn!("disabled package {id} is not a dependency");
+            }
+        }
+
+        if !disabled.is_empty() {
+            info!("excluding mods of {} disabled packages", disabled.len());
+        }
+
+        let sorted = self.sort_dependency(dep)?;
+
+        let mut install = self.install_all_enabled(sorted, &disabled).await?;
+        install.extend(once(package.install.clone()));
 
         Ok(install)
     }
@@ -324,6 +484,24 @@ impl Creeper {
     }
 }
 
+/// Merge the installations of packages in order, without the mods of those in `disabled`.
+///
+/// Mods are excluded by package rather than by file, so a mod also shipped by an enabled package is kept.
+fn merge_enabled(
+    install: impl IntoIterator<Item = (Id, Install)>,
+    disabled: &BTreeSet<Id>,
+) -> Install {
+    install
+        .into_iter()
+        .map(|(id, mut install)| {
+            if disabled.contains(&id) {
+                install.retain_mod(|_| false);
+            }
+            install
+        })
+        .collect()
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct JavaAgent {
@@ -333,6 +511,45 @@ pub struct JavaAgent {
     pub option: Option<String>,
 }
 
+/// Command line options gated on launcher features, see [`Install::mc_flag_feature`].
+///
+/// Values may contain placeholders like `${resolution_width}`, substituted when launching.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FeatureFlag {
+    /// Whether each feature has to be enabled, e.g. `is_demo_user = true`.
+    pub feature: BTreeMap<String, bool>,
+
+    pub flag: Vec<String>,
+}
+
+/// An archive artifact and where to expand it.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Archive {
+    /// The archive file, currently only `.zip` is supported.
+    #[serde(deserialize_with = "deserialize_zip")]
+    pub file: Artifact,
+
+    /// Directory to expand into, relative to the game directory.
+    #[serde(default)]
+    pub target: PathBuf,
+}
+
+/// Deserialize an archive file, rejecting formats other than `.zip`,
+/// so that a package using them fails to parse instead of failing at launch.
+fn deserialize_zip<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Artifact, D::Error> {
+    let file = Artifact::deserialize(deserializer)?;
+
+    if !file.name.ends_with(".zip") {
+        return Err(D::Error::custom(format!(
+            "unsupported archive format of {file}, only .zip is supported"
+        )));
+    }
+
+    Ok(file)
+}
+
 impl FromIterator<Self> for Install {
     fn from_iter<T: IntoIterator<Item = Self>>(iter: T) -> Self {
         let mut install = Self::default();
@@ -342,3 +559,86 @@ impl FromIterator<Self> for Install {
         install
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(name: &str) -> String {
+        format!(
+            r#"
+            [[archive]]
+            target = "config"
+            file = {{ blake3 = "{}", name = "{name}", len = 0 }}
+            "#,
+            "0".repeat(64)
+        )
+    }
+
+    #[test]
+    fn archive_format() {
+        let install = toml::from_str::<Install>(&archive("bundle.zip")).unwrap();
+        assert_eq!(install.archive[0].target, PathBuf::from("config"));
+
+        assert!(toml::from_str::<Install>(&archive("bundle.tar.gz")).is_err());
+    }
+
+    fn mc_mod(name: &str) -> Artifact {
+        Artifact::new("0".repeat(64), name.into(), None, 0)
+    }
+
+    #[test]
+    fn side_mod_server() {
+        let install = Install {
+            mc_mod: vec![mc_mod("common.jar")],
+            mc_mod_client: vec![mc_mod("minimap.jar")],
+            mc_mod_server: vec![mc_mod("backup.jar")],
+            server: true,
+            ..Default::default()
+        };
+
+        let names = install
+            .side_mod()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["common.jar", "backup.jar"]);
+
+        let install = Install {
+            server: false,
+            ..install
+        };
+
+        let names = install
+            .side_mod()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["common.jar", "minimap.jar"]);
+    }
+
+    #[test]
+    fn disabled_mods_excluded() {
+        let id = |s: &str| s.parse::<Id>().unwrap();
+        let install = |name: &str| Install {
+            mc_mod: vec![mc_mod(name)],
+            native: HashMap::from([(PathBuf::from(format!("{name}.so")), mc_mod(name))]),
+            ..Default::default()
+        };
+
+        // all mods share the same content, so only the package tells them apart
+        let merged = merge_enabled(
+            [
+                (id("fabric-api"), install("fabric-api.jar")),
+                (id("sodium"), install("sodium.jar")),
+            ],
+            &BTreeSet::from([id("sodium")]),
+        );
+
+        let names = merged.mods().map(|a| a.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["fabric-api.jar"]);
+        // everything else of a disabled package is still installed
+        assert_eq!(merged.native.len(), 2);
+
+        let merged = merge_enabled([(id("sodium"), install("sodium.jar"))], &BTreeSet::new());
+        assert_eq!(merged.mods().count(), 1);
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, ensure};
+use inquire::Select;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use serde_with::{NoneAsEmptyString, serde_as};
+use tokio::{process::Command, sync::Mutex, task::spawn_blocking};
+use tracing::{debug, info};
+
+use crate::{Creeper, path::creeper_config_dir, util::TomlFile};
+
+mod runtime;
+
+pub struct JavaManager {
+    pub config: TomlFile<JavaConfig>,
+
+    /// Versions reported by java executables, so that each is only spawned once.
+    probe: Mutex<HashMap<PathBuf, Version>>,
+}
+
+impl JavaManager {
+    pub fn new() -> Self {
+        Self {
+            config: TomlFile::new(),
+            probe: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The version of the java executable `bin`, probed once and cached afterwards.
+    pub async fn probe(&self, bin: &Path) -> anyhow::Result<Version> {
+        let mut probe = self.probe.lock().await;
+
+        if let Some(version) = probe.get(bin) {
+            return Ok(version.clone());
+        }
+
+        let version = get_java_version(bin).await?;
+        probe.insert(bin.to_owned(), version.clone());
+
+        Ok(version)
+    }
+}
+
+/// The requirement of a `javaVersion.majorVersion` in version metadata.
+///
+/// Java 8 and earlier are versioned like `1.8.0`.
+pub fn major_java_req(major: u8) -> VersionReq {
+    let req = match major {
+        ..=8 => format!(">=1.{major}.0"),
+        _ => format!(">={major}"),
+    };
+
+    req.parse().unwrap()
+}
+
+fn config_path() -> anyhow::Result<PathBuf> {
+    let path = creeper_config_dir()?.join("java.toml");
+
+    Ok(path)
+}
+
+impl Creeper {
+    /// Select a Java runtime matching `req`, prompting if several are configured.
+    ///
+    /// If [`JavaConfig::auto`] is set, the Mojang java runtime `component` is installed and used instead.
+    pub async fn prompt_select_java(
+        &self,
+        req: &VersionReq,
+        component: Option<&str>,
+    ) -> anyhow::Result<Java> {
+        let path = config_path()?;
+
+        let config = self.java.config.read(&path).await?.unwrap_or_default();
+
+        if config.auto
+            && let Some(component) = component
+        {
+            let path = self.install_java_runtime(component).await?;
+            let version = self.java.probe(&path).await?;
+
+            let java = Java {
+                name: Some(component.into()),
+                version,
+                path,
+            };
+
+            debug!("using mojang java runtime {java}");
+
+            return Ok(java);
+        }
+
+        let all = [self.path_java().await?]
+            .into_iter()
+            .chain(config.java)
+            .filter(|v| req.matches(&v.version))
+            .collect::<Vec<_>>();
+
+        ensure!(!all.is_empty(), "no configured Java runtime {req}");
+
+        if all.len() == 1 {
+            debug!("using the only java runtime matching {req}: {}", all[0]);
+
+            return Ok(all.into_iter().next().unwrap());
+        }
+
+        let select = spawn_blocking(|| {
+            Select::new("Select a Java runtime:", all)
+                .with_starting_cursor(0)
+                .prompt()
+        })
+        .await??;
+
+        Ok(select)
+    }
+
+    /// The java executable on `$PATH`.
+    pub async fn path_java(&self) -> anyhow::Result<Java> {
+        let path = PathBuf::from("java");
+
+        let version = self.java.probe(&path).await?;
+
+        let value = Java {
+            name: Some("$PATH".into()),
+            version,
+            path,
+        };
+
+        Ok(value)
+    }
+
+    /// Ensure `java` satisfies `req`, e.g. that of the game, updating its version to the one it reports now,
+    /// which changes with routine updates of the JDK.
+    pub async fn check_java(&self, java: &mut Java, req: &VersionReq) -> anyhow::Result<()> {
+        let actual = self.java.probe(&java.path).await?;
+
+        if actual != java.version {
+            info!(
+                "{} now reports version {actual}, updating from {}",
+                java.path.display(),
+                java.version
+            );
+            java.version = actual.clone();
+        }
+
+        if !req.matches(&actual) {
+            bail!(
+                "Java {actual} at {} does not satisfy {req} required by the game, \
+                configure a suitable Java runtime in {} and remove `.creeper/java.json` to select again",
+                java.path.display(),
+                config_path()?.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct JavaConfig {
+    /// Whether to install and use the Mojang java runtime of the game, instead of selecting a configured one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub java: Vec<Java>,
+}
+
+#[serde_as]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Java {
+    #[serde_as(as = "NoneAsEmptyString")]
+    pub name: Option<String>,
+
+    pub version: Version,
+
+    pub path: PathBuf,
+}
+
+impl Display for Java {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "({}) {name}", self.version)
+        } else {
+            write!(f, "({}) {}", self.version, self.path.display())
+        }
+    }
+}
+
+/// Parse the version in the output of `java -version`, e.g. `openjdk version "17.0.2" 2022-01-18`.
+///
+/// Versions are normalized to semver, e.g. `1.8.0_392` to `1.8.0` and `21` to `21.0.0`.
+fn parse_java_version(output: &str) -> anyhow::Result<Version> {
+    let line = output
+        .lines()
+        .find(|line| line.contains("version"))
+        .ok_or(anyhow!("invalid java -version output: {output}"))?;
+
+    let quoted = line
+        .split('"')
+        .nth(1)
+        .ok_or(anyhow!("invalid java -version output: {line}"))?;
+
+    let (number, pre) = match quoted.split_once('-') {
+        Some((number, pre)) => (number, Some(pre)),
+        None => (quoted, None),
+    };
+
+    let number = number.split(['_', '+']).next().unwrap_or(number);
+
+    let mut part = number.split('.').collect::<Vec<_>>();
+    part.resize(part.len().max(3), "0");
+
+    let mut version = part[..3].join(".");
+    if let Some(pre) = pre {
+        version = format!("{version}-{pre}");
+    }
+
+    let version = version
+        .parse()
+        .map_err(|e| anyhow!("invalid java version {quoted}: {e}"))?;
+
+    Ok(version)
+}
+
+async fn get_java_version(bin: impl AsRef<Path>) -> anyhow::Result<Version> {
+    let bin = bin.as_ref();
+
+    // `--version` is not supported before Java 9, and `-version` prints to stderr
+    let output = Command::new(bin)
+        .arg("-version")
+        .output()
+        .await
+        .map_err(|e| anyhow!("failed to run {}: {e}", bin.display()))?;
+
+    let version = parse_java_version(&String::from_utf8_lossy(&output.stderr))?;
+
+    debug!("{} reports java version {version}", bin.display());
+
+    Ok(version)
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env::consts,
+    iter::once,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{copy, read_to_string, remove_dir_all, try_exists, write};
+use tracing::{debug, info};
+
+use crate::{
+    Checksum, Creeper,
+    http::fetch_json,
+    path::creeper_data_dir,
+    util::{check_relative, ensure_dir, set_mode, symlink_auto},
+};
+
+/// Index of the java runtimes Mojang publishes for each platform.
+const RUNTIME_INDEX: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+/// Name of the file recording the version of an installed runtime.
+const VERSION_FILE: &str = ".creeper-runtime";
+
+/// Directory to install the Mojang java runtime `component` into.
+pub fn java_runtime_dir(component: &str) -> anyhow::Result<PathBuf> {
+    let dir = creeper_data_dir()?.join("java").join(component);
+    Ok(dir)
+}
+
+/// The platform of the current system in the runtime index.
+fn runtime_platform() -> anyhow::Result<&'static str> {
+    let platform = match (consts::OS, consts::ARCH) {
+        ("linux", "x86_64") => "linux",
+        ("linux", "x86") => "linux-i386",
+        ("macos", "x86_64") => "mac-os",
+        ("macos", "aarch64") => "mac-os-arm64",
+        ("windows", "x86_64") => "windows-x64",
+        ("windows", "x86") => "windows-x86",
+        ("windows", "aarch64") => "windows-arm64",
+        (os, arch) => bail!("no Mojang java runtime for {os} on {arch}"),
+    };
+
+    Ok(platform)
+}
+
+/// Path of the java executable relative to the runtime directory.
+fn java_bin() -> &'static Path {
+    match consts::OS {
+        "macos" => Path::new("jre.bundle/Contents/Home/bin/java"),
+        "windows" => Path::new("bin/javaw.exe"),
+        _ => Path::new("bin/java"),
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RuntimeDownload {
+    sha1: String,
+    size: u64,
+    url: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RuntimeVersion {
+    name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RuntimeEntry {
+    manifest: RuntimeDownload,
+    version: RuntimeVersion,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct FileDownloads {
+    raw: RuntimeDownload,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum RuntimeFile {
+    Directory,
+    File {
+        #[serde(default)]
+        executable: bool,
+        downloads: FileDownloads,
+    },
+    Link {
+        target: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RuntimeManifest {
+    files: BTreeMap<PathBuf, RuntimeFile>,
+}
+
+impl Creeper {
+    /// Install the Mojang java runtime `component`, e.g. `java-runtime-delta`, for the current platform,
+    /// returning the path to its java executable.
+    ///
+    /// Files are downloaded through the artifact storage and copied into [`java_runtime_dir`].
+    /// An installation of the same runtime version is reused.
+    pub async fn install_java_runtime(&self, component: &str) -> anyhow::Result<PathBuf> {
+        let dir = java_runtime_dir(component)?;
+        let version_file = dir.join(VERSION_FILE);
+
+        if self.args.offline {
+            if try_exists(&version_file).await? {
+                return Ok(dir.join(java_bin()));
+            }
+            bail!("offline mode enabled, cannot install java runtime {component}");
+        }
+
+        let platform = runtime_platform()?;
+
+        let index = fetch_json::<HashMap<String, HashMap<String, Vec<RuntimeEntry>>>>(
+            &self.http,
+            &self.fetch,
+            RUNTIME_INDEX,
+            None,
+        )
+        .await?;
+
+        let entry = index
+            .get(platform)
+            .and_then(|runtime| runtime.get(component))
+            .and_then(|entry| entry.first())
+            .ok_or(anyhow!(
+                "java runtime {component} is not available for {platform}"
+            ))?;
+
+        let version = &entry.version.name;
+
+        if try_exists(&version_file).await? && read_to_string(&version_file).await? == *version {
+            debug!("java runtime {component} {version} already installed");
+            return Ok(dir.join(java_bin()));
+        }
+
+        info!("installing java runtime {component} {version}");
+
+        let manifest = fetch_json::<RuntimeManifest>(
+            &self.http,
+            &self.fetch,
+            &entry.manifest.url,
+            Some(&entry.manifest.sha1),
+        )
+        .await?;
+
+        if try_exists(&dir).await? {
+            remove_dir_all(&dir).await?;
+        }
+        ensure_dir(&dir).await?;
+
+        let mut executable = HashMap::new();
+        let mut download = HashMap::new();
+
+        for (path, file) in &manifest.files {
+            check_relative(path)?;
+
+            match file {
+                RuntimeFile::Directory => ensure_dir(dir.join(path)).await?,
+                RuntimeFile::File {
+                    executable: exec,
+                    downloads,
+                } => {
+                    executable.insert(path.clone(), *exec);
+                    download.insert(
+                        path.clone(),
+                        (
+                            path.display().to_string(),
+                            downloads.raw.url.clone(),
+                            Some(downloads.raw.size),
+                            once(Checksum::sha1(downloads.raw.sha1.clone())),
+                        ),
+                    );
+                }
+                RuntimeFile::Link { .. } => {}
+            }
+        }
+
+        let art = self.batch_download(download).await?;
+
+        stream::iter(art)
+            .map(|(path, art)| {
+                let dst = dir.join(&path);
+                let exec = executable[&path];
+                async move {
+                    let src = self.retrieve_artifact(&art).await?;
+                    ensure_dir(dst.parent().unwrap()).await?;
+                    // copied rather than deployed, since the permissions differ from storage
+                    copy(&src, &dst).await?;
+                    set_mode(&dst, if exec { 0o755 } else { 0o644 }).await?;
+                    anyhow::Ok(())
+                }
+            })
+            .buffer_unordered(self.config.parallel_download())
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        for (path, file) in &manifest.files {
+            if let RuntimeFile::Link { target } = file {
+                let link = dir.join(path);
+                ensure_dir(link.parent().unwrap()).await?;
+                symlink_auto(target, &link).await?;
+            }
+        }
+
+        write(&version_file, version).await?;
+
+        let java = dir.join(java_bin());
+
+        debug!("installed java runtime {component} at {}", java.display());
+
+        Ok(java)
+    }
+}
@@ -1,57 +1,202 @@
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    process::ExitStatus,
 };
 
-use anyhow::{anyhow, bail, ensure};
+use anyhow::bail;
+use futures::{StreamExt, TryStreamExt, stream};
 use semver::VersionReq;
 use tokio::{
-    fs::{create_dir_all, read_link, read_to_string, remove_dir_all, try_exists, write},
-    process::Command,
+    fs::{
+        create_dir_all, read_link, read_to_string, remove_dir_all, remove_file, try_exists, write,
+    },
+    process::{Child, Command},
 };
 
-use crate::{Artifact, AssetIndex, Creeper, Install, java::Java, symlink_auto};
+use tracing::{debug, warn};
+
+use crate::{
+    Artifact, Creeper, Install,
+    install::FeatureFlag,
+    java::Java,
+    log4j::{McLogLevel, log4j_config},
+    symlink_auto,
+    usage::{locked_mc_version, record_version_use},
+    util::{check_relative, summarize},
+    vanilla::RuleChecker,
+    zip::extract_zip_all_once,
+};
+
+/// Options affecting how a game instance is launched, in addition to its `install.json`.
+#[derive(Clone, Default)]
+pub struct LaunchOption {
+    /// Installation merged on top of the instance's own, e.g. for additional JVM flags.
+    pub extra: Install,
+
+    /// Override of [`crate::Config::natives_dir`].
+    pub natives_dir: Option<PathBuf>,
+
+    /// Log in as this user instead of the default one, see [`Creeper::find_user`].
+    pub user: Option<String>,
+
+    /// Launcher features enabled or disabled, e.g. `is_demo_user`, see [`Install::mc_flag_feature`].
+    pub feature: HashMap<String, bool>,
+
+    /// Values of placeholders in feature-gated flags, e.g. `resolution_width`.
+    pub var: HashMap<String, String>,
+
+    /// Level to log at, replacing the log4j configuration of the game if specified.
+    pub mc_log_level: Option<McLogLevel>,
+}
 
 impl Creeper {
-    async fn decide_java(&self, req: &VersionReq) -> anyhow::Result<Java> {
+    async fn decide_java(&self, req: &VersionReq, component: Option<&str>) -> anyhow::Result<Java> {
         let path = self.game_env_dir().await?.join("java.json");
 
-        if try_exists(&path).await? {
+        let (mut java, cached) = if try_exists(&path).await? {
             let json = read_to_string(&path).await?;
-            Ok(serde_json::from_str(&json)?)
+            (serde_json::from_str::<Java>(&json)?, true)
         } else {
-            let java = self.prompt_select_java(req).await?;
+            (self.prompt_select_java(req, component).await?, false)
+        };
+
+        let version = java.version.clone();
+
+        self.check_java(&mut java, req).await?;
+
+        if !cached || java.version != version {
+            write(&path, serde_json::to_string(&java)?).await?;
+        }
+
+        Ok(java)
+    }
+
+    /// Directory the native libraries of the current game instance are deployed into.
+    pub async fn game_native_dir(&self, option: &LaunchOption) -> anyhow::Result<PathBuf> {
+        let natives_dir = option
+            .natives_dir
+            .as_deref()
+            .or(self.config.natives_dir.as_deref());
+        Ok(native_dir(natives_dir, self.game_dir().await?))
+    }
+
+    /// Remove the native libraries deployed by [`Self::launch`], which should be called after the game exits.
+    pub async fn clean_native(&self, option: &LaunchOption) -> anyhow::Result<()> {
+        clean_native_dir(&self.game_native_dir(option).await?).await
+    }
+
+    async fn game_pid_path(&self) -> anyhow::Result<PathBuf> {
+        let path = self.game_env_dir().await?.join("pid");
+        Ok(path)
+    }
+
+    /// The process of the current game instance launched earlier, if it is still running.
+    ///
+    /// A PID file left by a process no longer running is removed.
+    pub async fn running_game(&self) -> anyhow::Result<Option<u32>> {
+        running_pid(self.game_pid_path().await?).await
+    }
+
+    /// Remove the PID file written by [`Self::launch`], which should be called after the game exits.
+    pub async fn clean_pid(&self) -> anyhow::Result<()> {
+        let path = self.game_pid_path().await?;
 
-            if !java.check_version().await? {
-                bail!("invalid Java configuration {java}: version mismatch");
-            }
+        if try_exists(&path).await? {
+            remove_file(&path).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Deploy the current game instance and spawn the game process.
+    ///
+    /// Unlike `creeper launch`, this neither prints nor waits, leaving the lifecycle of the process to the caller.
+    /// The PID of the process is recorded in the instance, so that it is not launched twice at the same time,
+    /// which would corrupt worlds.
+    pub async fn launch(&self, option: LaunchOption) -> anyhow::Result<Child> {
+        if let Some(pid) = self.running_game().await? {
+            bail!("the game instance is already running with PID {pid}");
+        }
 
-            let json = serde_json::to_string(&java)?;
+        let mut cmd = self.launch_command(option).await?;
 
-            write(&path, json).await?;
+        let child = spawn_recorded(&mut cmd, self.game_pid_path().await?).await?;
 
-            Ok(java)
+        if let Some(version) = self.game.lock().await?.as_ref().and_then(locked_mc_version)
+            && let Err(e) = record_version_use(&version).await
+        {
+            warn!("failed to record use of minecraft {version}: {e}");
         }
+
+        Ok(child)
+    }
+
+    /// Like [`Self::launch`], but wait for the game to exit and clean up after it.
+    pub async fn launch_wait(&self, option: LaunchOption) -> anyhow::Result<ExitStatus> {
+        let mut proc = self.launch(option.clone()).await?;
+
+        let status = proc.wait().await?;
+
+        self.clean_pid().await?;
+        self.clean_native(&option).await?;
+
+        Ok(status)
     }
 
-    pub async fn launch(&self) -> anyhow::Result<Command> {
+    /// Deploy the current game instance and build the command to launch it.
+    pub async fn launch_command(&self, option: LaunchOption) -> anyhow::Result<Command> {
         let game_dir = self.game_dir().await?;
 
+        let native_dir = self.game_native_dir(&option).await?;
+
         let json = read_to_string(self.game_env_dir().await?.join("install.json")).await?;
 
         let mut install = serde_json::from_str::<Install>(&json)?;
 
+        install.extend([option.extra]);
+
         if install.user {
-            install.extend([self.user_install().await?]);
+            install.extend([self.user_install(option.user.as_deref()).await?]);
         }
 
-        let java = self.decide_java(&install.require_java).await?;
+        let mc_mod = install.side_mod().cloned().collect::<Vec<_>>();
+
+        let java = self
+            .decide_java(&install.require_java, install.java_runtime.as_deref())
+            .await?;
+
+        if try_exists(&native_dir).await? {
+            remove_dir_all(&native_dir).await?;
+        }
+        create_dir_all(&native_dir).await?;
+
+        if let Some(level) = option.mc_log_level {
+            let path = self.game_env_dir().await?.join("log4j2.xml");
+            write(&path, log4j_config(level)).await?;
+            debug!("logging at {level} with {}", path.display());
+            install
+                .java_flag
+                .push(format!("-Dlog4j.configurationFile={}", path.display()));
+        }
+
+        // the vanilla arguments point to `.creeper/native`, where the last occurrence of the property wins
+        install
+            .java_flag
+            .push(format!("-Djava.library.path={}", native_dir.display()));
+
+        // deploying links the stored files or falls back to copying,
+        // so a natives directory on another filesystem is fine
+        let mut roots = self.deploy_roots().await?;
+        roots.extend(option.natives_dir.clone());
+        self.batch_retrieve_artifact_within(install.native, &native_dir, &roots)
+            .await?;
 
         let mut cmd = Command::new(java.path);
 
         cmd.current_dir(game_dir);
 
-        for flag in install.java_flag {
+        for flag in normalize_java_flag(install.java_flag) {
             cmd.arg(flag);
         }
 
@@ -123,33 +268,18 @@ impl Creeper {
 
         if !install.mc_asset.is_empty() {
             let asset_path = game_dir.join(".creeper").join("asset");
-            create_dir_all(&asset_path).await?;
 
-            fn sha1_indexed_path(sha1: &str) -> anyhow::Result<PathBuf> {
-                ensure!(sha1.len() == 40, "invalid sha1 length");
-                let first2 = &sha1[0..2];
-                let path = PathBuf::from(".").join(first2).join(sha1);
-                Ok(path)
-            }
-
-            let mut assets = HashMap::new();
-
-            for (_, art) in &install.mc_asset {
-                let sha1 = art.sha1.as_ref().ok_or(anyhow!("missing SHA-1 checksum"))?;
-                assets.insert(sha1_indexed_path(sha1)?, art.clone());
-            }
-
-            self.batch_retrieve_artifact_to(assets, asset_path.join("objects"))
+            let assets_dir = self
+                .deploy_asset(
+                    install.mc_asset,
+                    install.mc_asset_virtual,
+                    install.mc_asset_map_to_resources,
+                    &asset_path,
+                    "index",
+                )
                 .await?;
 
-            let asset_index = AssetIndex::from_map(install.mc_asset)?;
-
-            let json = serde_json::to_string(&asset_index)?;
-            let path = asset_path.join("indexes").join("index.json");
-            create_dir_all(path.parent().unwrap()).await?;
-            write(path, json).await?;
-
-            cmd.arg("--assetsDir").arg(asset_path);
+            cmd.arg("--assetsDir").arg(assets_dir);
             cmd.arg("--assetIndex").arg("index");
         }
 
@@ -157,13 +287,17 @@ impl Creeper {
             cmd.arg(flag);
         }
 
+        for flag in feature_flag(install.mc_flag_feature, &option.feature, &option.var) {
+            cmd.arg(flag);
+        }
+
         let mod_dir = game_dir.join(".creeper").join("mod");
 
         if try_exists(&mod_dir).await? {
             remove_dir_all(&mod_dir).await?;
         }
 
-        self.retrieve_ordered(&mod_dir, &install.mc_mod, Some("jar"))
+        self.retrieve_ordered(&mod_dir, &mc_mod, Some("jar"))
             .await?;
 
         try_symlink(
@@ -202,9 +336,43 @@ impl Creeper {
         )
         .await?;
 
+        for archive in &install.archive {
+            check_relative(&archive.target)?;
+        }
+
+        stream::iter(install.archive)
+            .map(|archive| async move { self.deploy_archive(&archive.file, &archive.target).await })
+            .buffer_unordered(self.config.parallel_extract())
+            .try_collect::<Vec<_>>()
+            .await?;
+
         Ok(cmd)
     }
 
+    /// Expand an archive artifact into `target` under the game directory, see [`crate::install::Archive`].
+    ///
+    /// Archives already expanded are skipped, as recorded under `.creeper/archive`.
+    async fn deploy_archive(&self, art: &Artifact, target: impl AsRef<Path>) -> anyhow::Result<()> {
+        let target = target.as_ref();
+
+        let dst = self.game_dir().await?.join(target);
+        let stamp = self
+            .game_env_dir()
+            .await?
+            .join("archive")
+            .join(summarize(&format!("{}!{}", target.display(), art.name)));
+
+        let path = self.retrieve_artifact(art).await?;
+
+        if extract_zip_all_once(path, &dst, stamp, &art.blake3).await? {
+            debug!("expanded {art} into {}", dst.display());
+        } else {
+            debug!("{art} already expanded into {}", dst.display());
+        }
+
+        Ok(())
+    }
+
     async fn retrieve_ordered(
         &self,
         dir: impl AsRef<Path>,
@@ -233,6 +401,122 @@ impl Creeper {
     }
 }
 
+/// Remove overridden JVM flags, where the last occurrence wins.
+///
+/// This applies to system properties `-D<key>=<value>` and memory flags such as `-Xmx`,
+/// all other flags are kept in order.
+fn normalize_java_flag(flag: Vec<String>) -> Vec<String> {
+    fn key(flag: &str) -> Option<&str> {
+        if let Some(prop) = flag.strip_prefix("-D") {
+            let len = prop.find('=').unwrap_or(prop.len());
+            return Some(&flag[..2 + len]);
+        }
+
+        ["-Xmx", "-Xms", "-Xmn", "-Xss"]
+            .into_iter()
+            .find(|prefix| flag.starts_with(prefix))
+    }
+
+    let mut last = HashMap::new();
+
+    for (idx, flag) in flag.iter().enumerate() {
+        if let Some(key) = key(flag) {
+            last.insert(key.to_owned(), idx);
+        }
+    }
+
+    flag.iter()
+        .enumerate()
+        .filter(|(idx, flag)| key(flag).is_none_or(|key| last[key] == *idx))
+        .map(|(_, flag)| flag.clone())
+        .collect()
+}
+
+/// Game arguments of `flag` gated on the enabled `feature`, with placeholders substituted from `var`.
+fn feature_flag(
+    flag: Vec<FeatureFlag>,
+    feature: &HashMap<String, bool>,
+    var: &HashMap<String, String>,
+) -> Vec<String> {
+    let rule = RuleChecker::with_feature(feature.clone());
+
+    flag.into_iter()
+        .filter(|flag| rule.check_feature(&flag.feature))
+        .flat_map(|flag| flag.flag)
+        .map(|flag| shellexpand::env_with_context_no_errors(&flag, |k| var.get(k)).to_string())
+        .collect()
+}
+
+/// Directory the native libraries of the game instance at `game_dir` are deployed into,
+/// a subdirectory of `natives_dir` if given, so that instances sharing it do not clash.
+fn native_dir(natives_dir: Option<&Path>, game_dir: &Path) -> PathBuf {
+    match natives_dir {
+        Some(dir) => dir.join(summarize(&game_dir.display().to_string())),
+        None => game_dir.join(".creeper").join("native"),
+    }
+}
+
+/// Remove the native libraries deployed into `dir`.
+async fn clean_native_dir(dir: &Path) -> anyhow::Result<()> {
+    if try_exists(dir).await? {
+        debug!("removing native libraries under {}", dir.display());
+        remove_dir_all(dir).await?;
+    }
+
+    Ok(())
+}
+
+/// Spawn `cmd` without waiting for it, recording its PID in the file at `path`, see [`running_pid`].
+async fn spawn_recorded(cmd: &mut Command, path: impl AsRef<Path>) -> anyhow::Result<Child> {
+    let child = cmd.spawn()?;
+
+    if let Some(pid) = child.id() {
+        write(path, pid.to_string()).await?;
+    }
+
+    Ok(child)
+}
+
+/// The process recorded in the PID file at `path`, if it is still running, removing the file otherwise.
+async fn running_pid(path: impl AsRef<Path>) -> anyhow::Result<Option<u32>> {
+    let path = path.as_ref();
+
+    if !try_exists(path).await? {
+        return Ok(None);
+    }
+
+    let pid = read_to_string(path).await?.trim().parse::<u32>().ok();
+
+    match pid {
+        Some(pid) if is_alive(pid) => Ok(Some(pid)),
+        _ => {
+            debug!("removing stale PID file {}", path.display());
+            remove_file(path).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Whether a process with `pid` exists, which may be another program if the PID has been reused.
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+
+    // SAFETY: signal 0 only checks for the existence of the process
+    let ret = unsafe { libc::kill(pid, 0) };
+
+    // the process may exist but belong to another user
+    ret == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    // cannot check without platform APIs, so never block launching
+    false
+}
+
 async fn try_symlink(original: impl AsRef<Path>, link: impl AsRef<Path>) -> anyhow::Result<()> {
     let original = original.as_ref();
     let link = link.as_ref();
@@ -257,3 +541,147 @@ async fn try_symlink(original: impl AsRef<Path>, link: impl AsRef<Path>) -> anyh
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn java_flag_last_wins() {
+        let flag = [
+            "-Dfoo=1",
+            "-Xmx2G",
+            "--add-opens",
+            "java.base/java.lang=ALL-UNNAMED",
+            "-Dbar",
+            "-Dfoo=2",
+            "--add-opens",
+            "java.base/java.util=ALL-UNNAMED",
+            "-Xmx4G",
+            "-Dfoo.baz=3",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        assert_eq!(
+            normalize_java_flag(flag),
+            [
+                "--add-opens",
+                "java.base/java.lang=ALL-UNNAMED",
+                "-Dbar",
+                "-Dfoo=2",
+                "--add-opens",
+                "java.base/java.util=ALL-UNNAMED",
+                "-Xmx4G",
+                "-Dfoo.baz=3",
+            ]
+        );
+    }
+
+    fn resolution() -> Vec<FeatureFlag> {
+        vec![FeatureFlag {
+            feature: [("has_custom_resolution".into(), true)].into(),
+            flag: [
+                "--width",
+                "${resolution_width}",
+                "--height",
+                "${resolution_height}",
+            ]
+            .map(String::from)
+            .into(),
+        }]
+    }
+
+    #[test]
+    fn feature_flag_enabled() {
+        let feature = [("has_custom_resolution".into(), true)].into();
+        let var = [
+            ("resolution_width".into(), "1280".into()),
+            ("resolution_height".into(), "720".into()),
+        ]
+        .into();
+
+        assert_eq!(
+            feature_flag(resolution(), &feature, &var),
+            ["--width", "1280", "--height", "720"]
+        );
+    }
+
+    #[test]
+    fn feature_flag_disabled() {
+        assert!(feature_flag(resolution(), &HashMap::new(), &HashMap::new()).is_empty());
+
+        let feature = [("has_custom_resolution".into(), false)].into();
+        assert!(feature_flag(resolution(), &feature, &HashMap::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn native_dir_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let game_dir = dir.path().join("game");
+        let natives_dir = dir.path().join("tmpfs");
+
+        assert_eq!(
+            native_dir(None, &game_dir),
+            game_dir.join(".creeper").join("native")
+        );
+
+        let native = native_dir(Some(&natives_dir), &game_dir);
+        assert_eq!(native.parent(), Some(natives_dir.as_path()));
+
+        create_dir_all(&native).await.unwrap();
+        write(native.join("liblwjgl.so"), "").await.unwrap();
+
+        clean_native_dir(&native).await.unwrap();
+        assert!(!native.exists());
+        assert!(natives_dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn running_pid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pid");
+
+        assert_eq!(running_pid(&path).await.unwrap(), None);
+
+        // a live process blocks launching
+        write(&path, std::process::id().to_string()).await.unwrap();
+        assert_eq!(running_pid(&path).await.unwrap(), Some(std::process::id()));
+        assert!(try_exists(&path).await.unwrap());
+
+        // the PID of an exited process is stale
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id().unwrap();
+        child.wait().await.unwrap();
+        write(&path, pid.to_string()).await.unwrap();
+        assert_eq!(running_pid(&path).await.unwrap(), None);
+        assert!(!try_exists(&path).await.unwrap());
+
+        // so is a corrupted PID file
+        write(&path, "not a pid").await.unwrap();
+        assert_eq!(running_pid(&path).await.unwrap(), None);
+        assert!(!try_exists(&path).await.unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_await_child() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pid");
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "exit 3"]);
+
+        let mut child = spawn_recorded(&mut cmd, &path).await.unwrap();
+        assert_eq!(
+            read_to_string(&path).await.unwrap(),
+            child.id().unwrap().to_string()
+        );
+
+        // the embedder controls the lifecycle of the game
+        let status = child.wait().await.unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(running_pid(&path).await.unwrap(), None);
+    }
+}
@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
 
+use colored::Colorize;
 use semver::VersionReq;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use url::Url;
 
-use crate::{Id, index::VersionRev};
+use crate::{Creeper, Id, Package, VERSION, index::VersionRev, util::TomlFile};
 
 #[serde_as]
 #[derive(Clone, Serialize, Deserialize)]
@@ -27,4 +31,187 @@ impl Lock {
         }
         true
     }
+
+    /// Compute the changes needed to turn this lock into `other`.
+    pub fn diff(&self, other: &Lock) -> LockDiff {
+        let mut diff = LockDiff::default();
+
+        if self.registry != other.registry {
+            diff.registry = Some((self.registry.clone(), other.registry.clone()));
+        }
+
+        for (id, old) in &self.package {
+            match other.package.get(id) {
+                None => {
+                    diff.removed.insert(id.clone(), old.clone());
+                }
+                Some(new) if new != old => {
+                    diff.changed.insert(id.clone(), (old.clone(), new.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (id, new) in &other.package {
+            if !self.package.contains_key(id) {
+                diff.added.insert(id.clone(), new.clone());
+            }
+        }
+
+        diff
+    }
+}
+
+/// Create a [`TomlFile`] for lock files, with the generated-file header.
+pub fn lock_file() -> TomlFile<Lock> {
+    TomlFile::with_header(&format!(
+        "This file is generated by creeper {VERSION}.\nIt is not intended for manual editing."
+    ))
+}
+
+/// Changes between two lock files, see [`Lock::diff`].
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LockDiff {
+    /// The registry packages are resolved against, if it changed, from the old to the new one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<(Url, Url)>,
+    pub added: BTreeMap<Id, VersionRev>,
+    pub removed: BTreeMap<Id, VersionRev>,
+    pub changed: BTreeMap<Id, (VersionRev, VersionRev)>,
+}
+
+impl LockDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registry.is_none()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+impl Display for LockDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((old, new)) = &self.registry {
+            writeln!(f, "{} registry {old} -> {new}", "~".bold().yellow())?;
+        }
+        for (id, version) in &self.added {
+            writeln!(f, "{} {id}@{version}", "+".bold().green())?;
+        }
+        for (id, version) in &self.removed {
+            writeln!(f, "{} {id}@{version}", "-".bold().red())?;
+        }
+        for (id, (old, new)) in &self.changed {
+            writeln!(f, "{} {id}@{old} -> {new}", "~".bold().yellow())?;
+        }
+        Ok(())
+    }
+}
+
+impl Creeper {
+    /// Resolve dependencies of a package into a fresh lock against the current registry.
+    pub fn resolve_lock(&self, package: &Package) -> anyhow::Result<Lock> {
+        let sol = self.resolve(package.node.dep.clone())?;
+
+        let lock = Lock {
+            registry: self.config.registry.clone(),
+            package: sol,
+        };
+
+        Ok(lock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(registry: &str, package: &[(&str, &str)]) -> Lock {
+        Lock {
+            registry: registry.parse().unwrap(),
+            package: package
+                .iter()
+                .map(|(id, version)| (id.parse().unwrap(), version.parse().unwrap()))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn lock_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("creeper.lock");
+
+        let lock = lock("https://a.example/", &[("sodium", "0.5.8")]);
+        lock_file().write(&path, Some(lock.clone())).await.unwrap();
+
+        let toml = std::fs::read_to_string(&path).unwrap();
+        assert!(toml.starts_with(&format!("# This file is generated by creeper {VERSION}.\n")));
+
+        let read = lock_file().read(&path).await.unwrap().unwrap();
+        assert_eq!(read.registry, lock.registry);
+        assert_eq!(read.package, lock.package);
+    }
+
+    #[test]
+    fn diff_registry() {
+        let old = lock("https://a.example/", &[("sodium", "0.5.8")]);
+
+        let new = lock("https://b.example/", &[("sodium", "0.5.8")]);
+        let diff = old.diff(&new);
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.registry,
+            Some((old.registry.clone(), new.registry.clone()))
+        );
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty());
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(
+            json["registry"],
+            serde_json::json!(["https://a.example/", "https://b.example/"])
+        );
+
+        let new = lock("https://a.example/", &[("sodium", "0.5.9")]);
+        let diff = old.diff(&new);
+        assert_eq!(diff.registry, None);
+        assert_eq!(diff.changed.len(), 1);
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn diff_classify() {
+        let old = lock(
+            "https://a.example/",
+            &[
+                ("sodium", "0.5.8"),
+                ("lithium", "0.11.2"),
+                ("fabric", "0.15.11"),
+            ],
+        );
+        let new = lock(
+            "https://a.example/",
+            &[
+                ("sodium", "0.5.9"),
+                ("iris", "1.6.4"),
+                ("fabric", "0.15.11"),
+            ],
+        );
+
+        let diff = old.diff(&new);
+
+        let version = |v: &str| v.parse::<VersionRev>().unwrap();
+        let id = |id: &str| id.parse::<Id>().unwrap();
+        assert_eq!(
+            diff.changed,
+            BTreeMap::from([(id("sodium"), (version("0.5.8"), version("0.5.9")))])
+        );
+        assert_eq!(diff.added, BTreeMap::from([(id("iris"), version("1.6.4"))]));
+        assert_eq!(
+            diff.removed,
+            BTreeMap::from([(id("lithium"), version("0.11.2"))])
+        );
+
+        let text = diff.to_string();
+        assert!(text.contains("sodium@0.5.8 -> 0.5.9"));
+        assert!(!text.contains("fabric"));
+    }
 }
@@ -0,0 +1,83 @@
+use clap::ValueEnum;
+use parse_display::Display;
+
+/// Level of the log4j loggers of the game, see [`log4j_config`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display, ValueEnum)]
+#[display(style = "UPPERCASE")]
+pub enum McLogLevel {
+    Off,
+    Fatal,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+    All,
+}
+
+/// A log4j2 configuration logging at `level`, replacing the one in the game or loader.
+///
+/// Otherwise this is the client configuration of vanilla,
+/// logging to the console and `logs/latest.log` with the usual layout.
+pub fn log4j_config(level: McLogLevel) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Configuration status="WARN">
+    <Appenders>
+        <Console name="SysOut" target="SYSTEM_OUT">
+            <PatternLayout pattern="[%d{{HH:mm:ss}}] [%t/%level]: %msg{{nolookups}}%n" />
+        </Console>
+        <RollingRandomAccessFile name="File" fileName="logs/latest.log" filePattern="logs/%d{{yyyy-MM-dd}}-%i.log.gz">
+            <PatternLayout pattern="[%d{{HH:mm:ss}}] [%t/%level]: %msg{{nolookups}}%n" />
+            <Policies>
+                <TimeBasedTriggeringPolicy />
+                <OnStartupTriggeringPolicy />
+            </Policies>
+        </RollingRandomAccessFile>
+    </Appenders>
+    <Loggers>
+        <Root level="{level}">
+            <filters>
+                <MarkerFilter marker="NETWORK_PACKETS" onMatch="DENY" onMismatch="NEUTRAL" />
+            </filters>
+            <AppenderRef ref="SysOut" />
+            <AppenderRef ref="File" />
+        </Root>
+    </Loggers>
+</Configuration>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log4j_config_level() {
+        for (level, name) in [
+            (McLogLevel::Off, "OFF"),
+            (McLogLevel::Warn, "WARN"),
+            (McLogLevel::Debug, "DEBUG"),
+            (McLogLevel::All, "ALL"),
+        ] {
+            let config = log4j_config(level);
+            assert!(config.contains(&format!("<Root level=\"{name}\">")));
+            assert_eq!(config.matches("level=").count(), 1);
+        }
+
+        // braces of the layout survive formatting, with message lookups still disabled
+        let config = log4j_config(McLogLevel::Info);
+        assert!(config.contains("[%d{HH:mm:ss}] [%t/%level]: %msg{nolookups}%n"));
+        assert!(config.contains("filePattern=\"logs/%d{yyyy-MM-dd}-%i.log.gz\""));
+    }
+
+    #[test]
+    fn log_level_value() {
+        assert_eq!(
+            McLogLevel::from_str("debug", true).unwrap(),
+            McLogLevel::Debug
+        );
+        assert!(McLogLevel::from_str("verbose", true).is_err());
+    }
+}
@@ -1,11 +1,15 @@
 mod artifact;
 mod asset;
 mod builtin;
+mod cache;
 mod checksum;
 mod cmd;
+mod deploy;
 mod dev;
 mod fabric;
+mod forge;
 mod game;
+mod http;
 mod id;
 mod index;
 mod install;
@@ -13,6 +17,7 @@ mod jar;
 mod java;
 mod launch;
 mod lock;
+mod log4j;
 mod ms;
 mod neoforge;
 mod pack;
@@ -21,7 +26,11 @@ mod pbar;
 mod prelude;
 mod pubgrub;
 mod registry;
+mod snapshot;
+mod storage;
+mod telemetry;
 mod tool;
+mod usage;
 mod user;
 mod util;
 mod vanilla;
@@ -33,31 +42,42 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_inline_default::serde_inline_default;
 use std::{
+    collections::BTreeMap,
+    iter::once,
+    num::NonZeroUsize,
     ops::Deref,
     path::{Path, PathBuf},
     sync::Arc,
+    thread::available_parallelism,
+    time::Duration,
 };
 use stop::fatal;
 use tokio::{
     fs::{read_to_string, write},
     runtime,
 };
-use tracing::{Level, info, level_filters::LevelFilter};
+use tracing::{Level, info, level_filters::LevelFilter, warn};
 use tracing_indicatif::IndicatifLayer;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
 use url::Url;
 
 use crate::{
     artifact::ArtifactManager,
+    cache::{DEFAULT_STALE_CACHE_HOURS, clean_stale_cache},
     cmd::Execute,
+    deploy::DeployMode,
     dev::Dev,
     fabric::{FabricManager, IntermediaryManager},
+    forge::ForgeManager,
     game::GameManager,
+    http::{DEFAULT_FETCH_ATTEMPT, FetchOption},
+    id::DEFAULT_INDEX_DEPTH,
     index::IndexCache,
     java::JavaManager,
     neoforge::{NeoforgeManager, NeoforgeServerManager},
     path::{creeper_config_dir, init_creeper_dirs},
     registry::Registry,
+    storage::Storage,
     tool::Tool,
     user::UserManager,
     vanilla::{VanillaManager, VanillaServerManager},
@@ -74,11 +94,13 @@ pub struct CreeperInner {
     vanilla: VanillaManager,
     vanilla_server: VanillaServerManager,
     http: Client,
+    fetch: FetchOption,
     registry: Registry,
     index_cache: IndexCache,
     game: GameManager,
     neoforge: NeoforgeManager,
     neoforge_server: NeoforgeServerManager,
+    forge: ForgeManager,
     fabric: FabricManager,
     intermediary: IntermediaryManager,
     user: UserManager,
@@ -119,28 +141,48 @@ impl Creeper {
         Ok(config)
     }
 
-    pub async fn new(args: Args) -> anyhow::Result<Self> {
-        init_creeper_dirs().await?;
-
+    /// Path to the config file in use, see [`Args::config`].
+    pub fn config_path(args: &Args) -> anyhow::Result<PathBuf> {
         let path = args
             .config
             .clone()
             .unwrap_or(creeper_config_dir()?.join("config.toml"));
+        Ok(path)
+    }
+
+    pub async fn new(args: Args) -> anyhow::Result<Self> {
+        init_creeper_dirs().await?;
+
+        let mut config = Self::load_config(Self::config_path(&args)?).await?;
 
-        let config = Self::load_config(path).await?;
-
-        let http = Client::default();
-        let registry = Registry::new(config.registry.clone(), http.clone())?;
-        let game = GameManager::new(args.dir.clone());
-        let neoforge = NeoforgeManager::new(http.clone());
-        let vanilla = VanillaManager::new(http.clone());
-        let artifact =
-            ArtifactManager::new(http.clone(), args.offline, config.parallel_download).await?;
-        let user = UserManager::new();
-        let fabric = FabricManager::new(http.clone(), config.parallel_download);
+        config.apply_args(&args);
+
+        if config.stale_cache_hours > 0 {
+            let older_than = Duration::from_hours(config.stale_cache_hours);
+            if let Err(e) = clean_stale_cache(older_than).await {
+                warn!("failed to remove stale cache files: {e}");
+            }
+        }
+
+        let fetch = FetchOption::new(&config);
+
+        let http = http::build_client(&config)?;
+        let registry = Registry::new(
+            config.registry.clone(),
+            http.clone(),
+            fetch.clone(),
+            config.index_depth,
+        )?;
+        let game = GameManager::new(args.dir.clone(), args.manifest.clone());
+        let neoforge = NeoforgeManager::new(http.clone(), fetch.clone());
+        let forge = ForgeManager::new(http.clone(), fetch.clone());
+        let vanilla = VanillaManager::new(http.clone(), fetch.clone());
+        let artifact = ArtifactManager::new(http.clone(), fetch.clone(), &config, &args).await?;
+        let user = UserManager::new()?;
+        let fabric = FabricManager::new(http.clone(), config.parallel_download());
         let intermediary = IntermediaryManager::new(http.clone());
-        let vanilla_server = VanillaServerManager::new(http.clone());
-        let neoforge_server = NeoforgeServerManager::new(http.clone());
+        let vanilla_server = VanillaServerManager::new(http.clone(), fetch.clone());
+        let neoforge_server = NeoforgeServerManager::new(http.clone(), fetch.clone());
         let java = JavaManager::new();
 
         let val = CreeperInner {
@@ -150,10 +192,12 @@ impl Creeper {
             vanilla,
             vanilla_server,
             http,
+            fetch,
             registry,
             index_cache: IndexCache::new(),
             neoforge,
             neoforge_server,
+            forge,
             game,
             user,
             fabric,
@@ -167,6 +211,13 @@ impl Creeper {
         cmd.execute(self).await
     }
 
+    /// Release resources that should not rely on drop, i.e. close the artifact index database.
+    ///
+    /// The artifact storage cannot be used afterwards.
+    pub async fn shutdown(&self) {
+        self.artifact.close().await;
+    }
+
     pub async fn update(&self) -> anyhow::Result<()> {
         if self.args.offline {
             info!("skipping update because offline mode enabled");
@@ -195,6 +246,14 @@ pub struct Args {
     #[arg(short, long)]
     pub dir: Option<PathBuf>,
 
+    /// Read the manifest of current minecraft instance from this file instead of `creeper.toml`.
+    ///
+    /// Use `-` to read from standard input.
+    /// Unless `--dir` is specified, the home directory defaults to the parent directory of the file,
+    /// or the current directory if reading from standard input.
+    #[arg(long, value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
+
     /// Run in offline mode.
     ///
     /// If enabled, would prevent network requests and only use cached data.
@@ -203,6 +262,68 @@ pub struct Args {
     /// and there may still be network requests even if this option is enabled.
     #[arg(long, default_value_t = false)]
     pub offline: bool,
+
+    /// Limit number of threads for blocking operations, e.g. file hashing and extraction.
+    ///
+    /// If not specified, the runtime default is used.
+    /// Note that hashing of downloads is additionally bounded by `parallel-download` in config,
+    /// so raising this mostly speeds up bulk imports of local files.
+    #[arg(long, value_name = "N")]
+    pub blocking_threads: Option<NonZeroUsize>,
+
+    /// Limit number of parallel jobs of any kind, like `make -j`.
+    ///
+    /// This overrides `jobs` in config, which is the default of `parallel-download` and `parallel-extract`,
+    /// and is the default of `--blocking-threads`.
+    /// Use `0` for the available parallelism of the system.
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// Trust the artifact index instead of re-hashing stored files before use.
+    ///
+    /// This speeds up repeated launches, but a corrupted file in storage would go unnoticed.
+    #[arg(long, default_value_t = false)]
+    pub no_verify: bool,
+
+    /// Report for each artifact why it is reused or downloaded.
+    #[arg(long, default_value_t = false)]
+    pub explain: bool,
+
+    /// URL to the package registry, overriding `registry` in config.
+    #[arg(long, value_name = "URL")]
+    pub registry: Option<Url>,
+
+    /// Whether to download from the BMCLAPI mirror, overriding `use-bmclapi` in config.
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub use_bmclapi: Option<bool>,
+
+    /// Proxy to use for all hosts, e.g. `socks5://127.0.0.1:1080`, or `none` to connect directly.
+    ///
+    /// This overrides `proxy` in config entirely.
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// How stored artifacts are placed into game instances, overriding `deploy` in config.
+    #[arg(long, value_name = "MODE")]
+    pub deploy: Option<DeployMode>,
+
+    /// Permissions of stored artifacts in octal, overriding `store-mode` in config.
+    #[arg(long, value_name = "MODE")]
+    pub store_mode: Option<String>,
+
+    /// Do not send usage reports, even if `telemetry` is enabled in config.
+    #[arg(long, default_value_t = false)]
+    pub no_telemetry: bool,
+}
+
+impl Args {
+    /// The number of parallel jobs specified by [`Self::jobs`], resolving `0` to the available parallelism.
+    pub fn jobs(&self) -> Option<NonZeroUsize> {
+        let jobs = self.jobs?;
+        NonZeroUsize::new(jobs)
+            .or_else(|| available_parallelism().ok())
+            .or(NonZeroUsize::new(1))
+    }
 }
 
 impl Default for Args {
@@ -210,7 +331,18 @@ impl Default for Args {
         Self {
             config: None,
             dir: None,
+            manifest: None,
             offline: false,
+            blocking_threads: None,
+            jobs: None,
+            no_verify: false,
+            explain: false,
+            registry: None,
+            use_bmclapi: None,
+            proxy: None,
+            deploy: None,
+            store_mode: None,
+            no_telemetry: false,
         }
     }
 }
@@ -224,30 +356,226 @@ pub struct Config {
     #[serde(skip_serializing_if = "is_default_registry")]
     pub registry: Url,
 
-    /// Limit number of parallel downloads.
-    #[serde_inline_default(4)]
-    #[serde(skip_serializing_if = "is_default_parallel_download")]
-    pub parallel_download: usize,
+    /// Limit number of parallel jobs of any kind, defaults to the available parallelism of the system.
+    ///
+    /// This is the default of `parallel-download` and `parallel-extract`, and can be overridden with `--jobs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+
+    /// Limit number of parallel downloads, defaults to `jobs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_download: Option<usize>,
+
+    /// Limit number of archives expanded in parallel when launching, defaults to `jobs`.
+    ///
+    /// Each extraction holds open files, so a high limit may exhaust file descriptors with large modpacks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_extract: Option<usize>,
 
     #[serde_inline_default(false)]
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub use_bmclapi: bool,
+
+    /// Whether to re-hash stored files before use, can be overridden with `--no-verify`.
+    #[serde_inline_default(true)]
+    #[serde(skip_serializing_if = "is_true")]
+    pub verify_storage: bool,
+
+    /// Root directory of the artifact storage, defaults to the user data directory.
+    ///
+    /// Use `creeper storage relocate` to move an existing storage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<PathBuf>,
+
+    /// Proxy to use for each destination host, e.g. the registry host.
+    ///
+    /// Values are proxy URLs like `socks5://127.0.0.1:1080`, or `none` to connect directly.
+    /// The special host `*` matches any host not otherwise listed.
+    /// If non-empty, system proxy settings are ignored and unmatched hosts connect directly.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub proxy: BTreeMap<String, String>,
+
+    /// Maximum seconds an installation may take in total, unlimited if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_budget: Option<u64>,
+
+    /// Seconds to keep the minecraft version manifest cached before refreshing.
+    ///
+    /// Version metadata is immutable and always cached permanently.
+    #[serde_inline_default(3600)]
+    #[serde(skip_serializing_if = "is_default_manifest_ttl")]
+    pub manifest_ttl: u64,
+
+    /// Hours after which temporary files left in the cache, e.g. by interrupted downloads, are removed on startup.
+    ///
+    /// Partial downloads, which later runs resume, are kept for at least 30 days regardless.
+    /// Use `0` to disable the sweep, and `creeper cache clean` to remove them manually.
+    #[serde_inline_default(DEFAULT_STALE_CACHE_HOURS)]
+    #[serde(skip_serializing_if = "is_default_stale_cache_hours")]
+    pub stale_cache_hours: u64,
+
+    /// Permissions of files in the artifact storage in octal, e.g. `0444` to share it read-only with other users.
+    ///
+    /// Only applies on unix, where stored files are readonly with permissions left to the umask by default.
+    /// The index database is additionally kept writable by its owner.
+    /// Note that the storage directories must also be accessible, which is left to the umask.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_mode: Option<String>,
+
+    /// How stored artifacts are placed into game instances.
+    #[serde(default, skip_serializing_if = "DeployMode::is_default")]
+    pub deploy: DeployMode,
+
+    /// Directory to deploy native libraries into when launching, e.g. on a tmpfs to spare the disk.
+    ///
+    /// Each instance gets its own subdirectory, which is removed when the game exits.
+    /// Defaults to `.creeper/native` in the instance, can be overridden with `creeper launch --natives-dir`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub natives_dir: Option<PathBuf>,
+
+    /// Rules rewriting download URLs, from a URL prefix to its replacement, e.g. a mirror.
+    ///
+    /// Applies to both metadata and artifact downloads, with the longest matching prefix taking precedence.
+    /// For example, `"https://libraries.minecraft.net/" = "https://bmclapi2.bangbang93.com/maven/"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mirror: BTreeMap<String, String>,
+
+    /// Number of attempts to fetch metadata, e.g. version manifests, or to download an artifact, before giving up on a flaky server.
+    #[serde_inline_default(DEFAULT_FETCH_ATTEMPT)]
+    #[serde(skip_serializing_if = "is_default_fetch_attempt")]
+    pub fetch_attempt: u32,
+
+    /// Number of directory levels in the sparse index of the package registry.
+    ///
+    /// This must agree with the layout of the registry.
+    #[serde_inline_default(DEFAULT_INDEX_DEPTH)]
+    #[serde(skip_serializing_if = "is_default_index_depth")]
+    pub index_depth: usize,
+
+    /// Consent to report anonymous usage to `telemetry-endpoint` after each installation.
+    ///
+    /// Only the versions of creeper and minecraft, the mod loader, and the OS and architecture are sent.
+    /// This is off by default, and can be disabled for a single run with `--no-telemetry`.
+    #[serde_inline_default(false)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub telemetry: bool,
+
+    /// URL to post usage reports to, nothing is sent if not set regardless of `telemetry`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telemetry_endpoint: Option<Url>,
+
+    /// Launch the demo mode of the game, enabling the `is_demo_user` launcher feature as `creeper launch --demo`.
+    #[serde_inline_default(false)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub demo: bool,
+
+    /// Default width of the game window, only taking effect along with `height`.
+    ///
+    /// This enables the `has_custom_resolution` launcher feature, and is overridden by `creeper launch --width`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+
+    /// Default height of the game window, only taking effect along with `width`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+impl Config {
+    /// Apply the overrides given on the command line.
+    pub fn apply_args(&mut self, args: &Args) {
+        if let Some(jobs) = args.jobs() {
+            self.jobs = Some(jobs.get());
+        }
+
+        if let Some(registry) = &args.registry {
+            self.registry = registry.clone();
+        }
+
+        if let Some(use_bmclapi) = args.use_bmclapi {
+            self.use_bmclapi = use_bmclapi;
+        }
+
+        if let Some(proxy) = &args.proxy {
+            self.proxy = once(("*".into(), proxy.clone())).collect();
+        }
+
+        if let Some(mode) = args.deploy {
+            self.deploy = mode;
+        }
+
+        if let Some(mode) = &args.store_mode {
+            self.store_mode = Some(mode.clone());
+        }
+    }
+
+    /// The number of parallel jobs specified by [`Self::jobs`], resolving `0` or none to the available parallelism.
+    pub fn jobs(&self) -> usize {
+        self.jobs
+            .and_then(NonZeroUsize::new)
+            .or_else(|| available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get)
+    }
+
+    /// Limit number of parallel downloads, see [`Self::parallel_download`].
+    pub fn parallel_download(&self) -> usize {
+        self.parallel_download.unwrap_or_else(|| self.jobs()).max(1)
+    }
+
+    /// Limit number of archives expanded in parallel, see [`Self::parallel_extract`].
+    pub fn parallel_extract(&self) -> usize {
+        self.parallel_extract.unwrap_or_else(|| self.jobs()).max(1)
+    }
 }
 
 fn is_default_registry(registry: &Url) -> bool {
     registry == &"https://creeper-registry.pages.dev/".parse().unwrap()
 }
 
-fn is_default_parallel_download(parallel_download: &usize) -> bool {
-    *parallel_download == 4
+fn is_default_manifest_ttl(manifest_ttl: &u64) -> bool {
+    *manifest_ttl == 3600
+}
+
+fn is_default_stale_cache_hours(stale_cache_hours: &u64) -> bool {
+    *stale_cache_hours == DEFAULT_STALE_CACHE_HOURS
+}
+
+fn is_default_fetch_attempt(fetch_attempt: &u32) -> bool {
+    *fetch_attempt == DEFAULT_FETCH_ATTEMPT
+}
+
+fn is_default_index_depth(index_depth: &usize) -> bool {
+    *index_depth == DEFAULT_INDEX_DEPTH
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             registry: "https://creeper-registry.pages.dev/".parse().unwrap(),
-            parallel_download: 4,
+            jobs: None,
+            parallel_download: None,
+            parallel_extract: None,
             use_bmclapi: false,
+            verify_storage: true,
+            storage: None,
+            proxy: BTreeMap::new(),
+            timeout_budget: None,
+            manifest_ttl: 3600,
+            stale_cache_hours: DEFAULT_STALE_CACHE_HOURS,
+            store_mode: None,
+            deploy: DeployMode::default(),
+            natives_dir: None,
+            mirror: BTreeMap::new(),
+            fetch_attempt: DEFAULT_FETCH_ATTEMPT,
+            index_depth: DEFAULT_INDEX_DEPTH,
+            telemetry: false,
+            telemetry_endpoint: None,
+            demo: false,
+            width: None,
+            height: None,
         }
     }
 }
@@ -297,12 +625,48 @@ pub enum SubCommand {
 
     Install(cmd::Install),
 
+    Lock(cmd::Lock),
+
+    DiffLock(cmd::DiffLock),
+
     Nuke(cmd::Nuke),
 
+    Gc(cmd::Gc),
+
+    #[command(subcommand)]
+    Cache(cmd::Cache),
+
     Login(cmd::Login),
 
+    #[command(subcommand)]
+    Account(cmd::Account),
+
     Init(cmd::Init),
 
+    Fmt(cmd::Fmt),
+
+    Migrate(cmd::Migrate),
+
+    Verify(cmd::Verify),
+
+    Licenses(cmd::Licenses),
+
+    Env(cmd::Env),
+
+    CheckUpdates(cmd::CheckUpdates),
+
+    #[command(subcommand)]
+    Java(cmd::Java),
+
+    #[command(subcommand)]
+    Mod(cmd::Mod),
+
+    #[command(subcommand)]
+    Pack(cmd::Pack),
+
+    #[command(subcommand)]
+    Storage(Storage),
+
     #[command(subcommand)]
     Dev(Dev),
 
@@ -319,10 +683,25 @@ impl Execute for SubCommand {
             SubCommand::AwwMan => Ok(println!("{CREEPER_TEXT_ART}")),
             SubCommand::Install(install) => lib.execute(install).await,
             SubCommand::Launch(launch) => lib.execute(launch).await,
+            SubCommand::Lock(lock) => lib.execute(lock).await,
+            SubCommand::DiffLock(diff) => lib.execute(diff).await,
             SubCommand::Nuke(nuke) => lib.execute(nuke).await,
+            SubCommand::Gc(gc) => lib.execute(gc).await,
+            SubCommand::Cache(cache) => lib.execute(cache).await,
             SubCommand::Login(login) => lib.execute(login).await,
+            SubCommand::Account(account) => lib.execute(account).await,
             SubCommand::Init(init) => lib.execute(init).await,
+            SubCommand::Fmt(fmt) => lib.execute(fmt).await,
+            SubCommand::Migrate(migrate) => lib.execute(migrate).await,
+            SubCommand::Verify(verify) => lib.execute(verify).await,
+            SubCommand::Licenses(licenses) => lib.execute(licenses).await,
+            SubCommand::Env(env) => lib.execute(env).await,
+            SubCommand::CheckUpdates(check) => lib.execute(check).await,
+            SubCommand::Java(java) => lib.execute(java).await,
             SubCommand::Add(add) => lib.execute(add).await,
+            SubCommand::Mod(mods) => lib.execute(mods).await,
+            SubCommand::Pack(pack) => lib.execute(pack).await,
+            SubCommand::Storage(storage) => lib.execute(storage).await,
             SubCommand::Dev(_dev) => todo!(),
             SubCommand::Complete(complete) => lib.execute(complete).await,
         }
@@ -354,12 +733,19 @@ fn main() {
         .with(layer)
         .init();
 
-    let run = runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap_or_else(fatal!());
+    let mut run = runtime::Builder::new_multi_thread();
+
+    if let Some(n) = args.blocking_threads.or(args.jobs()) {
+        run.max_blocking_threads(n.get());
+    }
+
+    let run = run.enable_all().build().unwrap_or_else(fatal!());
 
     let creeper = run.block_on(Creeper::new(args)).unwrap_or_else(fatal!());
 
-    run.block_on(creeper.execute(cmd)).unwrap_or_else(fatal!());
+    let result = run.block_on(creeper.execute(cmd));
+
+    run.block_on(creeper.shutdown());
+
+    result.unwrap_or_else(fatal!());
 }
@@ -132,6 +132,11 @@ impl MicrosoftClient {
         Ok(uuid)
     }
 
+    /// The player name cached in the session data, without asking the server.
+    pub async fn cached_mc_name(&self) -> Option<String> {
+        self.data.read().await.mc_name.clone()
+    }
+
     pub async fn get_mc_name(&self) -> anyhow::Result<String> {
         if let Some(name) = self.data.read().await.mc_name.clone() {
             return Ok(name);
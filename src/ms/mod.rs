@@ -10,29 +10,32 @@ use std::{
 use anyhow::anyhow;
 use chrono::Utc;
 use oauth2::{
-    AccessToken, AuthUrl, ClientId, EndpointNotSet, EndpointSet, RedirectUrl, RefreshToken,
-    TokenUrl, basic::BasicClient,
+    AccessToken, AuthUrl, ClientId, DeviceAuthorizationUrl, EndpointNotSet, EndpointSet,
+    RedirectUrl, RefreshToken, TokenUrl, basic::BasicClient,
 };
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tokio::{
-    fs::{create_dir_all, read_to_string, try_exists, write},
+    fs::{read_to_string, try_exists},
     sync::RwLock,
 };
 use tracing::debug;
 use uuid::Uuid;
 
 use crate::path::creeper_data_dir;
+use crate::util::write_private;
 
 const AUTH_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/authorize";
 
 const TOKEN_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/token";
 
+const DEVICE_AUTH_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/devicecode";
+
 const CLIENT_ID: &str = "2409a08e-df70-4e42-99ba-0843d4a1658e";
 
 type OauthClient = oauth2::basic::BasicClient<
     EndpointSet,
-    EndpointNotSet,
+    EndpointSet,
     EndpointNotSet,
     EndpointNotSet,
     EndpointSet,
@@ -109,9 +112,7 @@ impl MicrosoftClient {
 
         let json = serde_json::to_string(&*data)?;
 
-        create_dir_all(path.parent().unwrap()).await?;
-
-        write(path, json).await?;
+        write_private(&path, json).await?;
 
         Ok(())
     }
@@ -138,6 +139,7 @@ impl MicrosoftClient {
             // .set_client_secret(ClientSecret::new("secret".into()))
             .set_auth_uri(AuthUrl::new(AUTH_URL.into())?)
             .set_token_uri(TokenUrl::new(TOKEN_URL.into())?)
+            .set_device_authorization_url(DeviceAuthorizationUrl::new(DEVICE_AUTH_URL.into())?)
             .set_redirect_uri(RedirectUrl::new("http://localhost:5555".into())?);
 
         let value = Self {
@@ -3,8 +3,9 @@ use std::time::{Duration, SystemTime};
 use colored::Colorize;
 use oauth2::{
     AccessToken, AuthorizationCode, CsrfToken, PkceCodeChallenge, RefreshToken, Scope,
-    TokenResponse,
+    StandardDeviceAuthorizationResponse, TokenResponse,
 };
+use tokio::time::sleep;
 use tracing::{info, trace};
 
 use crate::ms::{MicrosoftClient, calc_expiry};
@@ -60,10 +61,11 @@ impl MicrosoftClient {
     }
 
     pub async fn refresh_ms_token(&self) -> anyhow::Result<()> {
-        let mut data = self.data.write().await;
-
+        // obtained before locking, since this may prompt a login which also writes the data
         let refresh = self.get_ms_refresh_token().await?;
 
+        let mut data = self.data.write().await;
+
         let token = self
             .oauth
             .exchange_refresh_token(&refresh)
@@ -136,4 +138,44 @@ impl MicrosoftClient {
 
         Ok(())
     }
+
+    /// Log in with the [device code flow](https://learn.microsoft.com/entra/identity-platform/v2-oauth2-device-code),
+    /// where the user enters a code at the verification page on any device.
+    ///
+    /// Unlike [`Self::prompt_login`], this needs neither a browser nor a local port on this machine.
+    pub async fn prompt_login_device(&self) -> anyhow::Result<()> {
+        let mut data = self.data.write().await;
+
+        let http = Self::http_oauth()?;
+
+        let details: StandardDeviceAuthorizationResponse = self
+            .oauth
+            .exchange_device_code()
+            .add_scopes(Self::ms_scopes())
+            .request_async(&http)
+            .await?;
+
+        eprintln!(
+            "{} {} and enter code {}",
+            "Open".bold().cyan(),
+            details.verification_uri().as_str(),
+            details.user_code().secret().bold()
+        );
+
+        let token = self
+            .oauth
+            .exchange_device_access_token(&details)
+            .request_async(&http, sleep, None)
+            .await?;
+
+        info!("logged in with device code");
+
+        data.access_token = Some(token.access_token().clone());
+
+        data.refresh_token = token.refresh_token().cloned();
+
+        data.access_token_expiry = token.expires_in().map(|x| calc_expiry(x.as_secs()));
+
+        Ok(())
+    }
 }
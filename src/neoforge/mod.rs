@@ -15,6 +15,7 @@ use tracing::{debug, info};
 use crate::{
     Artifact, Checksum, Creeper, Id, Install,
     builtin::SyncBuiltinIndex,
+    http::{FetchOption, fetch_json},
     index::{Index, VersionRev},
     pack::PackNode,
     path::creeper_cache_dir,
@@ -30,15 +31,19 @@ fn cache_path() -> anyhow::Result<PathBuf> {
 
 pub struct NeoforgeManager {
     http: Client,
+    fetch: FetchOption,
 }
 
 impl NeoforgeManager {
-    pub fn new(http: Client) -> Self {
-        Self { http }
+    pub fn new(http: Client, fetch: FetchOption) -> Self {
+        Self { http, fetch }
     }
 }
 
-async fn query_neoforge_versions(http: &Client) -> anyhow::Result<Vec<String>> {
+async fn query_neoforge_versions(
+    http: &Client,
+    fetch: &FetchOption,
+) -> anyhow::Result<Vec<String>> {
     const VERSIONS_URL: &str =
         "https://maven.neoforged.net/api/maven/versions/releases/net/neoforged/neoforge";
 
@@ -49,13 +54,7 @@ async fn query_neoforge_versions(http: &Client) -> anyhow::Result<Vec<String>> {
         versions: Vec<String>,
     }
 
-    let versions = http
-        .get(VERSIONS_URL)
-        .send()
-        .await?
-        .error_for_status()?
-        .json::<Versions>()
-        .await?;
+    let versions = fetch_json::<Versions>(http, fetch, VERSIONS_URL, None).await?;
 
     Ok(versions.versions)
 }
@@ -68,7 +67,7 @@ impl SyncBuiltinIndex for NeoforgeManager {
     async fn sync_index(&self) -> anyhow::Result<Index> {
         info!("updating NeoForge metadata");
 
-        let versions = query_neoforge_versions(&self.http).await?;
+        let versions = query_neoforge_versions(&self.http, &self.fetch).await?;
 
         let count = versions.len();
 
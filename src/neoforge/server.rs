@@ -9,6 +9,7 @@ use tracing::debug;
 use crate::{
     Creeper, Id, Install, VersionRev,
     builtin::SyncBuiltinIndex,
+    http::FetchOption,
     index::{Index, independent_index},
     neoforge::{
         decode_neoforge_version, nf_required_mc_version, parse_neoforge_version,
@@ -20,11 +21,12 @@ use crate::{
 
 pub struct NeoforgeServerManager {
     http: Client,
+    fetch: FetchOption,
 }
 
 impl NeoforgeServerManager {
-    pub fn new(http: Client) -> Self {
-        Self { http }
+    pub fn new(http: Client, fetch: FetchOption) -> Self {
+        Self { http, fetch }
     }
 }
 
@@ -34,7 +36,7 @@ impl SyncBuiltinIndex for NeoforgeServerManager {
     }
 
     async fn sync_index(&self) -> anyhow::Result<Index> {
-        let versions = query_neoforge_versions(&self.http).await?;
+        let versions = query_neoforge_versions(&self.http, &self.fetch).await?;
 
         let count = versions.len();
 
@@ -190,6 +192,7 @@ impl Creeper {
         install.extend([Install {
             java_flag: vec![arg],
             mc_flag: vec!["nogui".into()],
+            server: true,
             ..Default::default()
         }]);
 
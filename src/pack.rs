@@ -4,21 +4,25 @@ use std::{
 };
 
 use semver::{Version, VersionReq};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de::Error};
 use serde_inline_default::serde_inline_default;
 use serde_with::{DisplayFromStr, serde_as};
 use spdx::Expression;
 
-use crate::{Id, Install, pubgrub::Conflict};
+use crate::{Id, Install, id::parse_version_req, pubgrub::Conflict};
 
 /// The package node in the dependency graph, containing only metadata needed for dependency resolution.
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct PackNode {
     /// Dependencies.
+    ///
+    /// A requirement of `latest` is stored as `*`,
+    /// so it is written back as `*` whenever the manifest is serialized from a [`Package`] again.
     #[serde(
         default,
         rename = "dependencies",
+        deserialize_with = "deserialize_dep",
         skip_serializing_if = "BTreeMap::is_empty"
     )]
     pub dep: BTreeMap<Id, VersionReq>,
@@ -38,6 +42,19 @@ pub struct PackNode {
     pub either_dep: Vec<BTreeMap<Id, VersionReq>>,
 }
 
+/// Deserialize dependencies, accepting `latest` as a version requirement.
+fn deserialize_dep<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Id, VersionReq>, D::Error> {
+    BTreeMap::<Id, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(id, req)| {
+            let req = parse_version_req(&req).map_err(D::Error::custom)?;
+            Ok((id, req))
+        })
+        .collect()
+}
+
 impl PackNode {
     pub fn neighbours(self) -> HashSet<Id> {
         self.dep
@@ -119,3 +136,26 @@ pub struct PackMeta {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub license: Option<Expression>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dep_latest() {
+        let node = toml::from_str::<PackNode>(
+            r#"
+            [dependencies]
+            sodium = "latest"
+            lithium = "^0.11"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(node.dep[&"sodium".parse::<Id>().unwrap()], VersionReq::STAR);
+        assert_eq!(
+            node.dep[&"lithium".parse::<Id>().unwrap()],
+            "^0.11".parse().unwrap()
+        );
+    }
+}
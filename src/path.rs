@@ -28,6 +28,18 @@ pub fn creeper_cache_dir() -> anyhow::Result<PathBuf> {
     Ok(dir)
 }
 
+/// Directory of downloads in progress, or left behind by an interrupted run.
+pub fn creeper_download_cache_dir() -> anyhow::Result<PathBuf> {
+    let dir = creeper_cache_dir()?.join("download");
+    Ok(dir)
+}
+
+/// Directory of files being extracted from archives into storage.
+pub fn creeper_extract_cache_dir() -> anyhow::Result<PathBuf> {
+    let dir = creeper_cache_dir()?.join("extract");
+    Ok(dir)
+}
+
 /// Directory for Minecraft instances managed by Creeper.
 pub fn creeper_mc_dir() -> anyhow::Result<PathBuf> {
     let dir = creeper_data_dir()?.join("minecraft");
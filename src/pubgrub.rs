@@ -15,7 +15,11 @@ use pubgrub::{DefaultStringReporter, Dependencies, DependencyProvider, Reporter}
 use semver::{BuildMetadata, Prerelease, Version, VersionReq};
 use tracing::{debug, error, info, trace, warn};
 
-use crate::{Creeper, Id, index::VersionRev, pack::PackNode};
+use crate::{
+    Creeper, Id,
+    index::{IndexSource, VersionRev},
+    pack::PackNode,
+};
 
 struct Error(anyhow::Error);
 
@@ -216,14 +220,14 @@ impl Extend<Conflict> for ConflictManager {
     }
 }
 
-struct Resolve {
-    lib: Creeper,
+struct Resolve<S = Creeper> {
+    lib: S,
     root: PackNode,
     conflict: RwLock<ConflictManager>,
 }
 
-impl Resolve {
-    fn new(lib: Creeper, root: PackNode) -> Self {
+impl<S: IndexSource> Resolve<S> {
+    fn new(lib: S, root: PackNode) -> Self {
         Self {
             lib,
             root,
@@ -278,7 +282,7 @@ impl VersionLike for VersionRev {
     }
 }
 
-impl DependencyProvider for Resolve {
+impl<S: IndexSource> DependencyProvider for Resolve<S> {
     type P = Package;
 
     type V = VersionRev;
@@ -427,66 +431,7 @@ impl Creeper {
         &self,
         req: BTreeMap<Id, VersionReq>,
     ) -> anyhow::Result<HashMap<Id, VersionRev>> {
-        info!("resolving {} required packages", req.len());
-
-        let resolve = Resolve::new(
-            self.clone(),
-            PackNode {
-                dep: req,
-                ..Default::default()
-            },
-        );
-
-        resolve.prepare()?;
-
-        let res = pubgrub::resolve(&resolve, Package::Root, Version::new(0, 0, 0));
-
-        let sol = res.map_err(|e| match e {
-            pubgrub::PubGrubError::NoSolution(derivation_tree) => {
-                let mut report = DefaultStringReporter::report(&derivation_tree);
-
-                // remove the ugly double newlines in the report
-                while report.find("\n\n").is_some() {
-                    report = report.replace("\n\n", "\n");
-                }
-
-                anyhow!("no solution:\n{report}")
-            }
-            pubgrub::PubGrubError::ErrorRetrievingDependencies {
-                package,
-                version,
-                source,
-            } => anyhow!(
-                "failed to retrieve dependencies for package {package} version {version}: {source}"
-            ),
-            pubgrub::PubGrubError::ErrorChoosingVersion { package, source } => {
-                anyhow!("failed to choose version for package {package}: {source}")
-            }
-            pubgrub::PubGrubError::ErrorInShouldCancel(_) => {
-                anyhow!("package resolution cancelled")
-            }
-        })?;
-
-        let sol = sol.into_iter();
-
-        let all = sol.len();
-
-        // PubGrub uses non-default hasher, convert to standard before returning
-        let sol = sol
-            .filter_map(|(k, v)| match k {
-                Package::Normal(id) => Some((id, v)),
-                _ => None,
-            })
-            .collect::<HashMap<_, _>>();
-
-        let real = sol.len();
-
-        info!(
-            "resolved {all} packages, of which {real} real and {} virtual",
-            all - real
-        );
-
-        Ok(sol)
+        resolve(self.clone(), req)
     }
 
     /// Topologically sort the dependencies. Dependencies goes before dependents in the output.
@@ -543,3 +488,219 @@ impl Creeper {
         Ok(order)
     }
 }
+
+/// Resolve the highest versions of packages satisfying `req` and all their dependencies,
+/// with indices looked up in `source`.
+fn resolve<S: IndexSource>(
+    source: S,
+    req: BTreeMap<Id, VersionReq>,
+) -> anyhow::Result<HashMap<Id, VersionRev>> {
+    info!("resolving {} required packages", req.len());
+
+    let resolve = Resolve::new(
+        source,
+        PackNode {
+            dep: req,
+            ..Default::default()
+        },
+    );
+
+    resolve.prepare()?;
+
+    let res = pubgrub::resolve(&resolve, Package::Root, Version::new(0, 0, 0));
+
+    let sol = res.map_err(|e| match e {
+        pubgrub::PubGrubError::NoSolution(derivation_tree) => {
+            let mut report = DefaultStringReporter::report(&derivation_tree);
+
+            // remove the ugly double newlines in the report
+            while report.find("\n\n").is_some() {
+                report = report.replace("\n\n", "\n");
+            }
+
+            anyhow!("no solution:\n{report}")
+        }
+        pubgrub::PubGrubError::ErrorRetrievingDependencies {
+            package,
+            version,
+            source,
+        } => anyhow!(
+            "failed to retrieve dependencies for package {package} version {version}: {source}"
+        ),
+        pubgrub::PubGrubError::ErrorChoosingVersion { package, source } => {
+            anyhow!("failed to choose version for package {package}: {source}")
+        }
+        pubgrub::PubGrubError::ErrorInShouldCancel(_) => {
+            anyhow!("package resolution cancelled")
+        }
+    })?;
+
+    let sol = sol.into_iter();
+
+    let all = sol.len();
+
+    // PubGrub uses non-default hasher, convert to standard before returning
+    let sol = sol
+        .filter_map(|(k, v)| match k {
+            Package::Normal(id) => Some((id, v)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let real = sol.len();
+
+    info!(
+        "resolved {all} packages, of which {real} real and {} virtual",
+        all - real
+    );
+
+    Ok(sol)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{
+        prelude::*,
+        test_runner::{Config, RngSeed},
+    };
+
+    use super::*;
+    use crate::index::Index;
+
+    /// Packages published at fixed versions, in place of a registry.
+    struct Registry(HashMap<Id, Index>);
+
+    impl IndexSource for Registry {
+        fn blocking_get_index(&self, package: &Id) -> anyhow::Result<Index> {
+            self.0
+                .get(package)
+                .cloned()
+                .ok_or(anyhow!("package {package} not found"))
+        }
+    }
+
+    fn node(dep: &[(&str, &str)]) -> PackNode {
+        PackNode {
+            dep: dep
+                .iter()
+                .map(|(id, req)| (id.parse().unwrap(), req.parse().unwrap()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn index(version: &[(&str, PackNode)]) -> Index {
+        version
+            .iter()
+            .map(|(v, node)| (v.parse().unwrap(), node.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn resolve_two_packages() {
+        let app = index(&[
+            ("1.0.0", node(&[("lib", "^1")])),
+            ("1.1.0", node(&[("lib", "^1.1")])),
+            // no published version of lib satisfies this, so it must be skipped
+            ("2.0.0", node(&[("lib", "^3")])),
+        ]);
+        let lib = index(&[
+            ("1.0.0", node(&[])),
+            ("1.2.0", node(&[])),
+            ("2.0.0", node(&[])),
+        ]);
+        let registry = Registry(
+            [("app".parse().unwrap(), app), ("lib".parse().unwrap(), lib)]
+                .into_iter()
+                .collect(),
+        );
+
+        let req = [("app".parse().unwrap(), VersionReq::STAR)]
+            .into_iter()
+            .collect();
+        let sol = resolve(registry, req).unwrap();
+
+        assert_eq!(sol.len(), 2);
+        assert_eq!(sol[&"app".parse::<Id>().unwrap()], "1.1.0".parse().unwrap());
+        assert_eq!(sol[&"lib".parse::<Id>().unwrap()], "1.2.0".parse().unwrap());
+    }
+
+    #[test]
+    fn resolve_latest() {
+        let lib = index(&[
+            ("1.0.0", node(&[])),
+            ("1.2.0", node(&[])),
+            ("2.0.0", node(&[])),
+        ]);
+        let registry = Registry([("lib".parse().unwrap(), lib)].into_iter().collect());
+
+        let req = toml::from_str::<PackNode>("dependencies = { lib = \"latest\" }")
+            .unwrap()
+            .dep;
+        let sol = resolve(registry, req).unwrap();
+
+        assert_eq!(sol[&"lib".parse::<Id>().unwrap()], "2.0.0".parse().unwrap());
+    }
+
+    /// Cases are generated from a fixed seed for reproducibility, unless `PROPTEST_RNG_SEED` is set.
+    fn config() -> Config {
+        let mut config = Config::with_cases(4096);
+        if config.rng_seed == RngSeed::Random {
+            config.rng_seed = RngSeed::Fixed(1501);
+        }
+        config.failure_persistence = None;
+        config
+    }
+
+    fn pre() -> impl Strategy<Value = String> {
+        prop_oneof![
+            4 => Just(String::new()),
+            1 => Just("-alpha".into()),
+            1 => Just("-alpha.1".into()),
+            1 => Just("-beta".into()),
+            1 => Just("-rc.1".into()),
+        ]
+    }
+
+    /// Small numbers, so that generated versions often hit the bounds of generated requirements.
+    fn version() -> impl Strategy<Value = Version> {
+        (0..3u64, 0..3u64, 0..3u64, pre())
+            .prop_map(|(major, minor, patch, pre)| format!("{major}.{minor}.{patch}{pre}"))
+            .prop_map(|s| s.parse().unwrap())
+    }
+
+    fn comparator() -> impl Strategy<Value = String> {
+        let op = prop::sample::select(vec!["", "=", ">", ">=", "<", "<=", "~", "^"]);
+        let num = (0..3u64, 0..3u64, 0..3u64, pre(), 0..4usize).prop_map(
+            |(major, minor, patch, pre, len)| match len {
+                0 => format!("{major}"),
+                1 => format!("{major}.{minor}"),
+                2 => format!("{major}.{minor}.*"),
+                _ => format!("{major}.{minor}.{patch}{pre}"),
+            },
+        );
+        prop_oneof![
+            1 => Just("*".to_string()),
+            8 => (op, num).prop_map(|(op, num)| format!("{op}{num}")),
+        ]
+    }
+
+    fn version_req() -> impl Strategy<Value = VersionReq> {
+        prop::collection::vec(comparator(), 1..3)
+            .prop_filter_map("invalid requirement", |c| c.join(", ").parse().ok())
+    }
+
+    proptest! {
+        #![proptest_config(config())]
+
+        #[test]
+        fn range_matches_req(req in version_req(), v in version()) {
+            let range = SemverPubgrub::<VersionRev>::from(&req);
+            prop_assert_eq!(
+                range.contains(&VersionRev::new(v.clone())),
+                req.matches(&v),
+                "{} against {}", req, v
+            );
+        }
+    }
+}
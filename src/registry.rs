@@ -4,11 +4,11 @@ use std::{
     sync::RwLock,
 };
 
-use anyhow::bail;
-use reqwest::Client;
+use anyhow::{anyhow, bail};
+use reqwest::{Client, StatusCode, header::CONTENT_TYPE};
 use semver::Version;
 use tokio::{
-    fs::{File, create_dir_all, read_to_string, try_exists},
+    fs::{File, create_dir_all, read, read_to_string, try_exists},
     io::AsyncWriteExt,
     process::Command,
 };
@@ -16,16 +16,20 @@ use tracing::{debug, info};
 use url::Url;
 
 use crate::{
-    Creeper, Id, Package,
+    Artifact, Creeper, Id, Package,
+    http::{FetchOption, fetch_json_raw, is_not_found},
     index::{Index, IndexLine, VersionRev},
     path::creeper_cache_dir,
     tool::BuildIndex,
-    util::summarize,
+    util::{summarize, write_atomic},
 };
 
 pub struct Registry {
     pub url: Url,
+    /// Number of directory levels in the sparse index.
+    pub index_depth: usize,
     http: Client,
+    fetch: FetchOption,
     cache: RwLock<HashMap<Id, BTreeMap<VersionRev, Package>>>,
 }
 
@@ -42,7 +46,12 @@ impl Registry {
         Ok(self.cache_path()?.join("package-index"))
     }
 
-    pub fn new(url: Url, http: Client) -> anyhow::Result<Self> {
+    pub fn new(
+        url: Url,
+        http: Client,
+        fetch: FetchOption,
+        index_depth: usize,
+    ) -> anyhow::Result<Self> {
         match url.scheme() {
             "file" => debug!("using local registry at {url}"),
             "https" => debug!("using remote registry at {url}"),
@@ -50,7 +59,9 @@ impl Registry {
         }
         Ok(Self {
             url,
+            index_depth,
             http,
+            fetch,
             cache: RwLock::new(HashMap::new()),
         })
     }
@@ -90,7 +101,7 @@ impl Registry {
         let path = self
             .index_cache_path()?
             .join("index")
-            .join(package.indexed_path())
+            .join(package.indexed_path_at(self.index_depth))
             .with_added_extension("jsonl");
 
         if !path.exists() {
@@ -106,7 +117,7 @@ impl Registry {
         let path = self
             .index_cache_path()?
             .join("index")
-            .join(package.indexed_path())
+            .join(package.indexed_path_at(self.index_depth))
             .with_added_extension("jsonl");
 
         if !try_exists(&path).await? {
@@ -118,6 +129,111 @@ impl Registry {
         Ok(pack)
     }
 
+    /// Path of a package manifest in a local registry.
+    fn package_path(&self, id: &Id, version: &Version, rev: u32) -> PathBuf {
+        PathBuf::from(self.url.path())
+            .join(id.indexed_path_at(self.index_depth))
+            .join(version.to_string())
+            .join(rev.to_string())
+            .with_added_extension("toml")
+    }
+
+    /// URL of a package manifest in a remote registry.
+    fn package_url(&self, id: &Id, version: &Version, rev: u32) -> anyhow::Result<Url> {
+        let url = self
+            .url
+            .join("package/")?
+            .join(&format!(
+                "{}/",
+                id.indexed_path_at(self.index_depth).to_str().unwrap()
+            ))?
+            .join(&format!("{version}/"))?
+            .join(&format!("{rev}.json"))?;
+
+        Ok(url)
+    }
+
+    /// URL of an artifact uploaded to a remote registry.
+    pub fn artifact_url(&self, blake3: &str) -> anyhow::Result<Url> {
+        let url = self
+            .url
+            .join("artifact/")?
+            .join(&format!("{}/", &blake3[..2]))?
+            .join(blake3)?;
+
+        Ok(url)
+    }
+
+    /// Upload a package to the registry.
+    ///
+    /// Each of `upload` is an artifact and its local file, which is `PUT` to [`Self::artifact_url`]
+    /// before the package manifest itself, so that the registry never lists a package with missing files.
+    ///
+    /// For local registries, the manifest is written into the registry directory and `upload` must be empty.
+    pub async fn publish(
+        &self,
+        package: &Package,
+        upload: &[(Artifact, PathBuf)],
+        token: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let Package {
+            id, version, rev, ..
+        } = package;
+
+        if self.url.scheme() == "file" {
+            if !upload.is_empty() {
+                bail!("cannot upload artifacts to a local registry, they must have a source URL");
+            }
+
+            let path = self.package_path(id, version, *rev);
+
+            if try_exists(&path).await? {
+                bail!("{id}@{version} rev {rev} already exists in the registry");
+            }
+
+            write_atomic(&path, toml::to_string_pretty(package)?).await?;
+
+            return Ok(());
+        }
+
+        let token = token.ok_or(anyhow!("an access token is required to publish"))?;
+
+        for (art, path) in upload {
+            let url = self.artifact_url(&art.blake3)?;
+            debug!("uploading {art} to {url}");
+
+            let body = read(path).await?;
+
+            self.http
+                .put(url)
+                .bearer_auth(token)
+                .header(CONTENT_TYPE, "application/octet-stream")
+                .body(body)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        let url = self.package_url(id, version, *rev)?;
+        debug!("uploading manifest to {url}");
+
+        let res = self
+            .http
+            .put(url)
+            .bearer_auth(token)
+            .json(package)
+            .send()
+            .await?;
+
+        if res.status() == StatusCode::CONFLICT {
+            bail!("{id}@{version} rev {rev} already exists in the registry");
+        }
+
+        res.error_for_status()?;
+
+        Ok(())
+    }
+
     pub async fn get(&self, id: &Id, version: &Version, rev: u32) -> anyhow::Result<Package> {
         if let Some(pack) = self.cache.read().unwrap().get(id) {
             if let Some(pack) = pack.get(&VersionRev::with_rev(version.clone(), rev)) {
@@ -126,13 +242,7 @@ impl Registry {
         }
 
         if self.url.scheme() == "file" {
-            let path = self.url.path();
-
-            let path = PathBuf::from(path)
-                .join(id.indexed_path())
-                .join(version.to_string())
-                .join(rev.to_string())
-                .with_added_extension("toml");
+            let path = self.package_path(id, version, rev);
 
             if !try_exists(&path).await? {
                 bail!("{id}@{version} rev {rev} does not exist");
@@ -140,7 +250,12 @@ impl Registry {
 
             let toml = read_to_string(&path).await?;
 
-            let pack = toml::from_str::<Package>(&toml)?;
+            let pack = toml::from_str::<Package>(&toml).map_err(|e| {
+                anyhow!(
+                    "invalid manifest of {id}@{version} rev {rev} at {}: {e}",
+                    path.display()
+                )
+            })?;
 
             self.cache
                 .write()
@@ -152,20 +267,17 @@ impl Registry {
             return Ok(pack);
         }
 
-        let url = self
-            .url
-            .join("package/")?
-            .join(&format!(
-                "{}/",
-                id.indexed_path().as_ref().to_str().unwrap()
-            ))?
-            .join(&format!("{version}/"))?
-            .join(&format!("{rev}.json"))?;
+        let url = self.package_url(id, version, rev)?;
 
-        let req = self.http.get(url).build()?;
-        let res = self.http.execute(req).await?;
+        let body = match fetch_json_raw(&self.http, &self.fetch, url.as_str(), None).await {
+            Err(e) if is_not_found(&e) => {
+                bail!("{id}@{version} rev {rev} does not exist in the registry")
+            }
+            res => res?,
+        };
 
-        let pack = res.json::<Package>().await?;
+        let pack = serde_json::from_slice::<Package>(&body)
+            .map_err(|e| anyhow!("invalid manifest of {id}@{version} rev {rev} at {url}: {e}"))?;
 
         self.cache
             .write()
@@ -194,6 +306,7 @@ impl Creeper {
                 let cmd = BuildIndex {
                     input: self.registry.url.path().into(),
                     output: Some(cache.join("index")),
+                    depth: self.registry.index_depth,
                 };
 
                 self.execute(cmd).await
@@ -241,3 +354,104 @@ impl Creeper {
         self.registry.get(package, version, rev).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncReadExt, net::TcpListener, sync::mpsc::unbounded_channel};
+
+    use super::*;
+
+    const MANIFEST: &str = r#"
+        id = "pack"
+        version = "1.0.0"
+
+        [package]
+        name = "Pack"
+        license = "MIT"
+        "#;
+
+    #[tokio::test]
+    async fn publish_upload() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (tx, mut req) = unbounded_channel();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut head = vec![];
+                let mut buf = [0; 1024];
+                while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    head.extend_from_slice(&buf[..n]);
+                }
+                let split = head.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+                let mut body = head.split_off(split);
+                let head = String::from_utf8(head).unwrap();
+
+                let len = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |len| len.parse::<usize>().unwrap());
+                while body.len() < len {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    body.extend_from_slice(&buf[..n]);
+                }
+
+                let line = head.lines().next().unwrap().to_owned();
+                let auth = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("authorization: "))
+                    .map(str::to_owned);
+                tx.send((line, auth, body)).unwrap();
+
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        let registry = Registry {
+            url: url.parse().unwrap(),
+            index_depth: 2,
+            http: Client::new(),
+            fetch: FetchOption::default(),
+            cache: RwLock::new(HashMap::new()),
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pack.jar");
+        tokio::fs::write(&path, "jar").await.unwrap();
+        let b3 = blake3::hash(b"jar").to_hex().to_string();
+        let art = Artifact::new(b3.clone(), "pack.jar".into(), None, 3);
+
+        let package = toml::from_str::<Package>(MANIFEST).unwrap();
+
+        // nothing is uploaded without a token
+        assert!(registry.publish(&package, &[], None).await.is_err());
+
+        registry
+            .publish(&package, &[(art, path)], Some("secret"))
+            .await
+            .unwrap();
+
+        // the artifact goes first, so that the manifest never refers to a missing file
+        let (line, auth, body) = req.recv().await.unwrap();
+        assert_eq!(line, format!("PUT /artifact/{}/{b3} HTTP/1.1", &b3[..2]));
+        assert_eq!(auth.as_deref(), Some("Bearer secret"));
+        assert_eq!(body, b"jar");
+
+        let (line, auth, body) = req.recv().await.unwrap();
+        let manifest = registry
+            .package_url(&package.id, &package.version, 0)
+            .unwrap();
+        assert_eq!(line, format!("PUT {} HTTP/1.1", manifest.path()));
+        assert_eq!(auth.as_deref(), Some("Bearer secret"));
+        let uploaded = serde_json::from_slice::<Package>(&body).unwrap();
+        assert_eq!(uploaded.id, package.id);
+        assert_eq!(uploaded.meta.license, package.meta.license);
+    }
+}
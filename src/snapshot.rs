@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use chrono::Local;
+use tokio::fs::{copy, create_dir_all, read_dir, remove_dir_all, remove_file, try_exists};
+use tracing::debug;
+use walkdir::WalkDir;
+
+use crate::Creeper;
+
+/// Recursively copy directory `src` to `dst`.
+///
+/// Worlds are modified in place by the game, so files are never linked.
+async fn copy_dir(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let path = dst.join(entry.path().strip_prefix(src)?);
+
+        if entry.file_type().is_dir() {
+            create_dir_all(&path).await?;
+        } else {
+            copy(entry.path(), &path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy all worlds in `saves`, or only `world`, into a new snapshot directory `snapshot`.
+async fn capture(saves: &Path, snapshot: &Path, world: Option<&str>) -> anyhow::Result<()> {
+    create_dir_all(snapshot).await?;
+
+    match world {
+        Some(world) => {
+            let src = saves.join(world);
+            if !try_exists(&src).await? {
+                bail!("world {world} does not exist");
+            }
+            copy_dir(&src, &snapshot.join(world)).await?;
+        }
+        None if try_exists(saves).await? => copy_dir(saves, snapshot).await?,
+        None => debug!("no worlds to snapshot"),
+    }
+
+    Ok(())
+}
+
+/// Replace worlds in `saves` with their copies in `snapshot`.
+///
+/// Entries of the snapshot that are not directories are not worlds, and skipped.
+async fn restore(snapshot: &Path, saves: &Path) -> anyhow::Result<()> {
+    let mut entries = read_dir(snapshot).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_dir() {
+            debug!("skipping non-world {}", entry.path().display());
+            continue;
+        }
+
+        let world = saves.join(entry.file_name());
+
+        if world.is_dir() {
+            remove_dir_all(&world).await?;
+        } else if try_exists(&world).await? {
+            remove_file(&world).await?;
+        }
+
+        copy_dir(&entry.path(), &world).await?;
+    }
+
+    Ok(())
+}
+
+impl Creeper {
+    async fn game_save_dir(&self) -> anyhow::Result<PathBuf> {
+        let dir = self.game_dir().await?.join("saves");
+        Ok(dir)
+    }
+
+    async fn game_snapshot_dir(&self) -> anyhow::Result<PathBuf> {
+        let dir = self.game_env_dir().await?.join("snapshot");
+        Ok(dir)
+    }
+
+    /// Copy all worlds, or only `world`, into a new timestamped snapshot.
+    ///
+    /// Returns the path to the snapshot.
+    pub async fn capture_state(&self, world: Option<&str>) -> anyhow::Result<PathBuf> {
+        let saves = self.game_save_dir().await?;
+
+        let snapshot = self
+            .game_snapshot_dir()
+            .await?
+            .join(Local::now().format("%Y%m%dT%H%M%S").to_string());
+
+        if try_exists(&snapshot).await? {
+            bail!("snapshot {} already exists", snapshot.display());
+        }
+
+        capture(&saves, &snapshot, world).await?;
+
+        debug!("captured state to {}", snapshot.display());
+
+        Ok(snapshot)
+    }
+
+    /// Replace worlds with their copies in a snapshot, the latest one if `name` is `None`.
+    ///
+    /// Worlds not present in the snapshot are left untouched.
+    /// Returns the path to the snapshot.
+    pub async fn restore_state(&self, name: Option<&str>) -> anyhow::Result<PathBuf> {
+        let dir = self.game_snapshot_dir().await?;
+
+        let snapshot = match name {
+            Some(name) => dir.join(name),
+            None => {
+                let mut latest = None;
+                if try_exists(&dir).await? {
+                    let mut entries = read_dir(&dir).await?;
+                    while let Some(entry) = entries.next_entry().await? {
+                        latest = latest.max(Some(entry.file_name()));
+                    }
+                }
+                dir.join(latest.ok_or(anyhow!("no snapshot to restore"))?)
+            }
+        };
+
+        if !try_exists(&snapshot).await? {
+            bail!("snapshot {} does not exist", snapshot.display());
+        }
+
+        let saves = self.game_save_dir().await?;
+
+        restore(&snapshot, &saves).await?;
+
+        debug!("restored state from {}", snapshot.display());
+
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+    use tokio::fs::{read_to_string, write};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn restore_captured() {
+        let dir = tempdir().unwrap();
+        let saves = dir.path().join("saves");
+        let snapshot = dir.path().join("snapshot");
+
+        create_dir_all(saves.join("world").join("region"))
+            .await
+            .unwrap();
+        write(saves.join("world").join("level.dat"), "before")
+            .await
+            .unwrap();
+        write(
+            saves.join("world").join("region").join("r.0.0.mca"),
+            "before",
+        )
+        .await
+        .unwrap();
+
+        capture(&saves, &snapshot, None).await.unwrap();
+        assert_eq!(
+            read_to_string(snapshot.join("world").join("level.dat"))
+                .await
+                .unwrap(),
+            "before"
+        );
+
+        // the game modifies the world in place
+        write(saves.join("world").join("level.dat"), "after")
+            .await
+            .unwrap();
+        write(saves.join("world").join("session.lock"), "")
+            .await
+            .unwrap();
+
+        // a stray file in the snapshot is not a world
+        write(snapshot.join("notes.txt"), "").await.unwrap();
+
+        restore(&snapshot, &saves).await.unwrap();
+
+        assert_eq!(
+            read_to_string(saves.join("world").join("level.dat"))
+                .await
+                .unwrap(),
+            "before"
+        );
+        assert!(!saves.join("world").join("session.lock").exists());
+        assert!(
+            saves
+                .join("world")
+                .join("region")
+                .join("r.0.0.mca")
+                .exists()
+        );
+        assert!(!saves.join("notes.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn capture_missing_world() {
+        let dir = tempdir().unwrap();
+        let saves = dir.path().join("saves");
+        create_dir_all(&saves).await.unwrap();
+
+        let result = capture(&saves, &dir.path().join("snapshot"), Some("world")).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,485 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use clap::Parser;
+use colored::Colorize;
+use tokio::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, try_exists};
+use toml_edit::{DocumentMut, value};
+use tracing::{info, trace};
+use walkdir::WalkDir;
+
+use crate::{
+    Creeper,
+    artifact::{CompressStat, IntegrityReport, StorageStat, StoreStat},
+    cmd::Execute,
+    util::{mv, write_atomic},
+};
+
+/// Manage the local artifact storage.
+#[derive(Clone, Debug, Parser)]
+pub enum Storage {
+    Vacuum(Vacuum),
+
+    Relocate(Relocate),
+
+    Compress(Compress),
+
+    ImportDir(ImportDir),
+
+    Verify(Verify),
+
+    Show(Show),
+
+    Stats(Stats),
+}
+
+impl Execute for Storage {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        match self {
+            Storage::Vacuum(vacuum) => lib.execute(vacuum).await,
+            Storage::Relocate(relocate) => lib.execute(relocate).await,
+            Storage::Compress(compress) => lib.execute(compress).await,
+            Storage::ImportDir(import) => lib.execute(import).await,
+            Storage::Verify(verify) => lib.execute(verify).await,
+            Storage::Show(show) => lib.execute(show).await,
+            Storage::Stats(stats) => lib.execute(stats).await,
+        }
+    }
+}
+
+/// Reclaim unused space in the artifact index database.
+#[derive(Clone, Debug, Parser)]
+pub struct Vacuum;
+
+impl Execute for Vacuum {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let (before, after) = lib.artifact.vacuum().await?;
+
+        eprintln!(
+            "{} artifact index from {:.1} KB to {:.1} KB",
+            "Compacted".bold().green(),
+            before as f64 / 1e3,
+            after as f64 / 1e3
+        );
+
+        Ok(())
+    }
+}
+
+/// Move the artifact storage and its index to another directory.
+///
+/// The new location is recorded in the config file.
+/// Game instances are re-linked to the new location on their next launch.
+#[derive(Clone, Debug, Parser)]
+pub struct Relocate {
+    /// The new storage root, which must be empty or not exist.
+    #[arg(value_name = "DIR")]
+    pub dir: PathBuf,
+}
+
+impl Execute for Relocate {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        if try_exists(&self.dir).await? && read_dir(&self.dir).await?.next_entry().await?.is_some()
+        {
+            bail!(
+                "cannot relocate storage to non-empty {}",
+                self.dir.display()
+            );
+        }
+
+        create_dir_all(&self.dir).await?;
+        let dir = self.dir.canonicalize()?;
+
+        let old = lib.artifact.storage_dir().to_owned();
+        if old.canonicalize()? == dir {
+            bail!("storage is already at {}", dir.display());
+        }
+
+        lib.artifact.close().await;
+
+        let count = move_storage(&old, &dir).await?;
+        info!("moved {count} artifacts");
+
+        let path = Creeper::config_path(&lib.args)?;
+        set_config_storage(&path, &dir).await.map_err(|e| {
+            anyhow!(
+                "storage moved to {}, but failed to update config: {e}",
+                dir.display()
+            )
+        })?;
+
+        eprintln!(
+            "{} artifact storage to {}",
+            "Relocated".bold().green(),
+            dir.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// Move the artifacts and then the index from the storage root `old` to `new`, returning the number of artifacts.
+///
+/// The index is moved last, so that an interrupted move leaves it in place with the artifacts to be re-downloaded.
+async fn move_storage(old: &Path, new: &Path) -> anyhow::Result<usize> {
+    let store = old.join("artifact");
+
+    let mut count = 0;
+
+    if try_exists(&store).await? {
+        for entry in WalkDir::new(&store) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let rel = entry.path().strip_prefix(old)?;
+            mv(entry.path(), new.join(rel)).await?;
+            count += 1;
+        }
+
+        remove_dir_all(&store).await?;
+    }
+
+    let index = old.join("artifact.db");
+    if try_exists(&index).await? {
+        mv(&index, new.join("artifact.db")).await?;
+    }
+
+    Ok(count)
+}
+
+/// Record the storage root `dir` in the config file at `path`, keeping the rest of the file as written.
+async fn set_config_storage(path: &Path, dir: &Path) -> anyhow::Result<()> {
+    let dir = dir
+        .to_str()
+        .ok_or(anyhow!("non UTF-8 path {}", dir.display()))?;
+
+    let mut config = read_to_string(path).await?.parse::<DocumentMut>()?;
+    config["storage"] = value(dir);
+
+    write_atomic(path, config.to_string()).await?;
+
+    Ok(())
+}
+
+/// Compress rarely used artifacts with zstd to save disk space.
+///
+/// Compressed artifacts are transparently decompressed when next used,
+/// at the cost of some CPU time.
+#[derive(Clone, Debug, Parser)]
+pub struct Compress {
+    /// Only compress artifacts not accessed for this many days.
+    #[arg(long, value_name = "DAYS", default_value_t = 30)]
+    pub unused_for: u64,
+}
+
+impl Execute for Compress {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let CompressStat {
+            count,
+            before,
+            after,
+        } = lib
+            .artifact
+            .compress(Duration::from_secs(self.unused_for * 24 * 60 * 60))
+            .await?;
+
+        eprintln!(
+            "{} {count} artifacts from {:.1} MB to {:.1} MB",
+            "Compressed".bold().green(),
+            before as f64 / 1e6,
+            after as f64 / 1e6
+        );
+
+        Ok(())
+    }
+}
+
+/// Store all jars in a directory, e.g. the mods of another launcher, into the artifact storage.
+///
+/// Files with identical content are stored once.
+#[derive(Clone, Debug, Parser)]
+pub struct ImportDir {
+    /// The directory to import from.
+    #[arg(value_name = "DIR")]
+    pub dir: PathBuf,
+
+    /// Also import jars in subdirectories.
+    #[arg(short, long, default_value_t = false)]
+    pub recursive: bool,
+}
+
+impl Execute for ImportDir {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let files = jars_in(&self.dir, self.recursive)?;
+
+        info!("importing {} jars from {}", files.len(), self.dir.display());
+
+        let (_, StoreStat { count, unique, new }) = lib.batch_store_artifact(files).await?;
+
+        eprintln!(
+            "{} {count} jars from {}, {unique} distinct of which {new} new",
+            "Imported".bold().green(),
+            self.dir.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// The jars directly in `dir`, or also in its subdirectories if `recursive`.
+fn jars_in(dir: &Path, recursive: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let mut walk = WalkDir::new(dir).min_depth(1);
+    if !recursive {
+        walk = walk.max_depth(1);
+    }
+
+    let mut files = vec![];
+
+    for entry in walk {
+        let entry = entry?;
+        let path = entry.path();
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        if !path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("jar"))
+        {
+            trace!("skipping non-jar {}", path.display());
+            continue;
+        }
+
+        files.push(path.to_owned());
+    }
+
+    Ok(files)
+}
+
+/// Check every stored artifact against its blake3, e.g. after a disk failure.
+///
+/// Unlike `creeper verify`, this covers the whole storage rather than the current instance.
+#[derive(Clone, Debug, Parser)]
+pub struct Verify {
+    /// Remove missing and corrupted artifacts from storage and the index,
+    /// so that they are downloaded again when next used.
+    #[arg(long, default_value_t = false)]
+    pub fix: bool,
+}
+
+impl Execute for Verify {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let IntegrityReport {
+            count,
+            missing,
+            corrupted,
+        } = lib.artifact.check_integrity().await?;
+
+        for art in &missing {
+            eprintln!("{} {art}", "Missing".bold().red());
+        }
+
+        for art in &corrupted {
+            eprintln!("{} {art}", "Corrupted".bold().red());
+        }
+
+        let problem = missing.len() + corrupted.len();
+
+        if self.fix {
+            for art in missing.iter().chain(&corrupted) {
+                lib.artifact.remove(art).await?;
+            }
+        }
+
+        eprintln!(
+            "{} {count} artifacts, {} missing and {} corrupted",
+            "Verified".bold().green(),
+            missing.len(),
+            corrupted.len()
+        );
+
+        if problem > 0 {
+            if self.fix {
+                eprintln!("{} {problem} broken artifacts", "Removed".bold().green());
+            } else {
+                bail!("found {problem} broken artifacts, use `--fix` to remove them");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Print the indexed information of stored artifacts, e.g. when they were last used.
+#[derive(Clone, Debug, Parser)]
+pub struct Show {
+    /// Name or checksum of the artifacts, or a prefix of their blake3.
+    #[arg(value_name = "QUERY")]
+    pub query: String,
+}
+
+impl Execute for Show {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let found = lib.artifact.find(&self.query).await?;
+
+        if found.is_empty() {
+            bail!("no artifact matching {} in storage", self.query);
+        }
+
+        for stored in &found {
+            let art = &stored.artifact;
+
+            println!("{}", art.to_string().bold());
+            println!("  blake3 {}", art.blake3);
+            for (func, hash) in [
+                ("sha1", &art.sha1),
+                ("sha256", &art.sha256),
+                ("md5", &art.md5),
+            ] {
+                if let Some(hash) = hash {
+                    println!("  {func} {hash}");
+                }
+            }
+            if let Some(src) = &art.src {
+                println!("  source {src}");
+            }
+            match stored.stored_len {
+                Some(stored) => println!(
+                    "  size {:.1} KB, compressed to {:.1} KB",
+                    art.len as f64 / 1e3,
+                    stored as f64 / 1e3
+                ),
+                None => println!("  size {:.1} KB", art.len as f64 / 1e3),
+            }
+            match stored.last_accessed() {
+                Some(time) => println!("  last accessed {}", time.format("%Y-%m-%d %H:%M UTC")),
+                None => println!("  last accessed never"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Print a summary of the artifact storage from its index.
+#[derive(Clone, Debug, Parser)]
+pub struct Stats;
+
+impl Execute for Stats {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let stat @ StorageStat {
+            count,
+            len,
+            compressed,
+            stored_len,
+            never_accessed,
+            ..
+        } = lib.artifact.stat().await?;
+
+        println!("artifacts       {count}");
+        println!("size            {:.1} MB", len as f64 / 1e6);
+        println!(
+            "stored size     {:.1} MB, {compressed} compressed",
+            stored_len as f64 / 1e6
+        );
+        println!("never accessed  {never_accessed}");
+        if let Some(time) = stat.oldest_access() {
+            println!("oldest access   {}", time.format("%Y-%m-%d %H:%M UTC"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::fs::write;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn move_small_storage() {
+        let old = tempfile::tempdir().unwrap();
+        let new = tempfile::tempdir().unwrap();
+
+        let art =
+            ["ab01", "ab02", "cd01"].map(|b3| PathBuf::from("artifact").join(&b3[..2]).join(b3));
+        for rel in &art {
+            create_dir_all(old.path().join(rel).parent().unwrap())
+                .await
+                .unwrap();
+            write(old.path().join(rel), rel.to_str().unwrap())
+                .await
+                .unwrap();
+        }
+        write(old.path().join("artifact.db"), "index")
+            .await
+            .unwrap();
+
+        assert_eq!(move_storage(old.path(), new.path()).await.unwrap(), 3);
+
+        for rel in &art {
+            assert_eq!(
+                read_to_string(new.path().join(rel)).await.unwrap(),
+                rel.to_str().unwrap()
+            );
+        }
+        assert!(try_exists(new.path().join("artifact.db")).await.unwrap());
+        assert!(!try_exists(old.path().join("artifact")).await.unwrap());
+        assert!(!try_exists(old.path().join("artifact.db")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn config_storage_keeps_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        write(&path, "# mirror for slow networks\nuse-bmclapi = true\n")
+            .await
+            .unwrap();
+
+        set_config_storage(&path, Path::new("/mnt/creeper"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_to_string(&path).await.unwrap(),
+            "# mirror for slow networks\nuse-bmclapi = true\nstorage = \"/mnt/creeper\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn import_dir_jars() {
+        let dir = tempfile::tempdir().unwrap();
+        create_dir_all(dir.path().join("disabled")).await.unwrap();
+        write(dir.path().join("sodium.jar"), "sodium")
+            .await
+            .unwrap();
+        write(dir.path().join("lithium.JAR"), "lithium")
+            .await
+            .unwrap();
+        write(dir.path().join("readme.txt"), "not a jar")
+            .await
+            .unwrap();
+        write(dir.path().join("disabled").join("iris.jar"), "iris")
+            .await
+            .unwrap();
+
+        let mut files = jars_in(dir.path(), false).unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                dir.path().join("lithium.JAR"),
+                dir.path().join("sodium.jar")
+            ]
+        );
+
+        assert_eq!(jars_in(dir.path(), true).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,183 @@
+use std::{collections::HashMap, env::consts, time::Duration};
+
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::{Args, Config, Creeper, Id, VersionRev};
+
+/// Loaders reported by [`Report`], other packages are never included.
+const LOADER: [&str; 3] = ["neoforge", "forge", "fabric"];
+
+/// Coarse anonymous usage sent after an installation, if opted in with [`crate::Config::telemetry`].
+///
+/// This is all that is ever sent, notably nothing about the user, the instance or other packages.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// Version of creeper itself.
+    pub creeper: &'static str,
+    /// Minecraft version installed.
+    pub minecraft: Option<String>,
+    /// Mod loader installed, e.g. `fabric`, without its version.
+    pub loader: Option<&'static str>,
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+impl Report {
+    /// Summarize the resolved dependencies of an installation.
+    pub fn new(dep: &HashMap<Id, VersionRev>) -> Self {
+        let minecraft = dep
+            .get(&Id::vanilla())
+            .or_else(|| dep.get(&Id::vanilla_server()))
+            .map(|version| version.version.to_string());
+
+        let loader = LOADER
+            .into_iter()
+            .find(|loader| dep.contains_key(&loader.parse::<Id>().unwrap()));
+
+        Self {
+            creeper: env!("CARGO_PKG_VERSION"),
+            minecraft,
+            loader,
+            os: consts::OS,
+            arch: consts::ARCH,
+        }
+    }
+}
+
+/// [`Creeper::telemetry_enabled`] under `config` and `args`.
+fn enabled(config: &Config, args: &Args) -> bool {
+    config.telemetry && config.telemetry_endpoint.is_some() && !args.no_telemetry && !args.offline
+}
+
+impl Creeper {
+    /// Whether [`Report`]s are sent, i.e. the user opted in, an endpoint is configured and not in offline mode.
+    pub fn telemetry_enabled(&self) -> bool {
+        enabled(&self.config, &self.args)
+    }
+
+    /// Send a [`Report`] to [`crate::Config::telemetry_endpoint`] if [telemetry is enabled](Self::telemetry_enabled).
+    ///
+    /// This is best effort, failures are only logged and never affect the caller.
+    pub async fn send_telemetry(&self, report: &Report) {
+        if !self.telemetry_enabled() {
+            debug!("telemetry disabled, not sending usage report");
+            return;
+        }
+
+        let Some(endpoint) = &self.config.telemetry_endpoint else {
+            return;
+        };
+
+        debug!("sending usage report {report:?} to {endpoint}");
+
+        let res = self
+            .http
+            .post(endpoint.clone())
+            .json(report)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+
+        if let Err(e) = res {
+            warn!("failed to send usage report: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::lock::Lock;
+
+    #[test]
+    fn enabled_opt_in() {
+        let on = Config {
+            telemetry: true,
+            telemetry_endpoint: Some("https://example.com/report".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(enabled(&on, &Args::default()));
+
+        // off by default
+        assert!(!enabled(&Config::default(), &Args::default()));
+
+        let no_endpoint = Config {
+            telemetry_endpoint: None,
+            ..on.clone()
+        };
+        assert!(!enabled(&no_endpoint, &Args::default()));
+
+        let no_telemetry = Args {
+            no_telemetry: true,
+            ..Default::default()
+        };
+        assert!(!enabled(&on, &no_telemetry));
+
+        let offline = Args {
+            offline: true,
+            ..Default::default()
+        };
+        assert!(!enabled(&on, &offline));
+    }
+
+    /// A lock as resolved for a fabric client with a mod and a private modpack.
+    const LOCK: &str = r#"
+        registry = "https://creeper-registry.pages.dev/"
+
+        [package]
+        vanilla = "1.20.1"
+        intermediary = "1.20.1"
+        fabric = "0.16.0"
+        sodium = "0.5.0"
+        my-secret-pack = "1.0.0"
+    "#;
+
+    #[test]
+    fn report_fields() {
+        let lock = toml::from_str::<Lock>(LOCK).unwrap();
+
+        let report = Report::new(&lock.package);
+        assert_eq!(report.minecraft.as_deref(), Some("1.20.1"));
+        assert_eq!(report.loader, Some("fabric"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        let fields = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            fields,
+            ["arch", "creeper", "loader", "minecraft", "os"]
+                .map(String::from)
+                .into()
+        );
+
+        let json = json.to_string();
+        assert!(!json.contains("sodium"));
+        assert!(!json.contains("my-secret-pack"));
+        assert!(!json.contains("0.16.0"));
+    }
+
+    #[test]
+    fn report_server() {
+        let lock = toml::from_str::<Lock>(
+            r#"
+            registry = "https://creeper-registry.pages.dev/"
+
+            [package]
+            vanilla-server = "1.21.1"
+            neoforge-server = "21.1.0"
+            "#,
+        )
+        .unwrap();
+
+        let report = Report::new(&lock.package);
+        assert_eq!(report.minecraft.as_deref(), Some("1.21.1"));
+    }
+}
@@ -8,6 +8,7 @@ use tracing::info;
 use crate::{
     Id,
     cmd::Execute,
+    id::DEFAULT_INDEX_DEPTH,
     index::{IndexLine, compile_index},
 };
 
@@ -23,45 +24,57 @@ pub struct BuildIndex {
     /// If not specified, will only perform a validation on the input.
     #[arg(short, long)]
     pub output: Option<PathBuf>,
+    /// Number of directory levels in the sparse index of the registry.
+    #[arg(long, default_value_t = DEFAULT_INDEX_DEPTH)]
+    pub depth: usize,
 }
 
 impl Execute for BuildIndex {
     async fn execute(self, _lib: &crate::Creeper) -> anyhow::Result<()> {
-        let mut read = read_dir(&self.input).await?;
-        while let Some(lv1) = read.next_entry().await? {
-            let lv1_name = lv1.file_name();
-            let lv1_name = lv1_name
-                .to_str()
-                .and_then(|s| Id::is_valid_index_lv1(s).then_some(s))
-                .ok_or(anyhow!("invalid index: {}", lv1.path().display()))?;
-            let lv1_path = lv1.path();
-            let mut read = read_dir(&lv1_path).await?;
-            while let Some(lv2) = read.next_entry().await? {
-                let lv2_name = lv2.file_name();
-                let lv2_name = lv2_name
-                    .to_str()
-                    .and_then(|s| Id::is_valid_index_lv2(s).then_some(s))
-                    .ok_or(anyhow!("invalid index: {}", lv2.path().display()))?;
-                let lv2_path = lv2.path();
-                let mut read = read_dir(&lv2_path).await?;
-                while let Some(p) = read.next_entry().await? {
-                    let id = p
-                        .file_name()
+        // directories to visit, with the index segments leading to them
+        let mut pending = vec![(self.input.clone(), vec![])];
+
+        while let Some((dir, index)) = pending.pop() {
+            let mut read = read_dir(&dir).await?;
+            while let Some(p) = read.next_entry().await? {
+                let name = p.file_name();
+
+                if index.len() < self.depth {
+                    let valid = |s: &str| {
+                        if index.is_empty() {
+                            Id::is_valid_index_lv1(s)
+                        } else {
+                            Id::is_valid_index_lv2(s)
+                        }
+                    };
+                    let name = name
                         .to_str()
-                        .ok_or(anyhow!("invalid name: {}", p.path().display()))
-                        .and_then(Id::from_str)?;
-                    if !id.is_of_index(lv1_name, lv2_name) {
-                        bail!("index and name mismatch: {}", p.path().display());
-                    }
+                        .filter(|s| valid(s))
+                        .ok_or(anyhow!("invalid index: {}", p.path().display()))?;
+                    let mut index = index.clone();
+                    index.push(name.to_owned());
+                    pending.push((p.path(), index));
+                    continue;
+                }
+
+                let id = name
+                    .to_str()
+                    .ok_or(anyhow!("invalid name: {}", p.path().display()))
+                    .and_then(Id::from_str)?;
+                let segments = index.iter().map(String::as_str).collect::<Vec<_>>();
+                if !id.is_of_index(&segments) {
+                    bail!("index and name mismatch: {}", p.path().display());
+                }
 
-                    info!("processing package {}", id);
+                info!("processing package {}", id);
 
-                    let index = compile_index(p.path()).await?;
+                let index = compile_index(p.path()).await?;
 
-                    if let Some(output) = &self.output {
-                        let output = output.join(id.indexed_path()).with_added_extension("jsonl");
-                        IndexLine::write(output, &id, index).await?;
-                    }
+                if let Some(output) = &self.output {
+                    let output = output
+                        .join(id.indexed_path_at(self.depth))
+                        .with_added_extension("jsonl");
+                    IndexLine::write(output, &id, index).await?;
                 }
             }
         }
@@ -1,10 +1,12 @@
 use clap::Parser;
 use url::Url;
 
-use crate::cmd::Execute;
+use crate::{cmd::Execute, http::fetch_sidecar};
 
 /// Download file from URL and generate artifact information.
 ///
+/// Checksums published as sidecar files like `<URL>.sha1` are included if they match the download,
+/// and ignored with a warning otherwise.
 /// This is useful for packagers.
 #[derive(Clone, Debug, Parser)]
 pub struct Download {
@@ -16,13 +18,28 @@ pub struct Download {
     /// Will use the URL if not specified.
     #[arg(long)]
     pub name: Option<String>,
+
+    /// Do not look for sidecar checksum files.
+    #[arg(long, default_value_t = false)]
+    pub no_sidecar: bool,
 }
 
 impl Execute for Download {
     async fn execute(self, lib: &crate::Creeper) -> anyhow::Result<()> {
         let name = self.name.unwrap_or(self.url.to_string());
 
-        let art = lib.download(name, self.url.to_string(), None, None).await?;
+        let sidecar = if self.no_sidecar {
+            vec![]
+        } else {
+            fetch_sidecar(&lib.http, &lib.fetch, self.url.as_str()).await?
+        };
+
+        // a wrong sidecar only loses its checksum, instead of failing the download
+        let mut art = lib
+            .download(name, self.url.to_string(), None, vec![])
+            .await?;
+
+        lib.affix_claimed(&mut art, sidecar).await?;
 
         let toml = toml::to_string_pretty(&art)?;
 
@@ -5,10 +5,13 @@ mod pack_nf_mod;
 
 mod prelude;
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
 
 use crate::{
-    Creeper, Id, YggdrasilClient,
+    Creeper, Id, McVersionExt, YggdrasilClient,
     cmd::Execute,
     id::{IdVersion, IdVersionReq},
     neoforge::{decode_neoforge_version, parse_neoforge_version},
@@ -17,7 +20,9 @@ use anyhow::{anyhow, bail};
 use clap::Parser;
 use colored::Colorize;
 use indexmap::IndexMap;
+use semver::Version;
 use stop::fatal;
+use tokio::fs::read_to_string;
 
 pub use prelude::*;
 
@@ -36,6 +41,8 @@ pub enum Tool {
 
     GetInstall(GetInstall),
 
+    DumpInstall(DumpInstall),
+
     DiscoverYggdrasil(DiscoverYggdrasil),
 
     #[command(name = "nf-version")]
@@ -46,6 +53,8 @@ pub enum Tool {
 
     Download(Download),
 
+    McVersion(McVersion),
+
     #[command(name = "pack-fabric-mod")]
     PackageFabricMod(PackageFabricMod),
 }
@@ -58,6 +67,7 @@ impl Execute for Tool {
             Tool::Resolve(resolve) => lib.execute(resolve).await,
             Tool::GetPackage(get_package) => lib.execute(get_package).await,
             Tool::GetInstall(get_install) => lib.execute(get_install).await,
+            Tool::DumpInstall(dump_install) => lib.execute(dump_install).await,
             Tool::DiscoverYggdrasil(discover_yggdrasil) => lib.execute(discover_yggdrasil).await,
             Tool::ListVersion(list_version) => lib.execute(list_version).await,
             Tool::NeoForgeVersion(nf_version) => lib.execute(nf_version).await,
@@ -65,6 +75,7 @@ impl Execute for Tool {
                 lib.execute(package_neoforge_mod).await
             }
             Tool::Download(download) => lib.execute(download).await,
+            Tool::McVersion(mc_version) => lib.execute(mc_version).await,
             Tool::PackageFabricMod(package_fabric_mod) => lib.execute(package_fabric_mod).await,
         }
     }
@@ -194,6 +205,42 @@ impl Execute for GetInstall {
     }
 }
 
+/// Print the complete installation of the current game instance as locked, as `creeper install` would deploy.
+///
+/// Unlike `get-install`, this includes the instance's own manifest and excludes disabled mods.
+#[derive(Clone, Debug, Parser)]
+pub struct DumpInstall {
+    /// Whether to pretty-print the JSON.
+    #[arg(long, default_value_t = false)]
+    pub pretty: bool,
+}
+
+impl Execute for DumpInstall {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let package = lib.game_pack().await?;
+
+        let lock = lib
+            .game_lock()
+            .await?
+            .ok_or(anyhow!("missing creeper.lock, run `creeper install` first"))?;
+
+        if !lock.satisfies(package.node.dep.clone()) {
+            bail!("creeper.lock is outdated, run `creeper install` first");
+        }
+
+        let install = lib.game_install(&package, lock.package).await?;
+
+        let json = if self.pretty {
+            serde_json::to_string_pretty(&install)?
+        } else {
+            serde_json::to_string(&install)?
+        };
+        println!("{json}");
+
+        Ok(())
+    }
+}
+
 /// List all versions available for the specified package.
 #[derive(Clone, Debug, Parser)]
 pub struct ListVersion {
@@ -263,3 +310,49 @@ impl Execute for NeoForgeVersion {
         Ok(())
     }
 }
+
+/// Print the `version.json` metadata of a minecraft version.
+#[derive(Clone, Debug, Parser)]
+pub struct McVersion {
+    /// The minecraft version.
+    #[arg(value_name = "VERSION", required_unless_present = "file")]
+    pub version: Option<Version>,
+
+    /// Read the metadata from a local file instead, e.g. a NeoForge `version.json`.
+    #[arg(long, value_name = "PATH", conflicts_with = "version")]
+    pub file: Option<PathBuf>,
+
+    /// Print the effective libraries, arguments and main class on the current platform,
+    /// after resolving `inheritsFrom` and applying rules.
+    #[arg(long, default_value_t = false)]
+    pub resolved: bool,
+}
+
+impl Execute for McVersion {
+    async fn execute(self, lib: &Creeper) -> anyhow::Result<()> {
+        let mut version: McVersionExt = match (self.version, self.file) {
+            (_, Some(file)) => serde_json::from_str(&read_to_string(file).await?)?,
+            (Some(version), None) => lib.vanilla_version(version).await?.into(),
+            (None, None) => unreachable!(),
+        };
+
+        if !self.resolved {
+            println!("{}", serde_json::to_string_pretty(&version)?);
+            return Ok(());
+        }
+
+        while let Some(parent) = version.inherits_from.clone() {
+            let parent = parent
+                .parse()
+                .map_err(|e| anyhow!("unsupported parent version {parent}: {e}"))?;
+            let parent = lib.vanilla_version(parent).await?;
+            version = version.inherit(parent.into());
+        }
+
+        let resolved = version.resolve();
+
+        println!("{}", serde_json::to_string_pretty(&resolved)?);
+
+        Ok(())
+    }
+}
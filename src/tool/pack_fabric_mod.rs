@@ -1,4 +1,3 @@
-use anyhow::anyhow;
 use clap::Parser;
 use colored::Colorize;
 use tracing::{error, info};
@@ -7,11 +6,10 @@ use url::Url;
 use crate::{
     Id, Install, Package,
     cmd::Execute,
-    fabric::FabricMod,
+    fabric::{FabricMod, meta::Environment},
     pack::{PackMeta, PackNode},
-    path::creeper_cache_dir,
     util::{parse_or_prompt, prompt_correct_license, prompt_save},
-    zip::{extract_zip, extract_zip_to},
+    zip::extract_zip,
 };
 
 #[derive(Clone, Debug, Parser)]
@@ -84,14 +82,10 @@ impl Execute for PackageFabricMod {
             error!("does not support provided dependencies in fabric.mod.json");
         }
 
-        let extract = creeper_cache_dir()?.join("extract");
-
         for jij in metadata.jars.into_iter().map(|x| x.file) {
-            let file = jij.file_name().ok_or(anyhow!("invalid filename"))?;
-
-            let path = extract.join(file);
+            let art = lib.store_zip_entry(&jar, jij).await?;
 
-            extract_zip_to(&jar, jij, &path).await?;
+            let path = lib.retrieve_artifact(&art).await?;
 
             if let Ok(json) = extract_zip(&path, "fabric.mod.json").await
                 && let Ok(metadata) = serde_json::from_str::<FabricMod>(&json)
@@ -107,6 +101,14 @@ impl Execute for PackageFabricMod {
             }
         }
 
+        let mut install = Install::default();
+
+        match metadata.environment {
+            Environment::All => install.mc_mod.push(art),
+            Environment::Client => install.mc_mod_client.push(art),
+            Environment::Server => install.mc_mod_server.push(art),
+        }
+
         let pack = Package {
             id,
             version: metadata.version,
@@ -118,10 +120,7 @@ impl Execute for PackageFabricMod {
                 desc: metadata.description,
                 license,
             },
-            install: Install {
-                mc_mod: vec![art],
-                ..Default::default()
-            },
+            install,
         };
 
         let toml = toml::to_string(&pack)?;
@@ -132,8 +131,7 @@ impl Execute for PackageFabricMod {
 
         let path = pack
             .id
-            .indexed_path()
-            .as_ref()
+            .indexed_path_at(lib.registry.index_depth)
             .join(pack.version.to_string())
             .join("0.toml");
 
@@ -173,8 +173,7 @@ impl Execute for PackageNeoforgeMod {
 
         let path = pack
             .id
-            .indexed_path()
-            .as_ref()
+            .indexed_path_at(lib.registry.index_depth)
             .join(pack.version.to_string())
             .join("0.toml");
 
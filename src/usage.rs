@@ -0,0 +1,102 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::ErrorKind,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::fs::read_to_string;
+use tracing::debug;
+
+use crate::{Id, lock::Lock, path::creeper_data_dir, util::write_atomic};
+
+/// File recording when each minecraft version was last launched, see [`record_version_use`].
+fn usage_path() -> anyhow::Result<PathBuf> {
+    let path = creeper_data_dir()?.join("version-usage.json");
+    Ok(path)
+}
+
+/// The minecraft version an instance is locked to, if any, either a client or a dedicated server.
+pub fn locked_mc_version(lock: &Lock) -> Option<String> {
+    lock.package
+        .get(&Id::vanilla())
+        .or_else(|| lock.package.get(&Id::vanilla_server()))
+        .map(|version| version.version.to_string())
+}
+
+/// Seconds since the unix epoch each minecraft version was last launched at.
+pub async fn version_usage() -> anyhow::Result<BTreeMap<String, u64>> {
+    let usage = match read_to_string(usage_path()?).await {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => Err(e)?,
+    };
+
+    Ok(usage)
+}
+
+/// Record that minecraft `version` is launched now.
+pub async fn record_version_use(version: &str) -> anyhow::Result<()> {
+    let mut usage = version_usage().await?;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    usage.insert(version.into(), now);
+
+    write_atomic(usage_path()?, serde_json::to_vec(&usage)?).await?;
+
+    debug!("recorded use of minecraft {version}");
+
+    Ok(())
+}
+
+/// Minecraft versions in `usage` launched before the `n` most recently launched ones.
+///
+/// Versions never recorded are not included, since they may have been launched before usage was recorded.
+pub fn stale_versions(usage: &BTreeMap<String, u64>, n: usize) -> BTreeSet<String> {
+    let mut usage = usage.iter().collect::<Vec<_>>();
+
+    usage.sort_by_key(|(_, time)| std::cmp::Reverse(**time));
+
+    usage
+        .into_iter()
+        .skip(n)
+        .map(|(version, _)| version.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_keep_two() {
+        let usage = [
+            ("1.19.4".into(), 100),
+            ("1.21.1".into(), 300),
+            ("1.20.1".into(), 200),
+        ]
+        .into();
+
+        assert_eq!(stale_versions(&usage, 2), ["1.19.4".into()].into());
+        assert!(stale_versions(&usage, 3).is_empty());
+        assert!(stale_versions(&BTreeMap::new(), 2).is_empty());
+    }
+
+    #[test]
+    fn locked_client_or_server() {
+        let lock = |package: &str| {
+            toml::from_str::<Lock>(&format!(
+                "registry = \"https://creeper-registry.pages.dev/\"\n[package]\n{package}"
+            ))
+            .unwrap()
+        };
+
+        let client = lock("vanilla = \"1.20.1\"\nfabric = \"0.16.0\"");
+        assert_eq!(locked_mc_version(&client).as_deref(), Some("1.20.1"));
+
+        let server = lock("vanilla-server = \"1.21.1\"");
+        assert_eq!(locked_mc_version(&server).as_deref(), Some("1.21.1"));
+
+        assert_eq!(locked_mc_version(&lock("sodium = \"0.5.0\"")), None);
+    }
+}
@@ -1,24 +1,22 @@
-use std::{
-    iter::{once, repeat_n},
-    path::PathBuf,
-};
+use std::{collections::HashMap, iter::once, path::PathBuf};
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use base64::{Engine, prelude::BASE64_STANDARD};
 use inquire::{Select, Text};
+use md5::{Digest, Md5};
 use parse_display::Display;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 use url::Url;
-use uuid::Uuid;
+use uuid::{Builder, Uuid};
 
 use crate::{
     Artifact, Checksum, Creeper, Install, YggdrasilClient, install::JavaAgent, ms::MicrosoftClient,
     path::creeper_config_dir, util::TomlFile,
 };
 
-#[derive(Clone, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize)]
 #[serde(tag = "type", deny_unknown_fields, rename_all = "kebab-case")]
 pub enum User {
     #[display("Offline Player {name}")]
@@ -45,6 +43,29 @@ pub struct UserConfig {
     pub user: Vec<User>,
 }
 
+/// UUID of an offline player as derived by vanilla Minecraft,
+/// i.e. a version 3 UUID from the MD5 hash of `OfflinePlayer:<name>`.
+pub fn offline_uuid(name: &str) -> Uuid {
+    let hash = Md5::digest(format!("OfflinePlayer:{name}"));
+    Builder::from_md5_bytes(hash.into()).into_uuid()
+}
+
+/// The first of `users` named `name`, i.e. by player name, authlib-injector account or UUID,
+/// where `ms_name` holds the known player names of Microsoft accounts.
+fn find_named<'a>(
+    users: &'a [User],
+    name: &str,
+    ms_name: &HashMap<Uuid, String>,
+) -> Option<&'a User> {
+    let is_uuid = |uuid: &Uuid| uuid.to_string() == name || uuid.simple().to_string() == name;
+
+    users.iter().find(|user| match user {
+        User::Offline { name: n } => n == name,
+        User::AuthlibInjector { account, uuid, .. } => account == name || is_uuid(uuid),
+        User::Microsoft { uuid } => is_uuid(uuid) || ms_name.get(uuid).is_some_and(|n| n == name),
+    })
+}
+
 fn config_path() -> anyhow::Result<PathBuf> {
     let path = creeper_config_dir()?.join("user.toml");
     Ok(path)
@@ -52,19 +73,24 @@ fn config_path() -> anyhow::Result<PathBuf> {
 
 pub struct UserManager {
     config: TomlFile<UserConfig>,
+    path: PathBuf,
 }
 
 impl UserManager {
-    pub fn new() -> Self {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self::at(config_path()?))
+    }
+
+    /// A user manager on the config at `path`, instead of the one of creeper.
+    fn at(path: PathBuf) -> Self {
         Self {
-            config: TomlFile::new(),
+            config: TomlFile::private(),
+            path,
         }
     }
 
     pub async fn add(&self, user: User) -> anyhow::Result<()> {
-        let path = config_path()?;
-
-        let mut config = self.config.read(&path).await?.unwrap_or_default();
+        let mut config = self.config.read(&self.path).await?.unwrap_or_default();
 
         if config.default.as_ref().is_some_and(|x| *x == user) || config.user.contains(&user) {
             warn!("{user} already exists in the config");
@@ -73,9 +99,53 @@ impl UserManager {
 
         config.user.push(user);
 
-        self.config.write(&path, Some(config)).await?;
+        self.save(config).await
+    }
 
-        Ok(())
+    /// The configured users.
+    pub async fn list(&self) -> anyhow::Result<UserConfig> {
+        let config = self.config.read(&self.path).await?.unwrap_or_default();
+        Ok(config)
+    }
+
+    /// Remove a user, which may be the default one.
+    pub async fn remove(&self, user: &User) -> anyhow::Result<()> {
+        let mut config = self.list().await?;
+
+        if config.default.as_ref() == Some(user) {
+            config.default = None;
+        } else if let Some(idx) = config.user.iter().position(|x| x == user) {
+            config.user.remove(idx);
+        } else {
+            bail!("{user} does not exist in the config");
+        }
+
+        self.save(config).await
+    }
+
+    /// Make a configured user the default, used when launching without choosing one.
+    pub async fn set_default(&self, user: User) -> anyhow::Result<()> {
+        let mut config = self.list().await?;
+
+        if config.default.as_ref() == Some(&user) {
+            return Ok(());
+        }
+
+        let idx = config
+            .user
+            .iter()
+            .position(|x| *x == user)
+            .ok_or(anyhow!("{user} does not exist in the config"))?;
+
+        config.user.remove(idx);
+        config.user.extend(config.default.take());
+        config.default = Some(user);
+
+        self.save(config).await
+    }
+
+    async fn save(&self, config: UserConfig) -> anyhow::Result<()> {
+        self.config.write(&self.path, Some(config)).await
     }
 }
 
@@ -100,6 +170,20 @@ impl Creeper {
 
         client.prompt_login().await?;
 
+        self.add_microsoft_user(client).await
+    }
+
+    /// Like [`Self::prompt_new_microsoft_user`], but log in with a device code instead of a local browser.
+    pub async fn prompt_new_microsoft_user_device(&self) -> anyhow::Result<User> {
+        let client = MicrosoftClient::new(self.http.clone())?;
+
+        client.prompt_login_device().await?;
+
+        self.add_microsoft_user(client).await
+    }
+
+    /// Finish login of a Microsoft account authorized with OAuth, and add it to the config.
+    async fn add_microsoft_user(&self, client: MicrosoftClient) -> anyhow::Result<User> {
         if !client.owns_minecraft().await? {
             bail!("the Microsoft account does not own Minecraft, please purchase it first");
         }
@@ -118,6 +202,14 @@ impl Creeper {
     pub async fn prompt_new_offline_user(&self) -> anyhow::Result<User> {
         let name = Text::new("Player name:").prompt()?;
 
+        self.new_offline_user(name).await
+    }
+
+    pub async fn new_offline_user(&self, name: String) -> anyhow::Result<User> {
+        if name.trim().is_empty() {
+            bail!("player name cannot be empty");
+        }
+
         let user = User::Offline { name };
 
         self.user.add(user.clone()).await?;
@@ -170,9 +262,7 @@ impl Creeper {
     }
 
     pub async fn prompt_select_user(&self) -> anyhow::Result<User> {
-        let path = config_path()?;
-
-        let config = self.user.config.read(path).await?.unwrap_or_default();
+        let config = self.user.list().await?;
 
         let users = config
             .default
@@ -190,13 +280,68 @@ impl Creeper {
         Ok(select)
     }
 
+    /// The player name of a Microsoft account as cached in its session, if any.
+    async fn microsoft_user_cached_name(&self, uuid: Uuid) -> Option<String> {
+        let client = MicrosoftClient::new(self.http.clone()).ok()?;
+        client.set_uuid(uuid).await;
+        client.load().await.ok()?;
+        client.cached_mc_name().await
+    }
+
+    async fn microsoft_user_name(&self, uuid: Uuid) -> anyhow::Result<String> {
+        let client = MicrosoftClient::new(self.http.clone())?;
+        client.set_uuid(uuid).await;
+        client.load().await?;
+        client.get_mc_name().await
+    }
+
+    /// Find a configured user by name, i.e. the player name, authlib-injector account or UUID.
+    ///
+    /// Names of Microsoft accounts are matched as cached in their sessions first,
+    /// and only fetched from the server for accounts without one if nothing matches.
+    pub async fn find_user(&self, name: &str) -> anyhow::Result<User> {
+        let config = self.user.list().await?;
+        let users = config
+            .default
+            .into_iter()
+            .chain(config.user)
+            .collect::<Vec<_>>();
+
+        let mut ms_name = HashMap::new();
+        let mut uncached = vec![];
+
+        for user in &users {
+            if let User::Microsoft { uuid } = user {
+                match self.microsoft_user_cached_name(*uuid).await {
+                    Some(n) => {
+                        ms_name.insert(*uuid, n);
+                    }
+                    None => uncached.push(*uuid),
+                }
+            }
+        }
+
+        if let Some(user) = find_named(&users, name, &ms_name) {
+            return Ok(user.clone());
+        }
+
+        for uuid in uncached {
+            match self.microsoft_user_name(uuid).await {
+                Ok(n) => {
+                    ms_name.insert(uuid, n);
+                }
+                Err(e) => warn!("cannot get the player name of Microsoft account {uuid}: {e}"),
+            }
+        }
+
+        match find_named(&users, name, &ms_name) {
+            Some(user) => Ok(user.clone()),
+            None => bail!("no user named {name} in config, add it with `creeper login`"),
+        }
+    }
+
     pub async fn prompt_decide_user(&self) -> anyhow::Result<User> {
-        let config = self
-            .user
-            .config
-            .read(config_path()?)
-            .await?
-            .unwrap_or_default();
+        let config = self.user.list().await?;
 
         if let Some(user) = config.default {
             return Ok(user);
@@ -206,13 +351,7 @@ impl Creeper {
     }
 
     fn user_install_offline(&self, name: String) -> anyhow::Result<Install> {
-        let uuid = format!("OfflinePlayer: {name}");
-
-        // to ensure sufficient length
-        let uuid = uuid + &repeat_n('\0', 16).collect::<String>();
-        let uuid = &uuid[..16];
-
-        let uuid = Uuid::from_slice(uuid.as_bytes())?;
+        let uuid = offline_uuid(&name);
 
         let install = Install {
             mc_flag: vec![
@@ -234,9 +373,15 @@ impl Creeper {
         client.set_uuid(uuid).await;
         client.load().await?;
 
+        // refreshed if expired, so that a stale session fails here instead of inside the game
+        let token = client.get_mc_jwt().await.map_err(|e| {
+            anyhow!(
+                "failed to refresh Microsoft session of {uuid}, please log in again with `creeper login`: {e}"
+            )
+        })?;
+
         let uuid = client.get_mc_uuid().await?;
         let name = client.get_mc_name().await?;
-        let token = client.get_mc_jwt().await?;
 
         client.save().await?;
 
@@ -333,8 +478,13 @@ impl Creeper {
         Ok(art)
     }
 
-    pub async fn user_install(&self) -> anyhow::Result<Install> {
-        let user = self.prompt_decide_user().await?;
+    /// Installation logging in as the user named `name` as in [`Self::find_user`],
+    /// or the default user if not specified.
+    pub async fn user_install(&self, name: Option<&str>) -> anyhow::Result<Install> {
+        let user = match name {
+            Some(name) => self.find_user(name).await?,
+            None => self.prompt_decide_user().await?,
+        };
 
         let install = match user {
             User::Offline { name } => self.user_install_offline(name)?,
@@ -387,3 +537,95 @@ struct AuthlibInjectorVersion {
 
 //     let version = serde_json::from_str::<AuthlibInjectorVersion>(json).unwrap();
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuid_vanilla() {
+        // as computed by vanilla `UUIDUtil.createOfflinePlayerUUID`
+        assert_eq!(
+            offline_uuid("Notch").to_string(),
+            "b50ad385-829d-3141-a216-7e7d7539ba7f"
+        );
+        assert_eq!(offline_uuid("Notch").get_version_num(), 3);
+        assert_ne!(offline_uuid("notch"), offline_uuid("Notch"));
+    }
+
+    #[test]
+    fn find_user_by_name() {
+        let steve = Uuid::from_u128(1);
+        let alex = Uuid::from_u128(2);
+        let users = [
+            User::Offline {
+                name: "Notch".into(),
+            },
+            User::Microsoft { uuid: steve },
+            User::Microsoft { uuid: alex },
+            User::AuthlibInjector {
+                server: "https://example.com/api/yggdrasil/".parse().unwrap(),
+                account: "jeb@example.com".into(),
+                uuid: Uuid::from_u128(3),
+            },
+        ];
+        let ms_name = HashMap::from([(steve, "Steve".to_string())]);
+
+        let find = |name| find_named(&users, name, &ms_name);
+
+        assert_eq!(find("Notch"), Some(&users[0]));
+        assert_eq!(find("Steve"), Some(&users[1]));
+        let (hyphenated, simple) = (alex.to_string(), alex.simple().to_string());
+        assert_eq!(find(&hyphenated), Some(&users[2]));
+        assert_eq!(find(&simple), Some(&users[2]));
+        assert_eq!(find("jeb@example.com"), Some(&users[3]));
+
+        // not cached, so left to be fetched
+        assert_eq!(find("Alex"), None);
+    }
+
+    #[tokio::test]
+    async fn account_add_default_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("user.toml");
+        let manager = UserManager::at(path.clone());
+
+        let notch = User::Offline {
+            name: "Notch".into(),
+        };
+        let steve = User::Microsoft {
+            uuid: Uuid::from_u128(1),
+        };
+
+        manager.add(notch.clone()).await.unwrap();
+        manager.add(steve.clone()).await.unwrap();
+        // adding again is ignored
+        manager.add(notch.clone()).await.unwrap();
+
+        let config = manager.list().await.unwrap();
+        assert_eq!(config.default, None);
+        assert_eq!(config.user, [notch.clone(), steve.clone()]);
+
+        manager.set_default(steve.clone()).await.unwrap();
+        manager.set_default(notch.clone()).await.unwrap();
+        let config = manager.list().await.unwrap();
+        assert_eq!(config.default, Some(notch.clone()));
+        assert_eq!(config.user, std::slice::from_ref(&steve));
+
+        manager.remove(&notch).await.unwrap();
+        let config = manager.list().await.unwrap();
+        assert_eq!(config.default, None);
+        assert_eq!(config.user, std::slice::from_ref(&steve));
+
+        assert!(manager.remove(&notch).await.is_err());
+        assert!(manager.set_default(notch).await.is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     collections::BTreeSet,
     fmt::Display,
     marker::PhantomData,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     str::FromStr,
     sync::OnceLock,
 };
@@ -17,19 +17,35 @@ use semver::{Version, VersionReq};
 use serde::{Serialize, de::DeserializeOwned};
 use tokio::{
     fs::{
-        File, copy, create_dir_all, metadata, read_to_string, remove_dir_all, remove_file, rename,
+        copy, create_dir_all, metadata, read_to_string, remove_dir_all, remove_file, rename,
         set_permissions, try_exists, write,
     },
     sync::RwLock,
     task::spawn_blocking,
 };
+use toml_edit::DocumentMut;
 use tracing::{info, trace};
 
+/// Create a directory and its parents, tolerating concurrent creation of the same directory.
+///
+/// [`create_dir_all`] may still fail with [`std::io::ErrorKind::AlreadyExists`] on some platforms
+/// when another task is creating the directory, e.g. sibling artifacts stored under the same prefix.
+pub async fn ensure_dir(dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let dir = dir.as_ref();
+    match create_dir_all(dir).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && dir.is_dir() => Ok(()),
+        Err(e) => Err(e)?,
+    }
+}
+
+/// Move a file, falling back to copy-and-remove across devices.
+///
+/// Within a single device the destination is replaced atomically.
 pub async fn mv(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> anyhow::Result<()> {
     if let Some(parent) = dst.as_ref().parent() {
-        create_dir_all(parent).await?;
+        ensure_dir(parent).await?;
     }
-    File::create(&dst).await?;
 
     let rename = rename(&src, &dst).await;
     match rename {
@@ -42,6 +58,52 @@ pub async fn mv(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> anyhow::Result<
     Ok(())
 }
 
+/// Write a file, creating parent directories if necessary.
+///
+/// The content is written to a uniquely named temporary file next to `path` first and then moved into place,
+/// so that an interrupted write never leaves a truncated file behind,
+/// and concurrent writers never share a temporary file.
+pub async fn write_atomic(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    persist_new(path.as_ref(), content.as_ref(), false).await
+}
+
+/// Write `content` to a new temporary file next to `path` and move it into place,
+/// with mode `0600` if `private`, otherwise as permitted by the umask.
+async fn persist_new(path: &Path, content: &[u8], private: bool) -> anyhow::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+        _ => PathBuf::from("."),
+    };
+
+    create_dir_all(&dir).await?;
+
+    let path = path.to_owned();
+    let content = content.to_vec();
+
+    spawn_blocking(move || {
+        let mut builder = tempfile::Builder::new();
+        builder.suffix(".tmp");
+
+        // temporary files are private by default
+        #[cfg(unix)]
+        if !private {
+            use std::os::unix::fs::PermissionsExt;
+
+            builder.permissions(std::fs::Permissions::from_mode(0o666));
+        }
+        #[cfg(not(unix))]
+        let _ = private;
+
+        let mut tmp = builder.tempfile_in(&dir)?;
+        std::io::Write::write_all(&mut tmp, &content)?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(&path)?;
+
+        anyhow::Ok(())
+    })
+    .await?
+}
+
 pub async fn set_readonly(path: impl AsRef<Path>) -> anyhow::Result<()> {
     let path = path.as_ref();
 
@@ -57,6 +119,84 @@ pub async fn set_readonly(path: impl AsRef<Path>) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Parse permissions of stored files in octal notation, e.g. `0444` or `0o444`.
+///
+/// Modes with any write bit are rejected, because deployed links would let instances modify the storage.
+pub fn parse_mode(mode: &str) -> anyhow::Result<u32> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    match u32::from_str_radix(digits, 8) {
+        Ok(m) if m & 0o222 != 0 => {
+            bail!("file mode {mode} of stored files must not be writable, try 0444")
+        }
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => bail!("invalid file mode {mode}, expected octal like 0444"),
+    }
+}
+
+/// Set the permissions of a file to `mode`, e.g. `0o444`.
+///
+/// This does nothing on non-unix platforms, which have no such permissions.
+pub async fn set_mode(path: impl AsRef<Path>, mode: u32) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = path.as_ref();
+
+        set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+
+        trace!("set {} to mode {mode:o}", path.display());
+    }
+
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+
+    Ok(())
+}
+
+/// Make a file executable by whoever can read it, like `chmod +x` but respecting the read bits.
+///
+/// Files already executable are left untouched.
+/// This does nothing on non-unix platforms, which have no such permissions.
+pub async fn set_executable(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = path.as_ref();
+
+        let mode = metadata(path).await?.permissions().mode() & 0o7777;
+        let exec = mode | (mode & 0o444) >> 2;
+
+        if exec != mode {
+            set_mode(path, exec).await?;
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Like [`write_atomic`], but the file is only accessible by its owner, i.e. mode `0600`, as for credentials.
+///
+/// The file is created with these permissions before any content is written,
+/// so that the content is never exposed to other users, not even in the temporary file.
+/// On non-unix platforms, files in the user directory are already private.
+pub async fn write_private(
+    path: impl AsRef<Path>,
+    content: impl AsRef<[u8]>,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+
+    persist_new(path, content.as_ref(), true).await?;
+
+    trace!("wrote {} privately", path.display());
+
+    Ok(())
+}
+
 /// Prompt the user to confirm the removal of a file or directory, and remove it if confirmed.
 pub async fn prompt_remove(path: impl AsRef<Path>) -> anyhow::Result<()> {
     let path = path.as_ref();
@@ -74,6 +214,8 @@ where
     T: Clone + Serialize + DeserializeOwned,
 {
     cache: RwLock<OnceLock<Option<T>>>,
+    header: Option<String>,
+    private: bool,
 }
 
 impl<T> TomlFile<T>
@@ -83,15 +225,40 @@ where
     pub fn new() -> Self {
         Self {
             cache: RwLock::new(OnceLock::new()),
+            header: None,
+            private: false,
+        }
+    }
+
+    /// Like [`TomlFile::new`], but write files with [`write_private`], e.g. those holding credentials.
+    pub fn private() -> Self {
+        Self {
+            private: true,
+            ..Self::new()
+        }
+    }
+
+    /// Like [`TomlFile::new`], but prefix every written file with `header` as TOML comments.
+    pub fn with_header(header: &str) -> Self {
+        Self {
+            cache: RwLock::new(OnceLock::new()),
+            header: Some(header.to_owned()),
+            private: false,
         }
     }
 
+    /// Read the value from file, or `None` if the file does not exist.
+    ///
+    /// A path of `-` reads from the standard input instead.
     pub async fn read(&self, path: impl AsRef<Path>) -> anyhow::Result<Option<T>> {
         if let Some(value) = self.cache.read().await.get() {
             return Ok(value.clone());
         }
 
-        let value = if try_exists(&path).await? {
+        let value = if path.as_ref() == Path::new("-") {
+            let toml = spawn_blocking(|| std::io::read_to_string(std::io::stdin())).await??;
+            Some(toml::from_str(&toml)?)
+        } else if try_exists(&path).await? {
             let toml = read_to_string(&path).await?;
             Some(toml::from_str(&toml)?)
         } else {
@@ -103,19 +270,28 @@ where
         Ok(value)
     }
 
+    /// Write the value to file with [`write_atomic`], or [`write_private`] if [private](Self::private), or remove the file if `None`.
     pub async fn write(&self, path: impl AsRef<Path>, value: Option<T>) -> anyhow::Result<()> {
         let path = path.as_ref();
 
+        if path == Path::new("-") {
+            bail!("cannot write to a file read from standard input");
+        }
+
         *self.cache.write().await = value.clone().into();
 
         if let Some(value) = value {
-            let toml = toml::to_string(&value)?;
+            let mut toml = toml::to_string(&value)?;
 
-            if let Some(parent) = path.parent() {
-                create_dir_all(parent).await?;
+            if let Some(header) = &self.header {
+                toml = prepend_comment(&toml, header)?;
             }
 
-            write(path, toml).await?;
+            if self.private {
+                write_private(path, toml).await?;
+            } else {
+                write_atomic(path, toml).await?;
+            }
         } else {
             if try_exists(path).await? {
                 remove_file(path).await?;
@@ -124,6 +300,44 @@ where
 
         Ok(())
     }
+
+    /// Write TOML text to file as is, e.g. edited with `toml_edit` to keep comments.
+    ///
+    /// The text is parsed first, so that the cache stays consistent with the file.
+    pub async fn write_str(&self, path: impl AsRef<Path>, toml: &str) -> anyhow::Result<()> {
+        let path = path.as_ref();
+
+        if path == Path::new("-") {
+            bail!("cannot write to a file read from standard input");
+        }
+
+        let value = toml::from_str(toml)?;
+        *self.cache.write().await = Some(value).into();
+
+        if self.private {
+            write_private(path, toml).await?;
+        } else {
+            write_atomic(path, toml).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prefix the TOML document `toml` with each line of `comment` as a comment, followed by a blank line.
+fn prepend_comment(toml: &str, comment: &str) -> anyhow::Result<String> {
+    let mut doc = toml.parse::<DocumentMut>()?;
+
+    let comment = comment
+        .lines()
+        .map(|line| format!("# {line}\n"))
+        .collect::<String>();
+
+    doc.as_table_mut()
+        .decor_mut()
+        .set_prefix(format!("{comment}\n"));
+
+    Ok(doc.to_string())
 }
 
 pub async fn prompt_valid<T>(message: &str) -> anyhow::Result<T>
@@ -389,6 +603,52 @@ pub fn skip_two<T>(skip: impl Fn(&T) -> bool, it: impl IntoIterator<Item = T>) -
     keep
 }
 
+/// Check that `path` stays inside the directory it is joined onto,
+/// i.e. it is relative and never escapes with `..`.
+pub fn check_relative(path: &Path) -> anyhow::Result<()> {
+    for component in path.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            _ => bail!("path {} escapes its target directory", path.display()),
+        }
+    }
+    Ok(())
+}
+
+/// Check that `path` is inside one of the directories `roots`,
+/// comparing lexically after making them absolute, so that `..` cannot escape.
+pub fn check_within(path: &Path, roots: &[PathBuf]) -> anyhow::Result<()> {
+    let normalized = normalize(&std::path::absolute(path)?);
+
+    for root in roots {
+        if normalized.starts_with(normalize(&std::path::absolute(root)?)) {
+            return Ok(());
+        }
+    }
+
+    bail!(
+        "path {} is outside the directories managed by creeper",
+        path.display()
+    )
+}
+
+/// Resolve `.` and `..` in `path` lexically, without following soft links.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
 /// Summarize a string into a shorter valid filename.
 ///
 /// While hashing a string directly also feasible for the purpose of generating a filename,
@@ -406,3 +666,91 @@ pub fn summarize(name: &str) -> String {
 
     format!("{}-{}", &hash[..8], &base64[..64.min(base64.len())])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deploy_path_escape() {
+        assert!(check_relative(Path::new("mods/sodium.jar")).is_ok());
+        assert!(check_relative(Path::new("../escape")).is_err());
+        assert!(check_relative(Path::new("mods/../../escape")).is_err());
+        assert!(check_relative(Path::new("/etc/passwd")).is_err());
+
+        let roots = [PathBuf::from("/home/steve/.minecraft")];
+        assert!(check_within(Path::new("/home/steve/.minecraft/.creeper/lib"), &roots).is_ok());
+        assert!(check_within(Path::new("/home/steve/.minecraft/../escape"), &roots).is_err());
+        assert!(check_within(Path::new("/home/steve/.minecraft-evil"), &roots).is_err());
+        assert!(check_within(Path::new("/etc"), &roots).is_err());
+    }
+
+    #[test]
+    fn store_mode() {
+        assert_eq!(parse_mode("0444").unwrap(), 0o444);
+        assert_eq!(parse_mode("0o440").unwrap(), 0o440);
+        assert_eq!(parse_mode("555").unwrap(), 0o555);
+
+        assert!(parse_mode("0644").is_err());
+        assert!(parse_mode("0666").is_err());
+        assert!(parse_mode("0o4200").is_err());
+        assert!(parse_mode("0888").is_err());
+        assert!(parse_mode("07777").is_err());
+        assert!(parse_mode("17444").is_err());
+    }
+
+    #[tokio::test]
+    async fn atomic_write_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("creeper.lock");
+
+        // a directory in the way cannot be replaced
+        create_dir_all(path.join("sub")).await.unwrap();
+
+        assert!(write_atomic(&path, "new").await.is_err());
+        assert!(path.join("sub").is_dir());
+
+        // the temporary file is not left behind
+        let mut entries = std::fs::read_dir(dir.path()).unwrap();
+        assert_eq!(entries.next().unwrap().unwrap().path(), path);
+        assert!(entries.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn atomic_write_concurrent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("creeper.lock");
+
+        let content = (0..16)
+            .map(|i| i.to_string().repeat(4096))
+            .collect::<Vec<_>>();
+
+        futures::future::try_join_all(content.iter().map(|c| write_atomic(&path, c)))
+            .await
+            .unwrap();
+
+        // one of the writers wins as a whole
+        assert!(content.contains(&read_to_string(&path).await.unwrap()));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn private_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credential.json");
+
+        // replacing a file with loose permissions
+        write(&path, "").await.unwrap();
+        set_mode(&path, 0o666).await.unwrap();
+
+        write_private(&path, "secret").await.unwrap();
+
+        let mode = metadata(&path).await.unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(read_to_string(&path).await.unwrap(), "secret");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
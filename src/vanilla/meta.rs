@@ -2,20 +2,27 @@ use std::{collections::HashMap, iter::once, path::Path};
 
 use crate::{
     Checksum, Creeper, Install, VERSION,
+    install::FeatureFlag,
+    java::major_java_req,
     util::skip_two,
-    vanilla::{RuleChecker, java_module_path},
+    vanilla::{RuleChecker, filter_lib, java_module_path},
 };
 
 use mc_launchermeta::version as mc_version;
+use semver::VersionReq;
 use serde::{Deserialize, Serialize};
 
 /// The (extended) Minecraft launcher `version.json` metadata.
 ///
 /// This is a superset of `mc_launchermeta::version::Version`,
-/// and is made compatible with the format used by NeoForge.
+/// and is made compatible with the format used by NeoForge and Forge.
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct McVersionExt {
+    /// Notes left by Forge.
+    #[serde(default, rename = "_comment_", skip_serializing_if = "Vec::is_empty")]
+    pub comment: Vec<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub inherits_from: Option<String>,
 
@@ -40,7 +47,7 @@ pub struct McVersionExt {
     pub id: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub java_version: Option<mc_version::JavaVersion>,
+    pub java_version: Option<JavaVersionExt>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub libraries: Vec<mc_version::library::Library>,
@@ -64,6 +71,7 @@ pub struct McVersionExt {
 impl From<mc_version::Version> for McVersionExt {
     fn from(value: mc_version::Version) -> Self {
         Self {
+            comment: vec![],
             inherits_from: None,
             arguments: value.arguments,
             minecraft_arguments: value.minecraft_arguments,
@@ -72,7 +80,10 @@ impl From<mc_version::Version> for McVersionExt {
             compliance_level: value.compliance_level,
             downloads: Some(value.downloads),
             id: value.id,
-            java_version: value.java_version,
+            java_version: value.java_version.map(|j| JavaVersionExt {
+                component: Some(j.component),
+                major_version: j.major_version,
+            }),
             libraries: value.libraries,
             logging: value.logging,
             main_class: value.main_class,
@@ -84,8 +95,128 @@ impl From<mc_version::Version> for McVersionExt {
     }
 }
 
+/// The `javaVersion` of [`McVersionExt`], where `component` is optional for NeoForge.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct JavaVersionExt {
+    /// Name of the Mojang java runtime, e.g. `java-runtime-gamma`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+
+    pub major_version: u8,
+}
+
+impl JavaVersionExt {
+    /// Name of the Mojang java runtime to use,
+    /// guessed from the major version if [`Self::component`] is missing.
+    pub fn runtime_component(&self) -> String {
+        if let Some(component) = &self.component {
+            return component.clone();
+        }
+
+        let component = match self.major_version {
+            ..=8 => "jre-legacy",
+            9..=16 => "java-runtime-alpha",
+            17..=20 => "java-runtime-gamma",
+            21..=24 => "java-runtime-delta",
+            25.. => "java-runtime-epsilon",
+        };
+
+        component.into()
+    }
+}
+
+impl McVersionExt {
+    /// Merge with the version this inherits from, see [`Self::inherits_from`].
+    ///
+    /// Libraries and arguments of `parent` are appended,
+    /// while other fields are taken from `parent` only if missing from `self`.
+    pub fn inherit(self, parent: McVersionExt) -> Self {
+        let mut libraries = self.libraries;
+        libraries.extend(parent.libraries);
+
+        let arguments = match (self.arguments, parent.arguments) {
+            (Some(child), Some(mut parent)) => {
+                parent.game.extend(child.game);
+                parent.jvm.extend(child.jvm);
+                Some(parent)
+            }
+            (child, parent) => child.or(parent),
+        };
+
+        Self {
+            comment: self.comment,
+            inherits_from: parent.inherits_from,
+            arguments,
+            minecraft_arguments: self.minecraft_arguments.or(parent.minecraft_arguments),
+            asset_index: self.asset_index.or(parent.asset_index),
+            assets: self.assets.or(parent.assets),
+            compliance_level: self.compliance_level.or(parent.compliance_level),
+            downloads: self.downloads.or(parent.downloads),
+            id: self.id,
+            java_version: self.java_version.or(parent.java_version),
+            libraries,
+            logging: self.logging.or(parent.logging),
+            main_class: self.main_class,
+            minimum_launcher_version: self
+                .minimum_launcher_version
+                .or(parent.minimum_launcher_version),
+            release_time: self.release_time,
+            time: self.time,
+            kind: self.kind,
+        }
+    }
+
+    /// Apply the rules of this version on the current platform, without downloading anything.
+    ///
+    /// Note that inheritance has to be resolved beforehand with [`McVersionExt::inherit`].
+    pub fn resolve(&self) -> ResolvedMcVersion {
+        let mut library = filter_lib(self.libraries.clone())
+            .into_iter()
+            .map(|a| a.path)
+            .collect::<Vec<_>>();
+        library.sort();
+
+        let (java_flag, mc_flag) = match (&self.arguments, &self.minecraft_arguments) {
+            (Some(args), _) => {
+                let install = Creeper::vanilla_args_install(args, &self.id);
+                (install.java_flag, install.mc_flag)
+            }
+            (None, Some(args)) => (vec![], args.split_whitespace().map(str::to_owned).collect()),
+            (None, None) => (vec![], vec![]),
+        };
+
+        ResolvedMcVersion {
+            id: self.id.clone(),
+            main_class: self.main_class.clone(),
+            java_version: self.java_version.as_ref().map(|j| j.major_version),
+            java_runtime: self
+                .java_version
+                .as_ref()
+                .map(JavaVersionExt::runtime_component),
+            library,
+            java_flag,
+            mc_flag,
+        }
+    }
+}
+
+/// The effective launch data of a version after rule filtering, for debugging.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ResolvedMcVersion {
+    pub id: String,
+    pub main_class: String,
+    pub java_version: Option<u8>,
+    pub java_runtime: Option<String>,
+    /// Paths of the libraries applicable to the current platform.
+    pub library: Vec<String>,
+    pub java_flag: Vec<String>,
+    pub mc_flag: Vec<String>,
+}
+
 impl Creeper {
-    fn vanilla_args_install(&self, args: &mc_version::Arguments, version_name: &str) -> Install {
+    fn vanilla_args_install(args: &mc_version::Arguments, version_name: &str) -> Install {
         let rule = RuleChecker::default();
 
         let version_type = format!("creeper {VERSION}");
@@ -144,9 +275,27 @@ impl Creeper {
             .map(|x| shellexpand::env_with_context_no_errors(x, |k| vars.get(k)).to_string())
             .collect();
 
+        // placeholders of launch options like `${resolution_width}` are kept for substitution when launching
+        let mc_flag_feature = args
+            .game
+            .iter()
+            .filter_map(|a| {
+                let feature = RuleChecker::feature_only(&a.rules)?;
+                let flag = a
+                    .values
+                    .iter()
+                    .map(|x| {
+                        shellexpand::env_with_context_no_errors(x, |k| vars.get(k)).to_string()
+                    })
+                    .collect();
+                Some(FeatureFlag { feature, flag })
+            })
+            .collect();
+
         Install {
             java_flag,
             mc_flag,
+            mc_flag_feature,
             ..Default::default()
         }
     }
@@ -172,6 +321,13 @@ impl Creeper {
             }));
         }
 
+        let native = self.vanilla_native(version.libraries.clone()).await?;
+
+        install.extend(once(Install {
+            native,
+            ..Default::default()
+        }));
+
         let lib = self.vanilla_lib(version.libraries).await?;
 
         let java_args = version
@@ -200,7 +356,7 @@ impl Creeper {
         }
 
         if let Some(args) = version.arguments {
-            let arg = self.vanilla_args_install(&args, &version.id);
+            let arg = Self::vanilla_args_install(&args, &version.id);
 
             install.extend(once(arg));
         }
@@ -209,9 +365,101 @@ impl Creeper {
             java_lib_class: lib,
             java_lib_mod,
             java_main_class: Some(version.main_class),
+            require_java: version
+                .java_version
+                .as_ref()
+                .map(|j| major_java_req(j.major_version))
+                .unwrap_or(VersionReq::STAR),
+            java_runtime: version
+                .java_version
+                .as_ref()
+                .map(JavaVersionExt::runtime_component),
             ..Default::default()
         }));
 
         Ok(install)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env::consts::OS;
+
+    use super::*;
+
+    /// A version with a library and a JVM flag for each of the current and another platform.
+    fn version_json() -> String {
+        let current = match OS {
+            "macos" => "osx",
+            os => os,
+        };
+        let other = if current == "windows" {
+            "linux"
+        } else {
+            "windows"
+        };
+
+        let library = |name: &str, os: &str| {
+            format!(
+                r#"{{
+                    "name": "org.example:{name}:1.0",
+                    "downloads": {{
+                        "artifact": {{ "path": "{name}.jar", "sha1": "{name}", "size": 0, "url": "https://example.com/{name}.jar" }}
+                    }},
+                    "rules": [{{ "action": "allow", "os": {{ "name": "{os}" }} }}]
+                }}"#
+            )
+        };
+
+        format!(
+            r#"{{
+                "id": "1.20.1",
+                "mainClass": "net.minecraft.client.main.Main",
+                "releaseTime": "2023-06-12T13:25:51+00:00",
+                "time": "2023-06-12T13:25:51+00:00",
+                "type": "release",
+                "javaVersion": {{ "component": "java-runtime-gamma", "majorVersion": 17 }},
+                "libraries": [{}, {}],
+                "arguments": {{
+                    "game": ["--version", "${{version_name}}"],
+                    "jvm": [
+                        {{ "rules": [{{ "action": "allow", "os": {{ "name": "{current}" }} }}], "value": "-Dcurrent" }},
+                        {{ "rules": [{{ "action": "allow", "os": {{ "name": "{other}" }} }}], "value": "-Dother" }}
+                    ]
+                }}
+            }}"#,
+            library("current", current),
+            library("other", other)
+        )
+    }
+
+    #[test]
+    fn resolved_rules() {
+        let version = serde_json::from_str::<McVersionExt>(&version_json()).unwrap();
+
+        let resolved = version.resolve();
+
+        assert_eq!(resolved.main_class, "net.minecraft.client.main.Main");
+        assert_eq!(resolved.library, ["current.jar"]);
+        assert_eq!(resolved.java_flag, ["-Dcurrent"]);
+        assert_eq!(resolved.mc_flag, ["--version", "1.20.1"]);
+    }
+
+    #[test]
+    fn java_runtime_component() {
+        let version = serde_json::from_str::<McVersionExt>(&version_json()).unwrap();
+
+        let java = version.java_version.as_ref().unwrap();
+        assert_eq!(java.runtime_component(), "java-runtime-gamma");
+        assert_eq!(
+            version.resolve().java_runtime.as_deref(),
+            Some("java-runtime-gamma")
+        );
+
+        // NeoForge only gives the major version
+        let java = serde_json::from_str::<JavaVersionExt>(r#"{ "majorVersion": 21 }"#).unwrap();
+        assert_eq!(java.runtime_component(), "java-runtime-delta");
+        let java = serde_json::from_str::<JavaVersionExt>(r#"{ "majorVersion": 8 }"#).unwrap();
+        assert_eq!(java.runtime_component(), "jre-legacy");
+    }
+}
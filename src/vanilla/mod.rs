@@ -2,62 +2,180 @@ mod meta;
 mod prelude;
 mod rule;
 mod server;
+mod update;
 
 use std::{
     collections::{HashMap, HashSet},
-    env::consts::OS,
+    env::consts::{ARCH, OS},
+    io::ErrorKind,
     iter::once,
-    path::PathBuf,
-    sync::OnceLock,
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
 use crate::{
     Artifact, Checksum, Creeper, Id, Install,
     builtin::SyncBuiltinIndex,
+    http::{FetchOption, fetch_json, fetch_json_raw},
     index::{Index, VersionRev, independent_index},
+    path::creeper_cache_dir,
+    util::{check_relative, write_atomic},
+    zip::zip_entries,
 };
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use creeper_maven_coord::MavenCoord;
 use mc_launchermeta::{
-    VERSION_MANIFEST_URL,
+    VERSION_MANIFEST_URL, VersionKind,
     version::{
         Version as McVersion,
-        library::{Artifact as McArtifact, Library},
+        library::{Artifact as McArtifact, Library, Natives},
     },
     version_manifest::Manifest,
 };
 use reqwest::Client;
 use semver::{Version, VersionReq};
-use tokio::sync::RwLock;
+use tokio::{
+    fs::{metadata, read_to_string, try_exists},
+    sync::{Mutex, OnceCell},
+};
 use tracing::{debug, info, trace};
 
 pub use prelude::*;
 
+/// Whether a library classifier applies to the current platform, e.g. `natives-macos-arm64`.
+///
+/// Classifiers without an architecture suffix apply to any architecture of their OS.
+/// Unknown classifiers, e.g. `sources`, never apply.
 pub fn check_class(class: &str) -> bool {
-    match class {
-        "natives-linux" => OS == "linux",
-        "natives-windows" => OS == "windows",
-        "natives-macos" | "natives-osx" => OS == "macos",
-        c => todo!("unknown classifier {c}"),
+    let Some(native) = class.strip_prefix("natives-") else {
+        trace!("ignoring unknown classifier {class}");
+        return false;
+    };
+
+    let (os, arch) = match native.split_once('-') {
+        Some((os, arch)) => (os, Some(arch)),
+        None => (native, None),
+    };
+
+    let os = match os {
+        "linux" => "linux",
+        "windows" => "windows",
+        "macos" | "osx" => "macos",
+        _ => {
+            trace!("ignoring classifier {class} of unknown OS");
+            return false;
+        }
+    };
+
+    let arch = match arch {
+        None => return os == OS,
+        Some("64" | "x64" | "x86_64" | "amd64") => "x86_64",
+        Some("32" | "x86" | "i386") => "x86",
+        Some("arm64" | "aarch64" | "aarch_64") => "aarch64",
+        Some("arm32" | "arm") => "arm",
+        Some(_) => {
+            trace!("ignoring classifier {class} of unknown architecture");
+            return false;
+        }
+    };
+
+    os == OS && arch == ARCH
+}
+
+/// The classifier of the native libraries for the current platform, e.g. `natives-windows-64`.
+fn native_class(natives: &Natives) -> Option<String> {
+    let class = match OS {
+        "linux" => natives.linux.as_ref(),
+        "windows" => natives.windows.as_ref(),
+        "macos" => natives.osx.as_ref(),
+        _ => None,
+    }?;
+
+    let arch = if cfg!(target_pointer_width = "64") {
+        "64"
+    } else {
+        "32"
+    };
+
+    Some(class.replace("${arch}", arch))
+}
+
+/// Whether an entry of a native jar is excluded from extraction by the `extract.exclude` rules.
+///
+/// Rules are usually directory prefixes like `META-INF/`, which exclude everything below.
+/// Rules with wildcards are globs matched against the whole path instead, e.g. `*.sha1` or `META-INF/*.SF`,
+/// where `*` matches any sequence of characters, including `/`, and `?` any single character.
+fn is_excluded(path: &Path, exclude: &[String]) -> bool {
+    let path = path.to_string_lossy();
+
+    exclude.iter().any(|rule| {
+        if rule.contains(['*', '?']) {
+            glob_match(rule.as_bytes(), path.as_bytes())
+        } else {
+            path.starts_with(rule.as_str())
+        }
+    })
+}
+
+/// Match `text` against the glob `pattern` as described in [`is_excluded`].
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    // position of the last `*` in the pattern and the text it is matched from, for backtracking
+    let mut star = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // let the last `*` swallow one more character
+                Some((sp, st)) => {
+                    star = Some((sp, st + 1));
+                    p = sp + 1;
+                    t = st + 1;
+                }
+                None => return false,
+            },
+        }
     }
+
+    pattern[p..].iter().all(|&c| c == b'*')
 }
 
 pub struct VanillaManager {
     http: Client,
-    manifest: OnceLock<Manifest>,
-    version: RwLock<HashMap<Version, McVersion>>,
+    fetch: FetchOption,
+    manifest: OnceCell<Manifest>,
+    version: Mutex<HashMap<Version, Arc<OnceCell<McVersion>>>>,
 }
 
 impl VanillaManager {
-    pub fn new(http: Client) -> Self {
+    pub fn new(http: Client, fetch: FetchOption) -> Self {
         Self {
             http,
-            manifest: OnceLock::new(),
-            version: RwLock::new(HashMap::new()),
+            fetch,
+            manifest: OnceCell::new(),
+            version: Mutex::new(HashMap::new()),
         }
     }
+
+    /// Get the version manifest, calling `fetch` for it unless already fetched.
+    ///
+    /// Concurrent first calls share the fetch of the first one.
+    async fn manifest_or<F>(&self, fetch: impl FnOnce() -> F) -> anyhow::Result<&Manifest>
+    where
+        F: Future<Output = anyhow::Result<Manifest>>,
+    {
+        self.manifest.get_or_try_init(fetch).await
+    }
 }
 
 impl SyncBuiltinIndex for VanillaManager {
@@ -68,10 +186,8 @@ impl SyncBuiltinIndex for VanillaManager {
     async fn sync_index(&self) -> anyhow::Result<Index> {
         info!("updating vanilla metadata");
 
-        let req = self.http.get(VERSION_MANIFEST_URL).build()?;
-        let res = self.http.execute(req).await?;
-
-        let manifest = res.json::<Manifest>().await?;
+        let manifest =
+            fetch_json::<Manifest>(&self.http, &self.fetch, VERSION_MANIFEST_URL, None).await?;
 
         let mut versions = vec![];
 
@@ -128,25 +244,162 @@ impl Creeper {
         Ok(map)
     }
 
+    /// Extract the native libraries of the current platform from the classifier jars of `lib` into storage,
+    /// returning them by their path in the natives directory.
+    ///
+    /// Only libraries listing `natives` are extracted, skipping entries under the prefixes in their `extract.exclude`.
+    /// Newer versions ship natives as regular libraries instead, which LWJGL loads from the classpath.
+    pub(crate) async fn vanilla_native(
+        &self,
+        lib: impl IntoIterator<Item = Library>,
+    ) -> anyhow::Result<HashMap<PathBuf, Artifact>> {
+        let rule = RuleChecker::default();
+
+        let mut native = HashMap::new();
+
+        for lib in lib {
+            if !lib.rules.iter().flatten().all(rule.checker()) {
+                continue;
+            }
+
+            let Some(class) = lib.natives.as_ref().and_then(native_class) else {
+                continue;
+            };
+
+            let Some(art) = lib
+                .downloads
+                .and_then(|d| d.classifiers)
+                .and_then(|mut c| c.remove(&class))
+            else {
+                debug!("missing {class} of {}", lib.name);
+                continue;
+            };
+
+            let exclude = lib
+                .extract
+                .and_then(|mut e| e.remove("exclude"))
+                .unwrap_or_default();
+
+            let jar = self
+                .download(
+                    format!("{}:{class}", lib.name),
+                    art.url,
+                    Some(art.size),
+                    once(Checksum::sha1(art.sha1)),
+                )
+                .await?;
+            let jar = self.retrieve_artifact(&jar).await?;
+
+            for (path, dir) in zip_entries(&jar).await? {
+                check_relative(&path)?;
+
+                if dir || is_excluded(&path, &exclude) {
+                    trace!("not extracting {} from {}", path.display(), lib.name);
+                    continue;
+                }
+
+                let art = self.store_zip_entry(&jar, &path).await?;
+                native.insert(path, art);
+            }
+        }
+
+        debug!("extracted {} native libraries", native.len());
+
+        Ok(native)
+    }
+
+    /// Get the version manifest, which is cached on disk for [`crate::Config::manifest_ttl`].
+    ///
+    /// Concurrent first calls share a single fetch.
     pub async fn vanilla_manifest(&self) -> anyhow::Result<&Manifest> {
-        if let Some(manifest) = self.vanilla.manifest.get() {
-            return Ok(manifest);
+        self.vanilla
+            .manifest_or(|| self.fetch_vanilla_manifest())
+            .await
+    }
+
+    async fn fetch_vanilla_manifest(&self) -> anyhow::Result<Manifest> {
+        let cache = creeper_cache_dir()?
+            .join("vanilla")
+            .join("version_manifest.json");
+
+        let ttl = Duration::from_secs(self.config.manifest_ttl);
+
+        let fresh = manifest_fresh(&cache, ttl).await?;
+
+        let manifest = if try_exists(&cache).await? && (fresh || self.args.offline) {
+            debug!("using cached minecraft version manifest");
+            serde_json::from_str(&read_to_string(&cache).await?)?
+        } else {
+            info!("synchronizing minecraft version manifest");
+
+            let json = fetch_json_raw(&self.http, &self.fetch, VERSION_MANIFEST_URL, None).await?;
+            let manifest = serde_json::from_slice(&json)?;
+            write_atomic(&cache, json).await?;
+
+            manifest
+        };
+
+        Ok(manifest)
+    }
+
+    /// Check that a minecraft version exists in the version manifest,
+    /// failing with similar versions as suggestions otherwise.
+    ///
+    /// This is cheap once the manifest is cached, so it should be called before any heavy work.
+    pub async fn check_vanilla_version(&self, version: &Version) -> anyhow::Result<()> {
+        let manifest = self.vanilla_manifest().await?;
+
+        if manifest.get_version(&version.to_string()).is_some() {
+            return Ok(());
         }
-        info!("synchronizing minecraft version manifest");
 
-        let req = self.http.get(VERSION_MANIFEST_URL).build()?;
-        let res = self.http.execute(req).await?;
+        let similar = similar_mc_version(manifest, version);
 
-        let manifest = res.json().await?;
+        if similar.is_empty() {
+            bail!("minecraft version {version} not found in manifest");
+        }
 
-        Ok(self.vanilla.manifest.get_or_init(|| manifest))
+        bail!(
+            "minecraft version {version} not found in manifest, did you mean {}?",
+            similar.join(", ")
+        )
     }
 
+    /// Check that some minecraft version in the manifest matches `req`,
+    /// failing with similar versions as suggestions otherwise.
+    ///
+    /// Like [`Self::check_vanilla_version`], this should be called before any heavy work.
+    pub async fn check_vanilla_version_req(&self, req: &VersionReq) -> anyhow::Result<()> {
+        let manifest = self.vanilla_manifest().await?;
+
+        check_mc_version_req(manifest, req)
+    }
+
+    /// Get the metadata of a minecraft version.
+    ///
+    /// Released metadata never changes, so it is cached on disk permanently,
+    /// keyed by the SHA-1 in its URL and verified against it on each read.
+    /// Concurrent first calls for the same version share a single fetch.
     pub async fn vanilla_version(&self, version: Version) -> anyhow::Result<McVersion> {
-        if let Some(mc_version) = self.vanilla.version.read().await.get(&version) {
-            return Ok(mc_version.clone());
-        }
-        info!("synchronizing minecraft {version} version metadata");
+        let cell = self
+            .vanilla
+            .version
+            .lock()
+            .await
+            .entry(version.clone())
+            .or_default()
+            .clone();
+
+        let mc_version = cell
+            .get_or_try_init(|| self.fetch_vanilla_version(&version))
+            .await?;
+
+        Ok(mc_version.clone())
+    }
+
+    async fn fetch_vanilla_version(&self, version: &Version) -> anyhow::Result<McVersion> {
+        self.check_vanilla_version(version).await?;
+
         let manifest = self.vanilla_manifest().await?;
         let url = manifest
             .get_version(&version.to_string())
@@ -154,15 +407,30 @@ impl Creeper {
             .url
             .to_owned();
 
-        let req = self.http.get(url).build()?;
-        let res = self.http.execute(req).await?;
-        let mc_version = res.json::<McVersion>().await?;
+        let sha1 = version_sha1(&url);
+
+        let cache = creeper_cache_dir()?
+            .join("vanilla")
+            .join("version")
+            .join(match &sha1 {
+                Some(sha1) => format!("{version}-{}.json", sha1.hex_hash),
+                None => format!("{version}.json"),
+            });
+
+        let cached = version_cached(&cache, sha1.as_ref()).await?;
+
+        if !cached {
+            info!("synchronizing minecraft {version} version metadata");
+
+            let sha1 = sha1.as_ref().map(|sha1| sha1.hex_hash.as_str());
+            let json = fetch_json_raw(&self.http, &self.fetch, &url, sha1).await?;
+            write_atomic(&cache, json).await?;
+        } else {
+            debug!("using cached minecraft {version} version metadata");
+        }
+
+        let mc_version = serde_json::from_str::<McVersion>(&read_to_string(&cache).await?)?;
 
-        self.vanilla
-            .version
-            .write()
-            .await
-            .insert(version, mc_version.clone());
         Ok(mc_version)
     }
 
@@ -181,6 +449,103 @@ impl Creeper {
     }
 }
 
+/// Fail unless some version in `manifest` matches `req`, suggesting the releases close to it.
+fn check_mc_version_req(manifest: &Manifest, req: &VersionReq) -> anyhow::Result<()> {
+    let matched = manifest
+        .versions
+        .iter()
+        .filter_map(|v| v.id.parse::<Version>().ok())
+        .any(|v| req.matches(&v));
+
+    if matched {
+        return Ok(());
+    }
+
+    // suggest versions close to the first bound of the requirement
+    let similar = match req.comparators.first() {
+        Some(c) => similar_mc_version(
+            manifest,
+            &Version::new(c.major, c.minor.unwrap_or(0), c.patch.unwrap_or(0)),
+        ),
+        None => vec![],
+    };
+
+    if similar.is_empty() {
+        bail!("no minecraft version matching {req} found in manifest");
+    }
+
+    bail!(
+        "no minecraft version matching {req} found in manifest, did you mean {}?",
+        similar.join(", ")
+    )
+}
+
+/// Releases in the manifest close to `version`, i.e. of the same minor version,
+/// or of the adjacent minor versions if there is none.
+///
+/// At most 5 versions are returned, the latest first.
+fn similar_mc_version<'a>(manifest: &'a Manifest, version: &Version) -> Vec<&'a str> {
+    const MAX: usize = 5;
+
+    let release = manifest
+        .versions
+        .iter()
+        .filter(|v| v.kind == VersionKind::Release)
+        .filter_map(|v| Some((v.id.as_str(), lenient_mc_version(&v.id)?)))
+        .filter(|(_, v)| v.major == version.major)
+        .collect::<Vec<_>>();
+
+    let same_minor = release
+        .iter()
+        .filter(|(_, v)| v.minor == version.minor)
+        .map(|(id, _)| *id)
+        .take(MAX)
+        .collect::<Vec<_>>();
+
+    if !same_minor.is_empty() {
+        return same_minor;
+    }
+
+    release
+        .iter()
+        .filter(|(_, v)| v.minor.abs_diff(version.minor) == 1)
+        .map(|(id, _)| *id)
+        .take(MAX)
+        .collect()
+}
+
+/// Parse a release id like `1.20` or `1.20.1` by padding the missing patch version.
+fn lenient_mc_version(id: &str) -> Option<Version> {
+    id.parse().or_else(|_| format!("{id}.0").parse()).ok()
+}
+
+/// Whether the version manifest cached at `cache` is younger than `ttl`.
+async fn manifest_fresh(cache: &Path, ttl: Duration) -> anyhow::Result<bool> {
+    match metadata(cache).await {
+        Ok(meta) => Ok(meta.modified()?.elapsed().is_ok_and(|age| age < ttl)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e)?,
+    }
+}
+
+/// The SHA-1 of version metadata given in its URL,
+/// e.g. `https://piston-meta.mojang.com/v1/packages/<sha1>/<version>.json`.
+fn version_sha1(url: &str) -> Option<Checksum> {
+    url.rsplit('/')
+        .nth(1)
+        .filter(|s| s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|s| Checksum::sha1(s.to_owned()))
+}
+
+/// Whether version metadata cached at `cache` can be used regardless of its age,
+/// i.e. it matches `sha1`, without which it is never reused.
+async fn version_cached(cache: &Path, sha1: Option<&Checksum>) -> anyhow::Result<bool> {
+    match sha1 {
+        Some(sha1) => Ok(try_exists(cache).await? && sha1.check(cache).await?),
+        None => Ok(false),
+    }
+}
+
 fn filter_lib(lib: impl IntoIterator<Item = Library>) -> Vec<McArtifact> {
     let rule = RuleChecker::default();
 
@@ -245,3 +610,148 @@ pub fn mc_java_req(version: &Version) -> VersionReq {
         _ => ">=1.5.0".parse().unwrap(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mc_launchermeta::version_manifest::{Latest, Version as ManifestVersion};
+
+    use super::*;
+
+    fn manifest(ids: &[&str]) -> Manifest {
+        Manifest {
+            latest: Latest {
+                release: ids[0].into(),
+                snapshot: ids[0].into(),
+            },
+            versions: ids
+                .iter()
+                .map(|id| ManifestVersion {
+                    id: id.to_string(),
+                    url: String::new(),
+                    time: String::new(),
+                    release_time: String::new(),
+                    kind: VersionKind::Release,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn version_req_typo() {
+        let manifest = manifest(&["1.21.1", "1.21", "1.20.6", "1.20.1", "1.20"]);
+        let check = |req: &str| check_mc_version_req(&manifest, &req.parse().unwrap());
+
+        check("=1.20.1").unwrap();
+        check("^1.20").unwrap();
+        check("~1.21.1").unwrap();
+        check("*").unwrap();
+
+        // not only exact requirements are checked
+        for req in ["=1.20.11", "~1.20.7", "=1.19", ">=1.22"] {
+            assert!(check(req).is_err(), "{req} should not match");
+        }
+
+        let err = check("=1.20.11").unwrap_err().to_string();
+        assert!(err.ends_with("did you mean 1.20.6, 1.20.1, 1.20?"), "{err}");
+
+        let err = check("=1.19").unwrap_err().to_string();
+        assert!(err.ends_with("did you mean 1.20.6, 1.20.1, 1.20?"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn cache_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let ttl = Duration::from_secs(3600);
+        let old = std::time::SystemTime::now() - Duration::from_secs(365 * 24 * 3600);
+
+        let manifest = dir.path().join("version_manifest.json");
+        assert!(!manifest_fresh(&manifest, ttl).await.unwrap());
+        std::fs::write(&manifest, "{}").unwrap();
+        assert!(manifest_fresh(&manifest, ttl).await.unwrap());
+
+        // the manifest is refetched after its TTL
+        std::fs::File::options()
+            .write(true)
+            .open(&manifest)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert!(!manifest_fresh(&manifest, ttl).await.unwrap());
+
+        let version = dir.path().join("1.20.1.json");
+        std::fs::write(&version, "{}").unwrap();
+        let hash = crate::checksum::sha1(&version).await.unwrap();
+        let url = format!("https://piston-meta.mojang.com/v1/packages/{hash}/1.20.1.json");
+        let sha1 = version_sha1(&url).unwrap();
+        assert_eq!(sha1.hex_hash, hash);
+
+        // while version metadata is served from cache however old it is
+        std::fs::File::options()
+            .write(true)
+            .open(&version)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert!(version_cached(&version, Some(&sha1)).await.unwrap());
+
+        std::fs::write(&version, "{ }").unwrap();
+        assert!(!version_cached(&version, Some(&sha1)).await.unwrap());
+        assert!(!version_cached(&version, None).await.unwrap());
+        assert!(version_sha1("https://example.com/1.20.1.json").is_none());
+    }
+
+    #[tokio::test]
+    async fn manifest_fetched_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/version_manifest.json",
+            listener.local_addr().unwrap()
+        );
+        let hit = Arc::new(AtomicUsize::new(0));
+
+        let counter = hit.clone();
+        tokio::spawn(async move {
+            let body = r#"{"latest":{"release":"1.20.1","snapshot":"1.20.1"},"versions":[]}"#;
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let mut req = vec![];
+                let mut buf = [0; 1024];
+                while !req.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                }
+
+                // keep the first fetch in flight while the second call comes in
+                tokio::time::sleep(Duration::from_millis(100)).await;
+
+                let res = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(res.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        let vanilla = VanillaManager::new(Client::new(), FetchOption::default());
+        let fetch = || async {
+            let json = fetch_json_raw(&vanilla.http, &vanilla.fetch, &url, None).await?;
+            Ok(serde_json::from_slice::<Manifest>(&json)?)
+        };
+
+        let (a, b) = tokio::join!(vanilla.manifest_or(fetch), vanilla.manifest_or(fetch));
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_eq!(a.latest.release, "1.20.1");
+        assert!(std::ptr::eq(a, b));
+        assert_eq!(hit.load(Ordering::SeqCst), 1);
+    }
+}
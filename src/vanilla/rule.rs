@@ -1,9 +1,52 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     env::consts::{ARCH, OS},
+    process::Command,
+    sync::OnceLock,
 };
 
-use mc_launchermeta::version::rule::{Os, Rule};
+use mc_launchermeta::version::rule::{Os, Rule, RuleAction};
+use regex::Regex;
+use tracing::{debug, warn};
+
+/// Version of the running operating system as the vanilla launcher reports it,
+/// e.g. `10.0.19045` on Windows, `14.4.1` on macOS and the kernel release on Linux.
+///
+/// This is detected once and `None` if it cannot be.
+fn os_version() -> Option<&'static str> {
+    static VERSION: OnceLock<Option<String>> = OnceLock::new();
+
+    VERSION
+        .get_or_init(|| {
+            let version = detect_os_version();
+            debug!("detected OS version {version:?}");
+            version
+        })
+        .as_deref()
+}
+
+fn detect_os_version() -> Option<String> {
+    let output = |cmd: &str, args: &[&str]| {
+        let out = Command::new(cmd).args(args).output().ok()?;
+        out.status
+            .success()
+            .then(|| String::from_utf8_lossy(&out.stdout).trim().to_owned())
+    };
+
+    match OS {
+        "linux" => std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .ok()
+            .map(|s| s.trim().to_owned()),
+        "macos" => output("sw_vers", &["-productVersion"]),
+        // e.g. `Microsoft Windows [Version 10.0.19045.4291]`
+        "windows" => {
+            let ver = output("cmd", &["/C", "ver"])?;
+            let ver = ver.split_once("Version ")?.1.trim_end_matches(']');
+            Some(ver.to_owned())
+        }
+        _ => output("uname", &["-r"]),
+    }
+}
 
 #[derive(Default)]
 pub struct RuleChecker {
@@ -11,6 +54,11 @@ pub struct RuleChecker {
 }
 
 impl RuleChecker {
+    /// A checker with the given launcher features enabled or disabled, others being disabled.
+    pub fn with_feature(feature: HashMap<String, bool>) -> Self {
+        Self { feature }
+    }
+
     pub fn checker(&self) -> impl Fn(&Rule) -> bool {
         move |rule| self.check(rule)
     }
@@ -18,18 +66,41 @@ impl RuleChecker {
     pub fn check(&self, rule: &Rule) -> bool {
         let os = rule.os.as_ref().is_none_or(Self::check_os);
 
-        let feature = rule.features.iter().all(|(k, v)| {
+        let feature = self.check_feature(&rule.features);
+
+        let apply = os && feature;
+
+        match rule.action {
+            RuleAction::Allow => apply,
+            RuleAction::Disallow => !apply,
+        }
+    }
+
+    /// Whether each feature is enabled or disabled as required.
+    pub fn check_feature(&self, feature: &BTreeMap<String, bool>) -> bool {
+        feature.iter().all(|(k, v)| {
             let enable = self.feature.get(k).unwrap_or(&false);
 
             enable == v
-        });
+        })
+    }
 
-        let apply = os && feature;
+    /// The features required by `rules` if they only gate on features on the current platform,
+    /// so that they can be checked when launching instead of when installing.
+    ///
+    /// Returns `None` if `rules` involve no features, disallow anything, or exclude the current platform.
+    pub fn feature_only(rules: &[Rule]) -> Option<BTreeMap<String, bool>> {
+        let mut feature = BTreeMap::new();
 
-        match rule.action {
-            mc_launchermeta::version::rule::RuleAction::Allow => apply,
-            mc_launchermeta::version::rule::RuleAction::Disallow => !apply,
+        for rule in rules {
+            if rule.action != RuleAction::Allow || !rule.os.as_ref().is_none_or(Self::check_os) {
+                return None;
+            }
+
+            feature.extend(rule.features.clone());
         }
+
+        (!feature.is_empty()).then_some(feature)
     }
 
     pub fn check_os(os: &Os) -> bool {
@@ -46,8 +117,64 @@ impl RuleChecker {
         let version = os
             .version
             .as_ref()
-            .is_none_or(|_| todo!("does not support checking OS version"));
+            .is_none_or(|x| Self::check_os_version(x));
 
         name && arch && version
     }
+
+    /// Whether the OS version matches the regular expression `pattern`.
+    ///
+    /// Invalid patterns and undetectable versions never match.
+    pub fn check_os_version(pattern: &str) -> bool {
+        let re = match Regex::new(pattern) {
+            Ok(re) => re,
+            Err(e) => {
+                warn!("ignoring rule with invalid OS version pattern {pattern}: {e}");
+                return false;
+            }
+        };
+
+        os_version().is_some_and(|version| re.is_match(version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(json: &str) -> Vec<Rule> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn feature_gated() {
+        let resolution =
+            rules(r#"[{ "action": "allow", "features": { "has_custom_resolution": true } }]"#);
+
+        let feature = RuleChecker::feature_only(&resolution).unwrap();
+        assert_eq!(feature, [("has_custom_resolution".into(), true)].into());
+
+        assert!(!RuleChecker::default().check_feature(&feature));
+        let enabled = RuleChecker::with_feature([("has_custom_resolution".into(), true)].into());
+        assert!(enabled.check_feature(&feature));
+
+        // requiring a feature disabled is met by leaving it out
+        let demo = [("is_demo_user".into(), false)].into();
+        assert!(RuleChecker::default().check_feature(&demo));
+        assert!(
+            !RuleChecker::with_feature([("is_demo_user".into(), true)].into()).check_feature(&demo)
+        );
+    }
+
+    #[test]
+    fn feature_only_rules() {
+        assert_eq!(RuleChecker::feature_only(&[]), None);
+
+        // resolved when installing instead
+        let os = rules(r#"[{ "action": "allow", "os": { "arch": "x86" } }]"#);
+        assert_eq!(RuleChecker::feature_only(&os), None);
+
+        let disallow = rules(r#"[{ "action": "disallow", "features": { "is_demo_user": true } }]"#);
+        assert_eq!(RuleChecker::feature_only(&disallow), None);
+    }
 }
@@ -7,17 +7,21 @@ use semver::Version;
 use tracing::{debug, trace};
 
 use crate::{
-    Checksum, Creeper, Id, Install, VersionRev, builtin::SyncBuiltinIndex,
-    index::independent_index, jar::jar_main_class,
+    Checksum, Creeper, Id, Install, VersionRev,
+    builtin::SyncBuiltinIndex,
+    http::{FetchOption, fetch_json},
+    index::independent_index,
+    jar::jar_main_class,
 };
 
 pub struct VanillaServerManager {
     http: Client,
+    fetch: FetchOption,
 }
 
 impl VanillaServerManager {
-    pub fn new(http: Client) -> Self {
-        Self { http }
+    pub fn new(http: Client, fetch: FetchOption) -> Self {
+        Self { http, fetch }
     }
 }
 
@@ -27,14 +31,8 @@ impl SyncBuiltinIndex for VanillaServerManager {
     }
 
     async fn sync_index(&self) -> anyhow::Result<crate::index::Index> {
-        let manifest = self
-            .http
-            .get(VERSION_MANIFEST_URL)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<Manifest>()
-            .await?;
+        let manifest =
+            fetch_json::<Manifest>(&self.http, &self.fetch, VERSION_MANIFEST_URL, None).await?;
 
         let mut versions = vec![];
 
@@ -95,6 +93,8 @@ impl Creeper {
 
             mc_flag: vec!["nogui".into()],
 
+            server: true,
+
             ..Default::default()
         };
 
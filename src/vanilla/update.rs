@@ -0,0 +1,110 @@
+use std::io::ErrorKind;
+
+use anyhow::{anyhow, bail};
+use mc_launchermeta::{
+    VERSION_MANIFEST_URL,
+    version_manifest::{Latest, Manifest},
+};
+use reqwest::{
+    StatusCode,
+    header::{ETAG, IF_NONE_MATCH},
+};
+use serde::{Deserialize, Serialize};
+use tokio::fs::read_to_string;
+use tracing::debug;
+
+use crate::{Creeper, path::creeper_cache_dir, util::write_atomic};
+
+/// The latest versions seen by the last check, persisted across runs.
+#[derive(Clone, Serialize, Deserialize)]
+struct LatestState {
+    /// Entity tag of the version manifest the latest versions were read from.
+    etag: Option<String>,
+    latest: Latest,
+}
+
+/// Result of [`Creeper::check_vanilla_update`].
+#[derive(Clone, Debug)]
+pub struct VanillaUpdate {
+    /// The latest versions as of the previous check, `None` on the first check.
+    pub previous: Option<Latest>,
+    pub latest: Latest,
+}
+
+impl VanillaUpdate {
+    /// The new latest release, if it changed since the previous check.
+    pub fn new_release(&self) -> Option<&str> {
+        let previous = self.previous.as_ref()?;
+        (previous.release != self.latest.release).then_some(&self.latest.release)
+    }
+
+    /// The new latest snapshot, if it changed since the previous check.
+    pub fn new_snapshot(&self) -> Option<&str> {
+        let previous = self.previous.as_ref()?;
+        (previous.snapshot != self.latest.snapshot).then_some(&self.latest.snapshot)
+    }
+}
+
+impl Creeper {
+    /// Check whether the latest release or snapshot changed since the last check.
+    ///
+    /// The version manifest is requested conditionally on its entity tag,
+    /// so nothing but the headers is transferred unless it changed.
+    /// A changed manifest also refreshes the one cached for [`Self::vanilla_manifest`].
+    pub async fn check_vanilla_update(&self) -> anyhow::Result<VanillaUpdate> {
+        if self.args.offline {
+            bail!("cannot check for updates in offline mode");
+        }
+
+        let dir = creeper_cache_dir()?.join("vanilla");
+        let path = dir.join("latest.json");
+
+        let previous = match read_to_string(&path).await {
+            Ok(json) => Some(serde_json::from_str::<LatestState>(&json)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => Err(e)?,
+        };
+
+        let mut req = self.http.get(&*self.fetch.mirror(VERSION_MANIFEST_URL));
+        if let Some(etag) = previous.as_ref().and_then(|s| s.etag.as_ref()) {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+
+        let res = self.http.execute(req.build()?).await?;
+
+        if res.status() == StatusCode::NOT_MODIFIED
+            && let Some(previous) = previous
+        {
+            debug!("minecraft version manifest not modified since last check");
+            return Ok(VanillaUpdate {
+                previous: Some(previous.latest.clone()),
+                latest: previous.latest,
+            });
+        }
+
+        let res = res.error_for_status()?;
+
+        let etag = res
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+
+        let json = res.bytes().await?;
+        let manifest = serde_json::from_slice::<Manifest>(&json)
+            .map_err(|e| anyhow!("invalid minecraft version manifest: {e}"))?;
+
+        write_atomic(dir.join("version_manifest.json"), &json).await?;
+
+        let state = LatestState {
+            etag,
+            latest: manifest.latest,
+        };
+        write_atomic(&path, serde_json::to_vec(&state)?).await?;
+
+        Ok(VanillaUpdate {
+            previous: previous.map(|s| s.latest),
+            latest: state.latest,
+        })
+    }
+}
@@ -5,15 +5,13 @@ use inquire::Password;
 use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_inline_default::serde_inline_default;
-use tokio::{
-    fs::{create_dir_all, read_to_string, write},
-    sync::RwLock,
-};
+use tokio::{fs::read_to_string, sync::RwLock};
 use tracing::{debug, info, warn};
 use url::Url;
 use uuid::Uuid;
 
 use crate::path::creeper_data_dir;
+use crate::util::write_private;
 
 pub struct YggdrasilClient {
     pub server: Url,
@@ -105,11 +103,7 @@ impl YggdrasilClient {
 
         let json = serde_json::to_string(&storage)?;
 
-        if let Some(parent) = path.parent() {
-            create_dir_all(parent).await?;
-        }
-
-        write(path, json).await?;
+        write_private(&path, json).await?;
 
         Ok(())
     }
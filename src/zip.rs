@@ -1,29 +1,42 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::anyhow;
-use async_zip::base::read::seek::ZipFileReader;
+use async_zip::{ZipEntry, tokio::read::seek::ZipFileReader};
 use tokio::{
-    fs::{File, create_dir_all},
-    io::{BufReader, copy},
+    fs::{File, create_dir_all, read_to_string, try_exists},
+    io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter, copy},
 };
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 
-/// Extract a text file from a zip archive `zip_file` at the path `path`.
+use crate::{
+    Artifact, Creeper,
+    path::creeper_extract_cache_dir,
+    util::{check_relative, set_executable, summarize, write_atomic},
+};
+
+/// Size of the buffer used when streaming zip entries.
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Whether a zip entry is marked executable, which only archives created on unix record.
+fn is_executable(entry: &ZipEntry) -> bool {
+    entry
+        .unix_permissions()
+        .is_some_and(|mode| mode & 0o111 != 0)
+}
+
+/// Open a zip archive and locate the entry at `path`.
 ///
 /// # Panics
 ///
 /// The function panics unless `path` is valid UTF-8.
-pub async fn extract_zip(
-    zip_file: impl AsRef<Path>,
-    path: impl AsRef<Path>,
-) -> anyhow::Result<String> {
-    let zip_file = zip_file.as_ref();
-    let path = path.as_ref();
-
+async fn open_entry(
+    zip_file: &Path,
+    path: &Path,
+) -> anyhow::Result<(ZipFileReader<BufReader<File>>, usize)> {
     let zip = File::open(&zip_file).await?;
     let read = BufReader::new(zip);
 
-    let mut zip = ZipFileReader::with_tokio(read).await?;
+    let zip = ZipFileReader::with_tokio(read).await?;
 
     let idx = zip
         .file()
@@ -40,6 +53,23 @@ pub async fn extract_zip(
             zip_file.display()
         ))?;
 
+    Ok((zip, idx))
+}
+
+/// Extract a text file from a zip archive `zip_file` at the path `path`.
+///
+/// # Panics
+///
+/// The function panics unless `path` is valid UTF-8.
+pub async fn extract_zip(
+    zip_file: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<String> {
+    let zip_file = zip_file.as_ref();
+    let path = path.as_ref();
+
+    let (mut zip, idx) = open_entry(zip_file, path).await?;
+
     let mut read = zip.reader_with_entry(idx).await?;
 
     let mut buf = String::new();
@@ -62,33 +92,321 @@ pub async fn extract_zip_to(
     let zip_file = zip_file.as_ref();
     let path = path.as_ref();
 
-    let zip = File::open(&zip_file).await?;
-    let read = BufReader::new(zip);
+    let (mut zip, idx) = open_entry(zip_file, path).await?;
 
-    let mut zip = ZipFileReader::with_tokio(read).await?;
+    let mut read = zip.reader_with_entry(idx).await?.compat();
 
-    let idx = zip
+    if let Some(parent) = dst.as_ref().parent() {
+        create_dir_all(parent).await?;
+    }
+
+    let mut write = BufWriter::with_capacity(BUF_SIZE, File::create(dst).await?);
+    copy(&mut read, &mut write).await?;
+    write.shutdown().await?;
+
+    Ok(())
+}
+
+/// Paths of the entries of a zip archive `zip_file`, and whether each is a directory.
+pub async fn zip_entries(zip_file: impl AsRef<Path>) -> anyhow::Result<Vec<(PathBuf, bool)>> {
+    let zip = File::open(zip_file.as_ref()).await?;
+    let zip = ZipFileReader::with_tokio(BufReader::new(zip)).await?;
+
+    zip.file()
+        .entries()
+        .iter()
+        .map(|e| Ok((PathBuf::from(e.filename().as_str()?), e.dir()?)))
+        .collect()
+}
+
+/// Expand every entry of a zip archive `zip_file` into the directory `dst`.
+///
+/// Entries whose path would escape `dst` are rejected.
+/// Files marked executable in the archive are made executable on unix.
+/// Only the archive and the entry being written are kept open at a time.
+pub async fn extract_zip_all(
+    zip_file: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let zip_file = zip_file.as_ref();
+    let dst = dst.as_ref();
+
+    let zip = File::open(zip_file).await?;
+    let mut zip = ZipFileReader::with_tokio(BufReader::new(zip)).await?;
+
+    let entries = zip
         .file()
         .entries()
         .iter()
-        .position(|e| {
-            e.filename()
-                .as_str()
-                .is_ok_and(|s| s == path.to_str().unwrap())
+        .map(|e| {
+            Ok((
+                PathBuf::from(e.filename().as_str()?),
+                e.dir()?,
+                is_executable(e),
+            ))
         })
-        .ok_or(anyhow!(
-            "{} not found in {}",
-            path.display(),
-            zip_file.display()
-        ))?;
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
-    let mut read = zip.reader_with_entry(idx).await?.compat();
+    for (idx, (path, dir, exec)) in entries.into_iter().enumerate() {
+        check_relative(&path)?;
 
-    if let Some(parent) = dst.as_ref().parent() {
-        create_dir_all(parent).await?;
-    }
+        let path = dst.join(path);
+
+        if dir {
+            create_dir_all(&path).await?;
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
 
-    copy(&mut read, &mut File::create(dst).await?).await?;
+        let mut read = zip.reader_with_entry(idx).await?.compat();
+
+        let mut write = BufWriter::with_capacity(BUF_SIZE, File::create(&path).await?);
+        copy(&mut read, &mut write).await?;
+        write.shutdown().await?;
+
+        if exec {
+            set_executable(&path).await?;
+        }
+    }
 
     Ok(())
 }
+
+/// Like [`extract_zip_all`], but skip if `stamp` records `id` and every file of the archive is still in `dst`.
+///
+/// `id` identifies the content of the archive, e.g. its hash, and is recorded in `stamp` after expanding.
+/// Files edited after expanding are kept as is.
+/// Returns whether the archive was expanded.
+pub async fn extract_zip_all_once(
+    zip_file: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    stamp: impl AsRef<Path>,
+    id: &str,
+) -> anyhow::Result<bool> {
+    let zip_file = zip_file.as_ref();
+    let dst = dst.as_ref();
+    let stamp = stamp.as_ref();
+
+    if try_exists(stamp).await? && read_to_string(stamp).await? == id {
+        let mut complete = true;
+
+        for (path, _) in zip_entries(zip_file).await? {
+            check_relative(&path)?;
+            if !try_exists(dst.join(path)).await? {
+                complete = false;
+                break;
+            }
+        }
+
+        if complete {
+            return Ok(false);
+        }
+    }
+
+    extract_zip_all(zip_file, dst).await?;
+
+    write_atomic(stamp, id).await?;
+
+    Ok(true)
+}
+
+impl Creeper {
+    /// Store a file from a zip archive `zip_file` at the path `path` as an artifact.
+    ///
+    /// The entry is hashed while being streamed to a temporary file,
+    /// which is then moved into storage, avoiding a second pass over the content.
+    /// The artifact is deployed executable if the entry is marked so, see [`Artifact::executable`].
+    ///
+    /// # Panics
+    ///
+    /// The function panics unless `path` is valid UTF-8.
+    pub async fn store_zip_entry(
+        &self,
+        zip_file: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<Artifact> {
+        let zip_file = zip_file.as_ref();
+        let path = path.as_ref();
+
+        let name = path
+            .file_name()
+            .ok_or(anyhow!("missing filename"))?
+            .display()
+            .to_string();
+
+        let (mut zip, idx) = open_entry(zip_file, path).await?;
+
+        let exec = is_executable(&zip.file().entries()[idx]);
+
+        let mut read = zip.reader_with_entry(idx).await?.compat();
+
+        let tmp = creeper_extract_cache_dir()?.join(summarize(&format!(
+            "{}!{}",
+            zip_file.display(),
+            path.display()
+        )));
+
+        create_dir_all(tmp.parent().unwrap()).await?;
+
+        let mut write = BufWriter::with_capacity(BUF_SIZE, File::create(&tmp).await?);
+        let mut hasher = blake3::Hasher::new();
+        let mut buf = vec![0; BUF_SIZE];
+        let mut len = 0;
+
+        loop {
+            let n = read.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            write.write_all(&buf[..n]).await?;
+            len += n as u64;
+        }
+
+        write.shutdown().await?;
+
+        let b3 = hasher.finalize().to_hex().to_string();
+
+        let art = Artifact {
+            executable: exec,
+            ..Artifact::new(b3, name, None, len)
+        };
+
+        self.store_moved(&tmp, &art).await?;
+
+        Ok(art)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_zip::{Compression, ZipEntryBuilder, base::write::ZipFileWriter};
+
+    async fn write_zip(path: &Path, entry: &[(&str, &str)]) {
+        let mut zip = ZipFileWriter::with_tokio(File::create(path).await.unwrap());
+        for (name, content) in entry {
+            let entry = ZipEntryBuilder::new((*name).into(), Compression::Deflate);
+            zip.write_entry_whole(entry, content.as_bytes())
+                .await
+                .unwrap();
+        }
+        zip.close()
+            .await
+            .unwrap()
+            .into_inner()
+            .shutdown()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn extract_into_subdir() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("bundle.zip");
+        let dst = dir.path().join("game").join("config");
+
+        write_zip(&zip, &[("a.toml", "a"), ("sub/b.toml", "b")]).await;
+
+        extract_zip_all(&zip, &dst).await.unwrap();
+
+        assert_eq!(read_to_string(dst.join("a.toml")).await.unwrap(), "a");
+        assert_eq!(read_to_string(dst.join("sub/b.toml")).await.unwrap(), "b");
+    }
+
+    #[tokio::test]
+    async fn extract_refuse_escape() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("evil.zip");
+        let dst = dir.path().join("game");
+
+        write_zip(&zip, &[("../evil.txt", "evil")]).await;
+
+        assert!(extract_zip_all(&zip, &dst).await.is_err());
+        assert!(!try_exists(dir.path().join("evil.txt")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn extract_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("bundle.zip");
+        let dst = dir.path().join("game").join("config");
+        let stamp = dir.path().join("stamp");
+
+        write_zip(&zip, &[("a.toml", "a"), ("sub/b.toml", "b")]).await;
+
+        assert!(
+            extract_zip_all_once(&zip, &dst, &stamp, "v1")
+                .await
+                .unwrap()
+        );
+
+        // up to date, so that edits are kept
+        std::fs::write(dst.join("a.toml"), "edited").unwrap();
+        assert!(
+            !extract_zip_all_once(&zip, &dst, &stamp, "v1")
+                .await
+                .unwrap()
+        );
+        assert_eq!(read_to_string(dst.join("a.toml")).await.unwrap(), "edited");
+
+        // a missing file is restored
+        std::fs::remove_file(dst.join("sub/b.toml")).unwrap();
+        assert!(
+            extract_zip_all_once(&zip, &dst, &stamp, "v1")
+                .await
+                .unwrap()
+        );
+        assert_eq!(read_to_string(dst.join("sub/b.toml")).await.unwrap(), "b");
+
+        // another archive is expanded again
+        assert!(
+            extract_zip_all_once(&zip, &dst, &stamp, "v2")
+                .await
+                .unwrap()
+        );
+        assert_eq!(read_to_string(dst.join("a.toml")).await.unwrap(), "a");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn extract_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("tools.zip");
+        let dst = dir.path().join("game");
+
+        let mut writer = ZipFileWriter::with_tokio(File::create(&zip).await.unwrap());
+        for (name, mode) in [("tool", 0o755), ("data", 0o644)] {
+            let entry =
+                ZipEntryBuilder::new(name.into(), Compression::Stored).unix_permissions(mode);
+            writer.write_entry_whole(entry, b"").await.unwrap();
+        }
+        writer
+            .close()
+            .await
+            .unwrap()
+            .into_inner()
+            .shutdown()
+            .await
+            .unwrap();
+
+        let (reader, idx) = open_entry(&zip, Path::new("tool")).await.unwrap();
+        assert!(is_executable(&reader.file().entries()[idx]));
+
+        extract_zip_all(&zip, &dst).await.unwrap();
+
+        let mode = |name| {
+            std::fs::metadata(dst.join(name))
+                .unwrap()
+                .permissions()
+                .mode()
+        };
+        assert_ne!(mode("tool") & 0o111, 0);
+        assert_eq!(mode("data") & 0o111, 0);
+    }
+}